@@ -0,0 +1,899 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use tauri::AppHandle;
+
+use super::backup::{progress_reporter, reset_cancellation, timestamp};
+use super::destinations::read_disk_info;
+use super::policy::{require_plain_name, require_root, require_within, FsCommandError, RootKind};
+use super::quarantine::session_backup_path;
+use super::repository::{safe_join, RestoreStats, SnapshotProgress};
+use super::session::collect_session_files;
+use super::snapshots::{new_snapshot_name, SnapshotRetention, PARTIAL_SUFFIX};
+
+/// Folder inside a session backup that holds its archives in archive mode.
+pub const ARCHIVES_DIR: &str = "archives";
+
+/// Volume size when a destination doesn't set one. Stays under FAT32's file
+/// size limit and fits on a single-layer DVD.
+pub const DEFAULT_VOLUME_BYTES: u64 = 4 * 1024 * 1024 * 1024 - 1;
+
+const MIN_VOLUME_BYTES: u64 = 1024 * 1024;
+
+/// Room left on a disk beyond the next volume, for the catalog and the
+/// filesystem's own bookkeeping.
+const SPACE_MARGIN: u64 = 64 * 1024 * 1024;
+
+/// Archives kept when the destination sets no retention. Each one is a full
+/// copy of the session, so keeping all of them would fill the disk.
+pub const DEFAULT_ARCHIVES_KEPT: u32 = 2;
+
+const CANCELLED: &str = "Backup cancelled";
+
+const CATALOG_SUFFIX: &str = ".catalog.json";
+
+const ARCHIVE_VERSION: u32 = 1;
+
+/// Files are copied into volumes through a buffer of this size.
+const COPY_BUFFER: usize = 1024 * 1024;
+
+/// Which volumes an archive was split into and where in them each file lives.
+/// A copy sits next to the volumes, so any disk holding them can be restored from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveCatalog {
+    pub version: u32,
+    pub id: String,
+    pub session_name: String,
+    pub source_path: String,
+    pub source_host: String,
+    pub created_at: String,
+    pub image_count: Option<u32>,
+    pub volume_bytes: u64,
+    pub volumes: Vec<ArchiveVolume>,
+    pub files: Vec<ArchiveFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveVolume {
+    pub number: u32,
+    pub name: String,
+    pub size: u64,
+    /// BLAKE3 hash of the whole volume.
+    pub hash: String,
+    /// The `archives` folder the volume was written to, which names the disk.
+    #[serde(default)]
+    pub archives_dir: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveFile {
+    /// Relative to the session folder, with `/` separators.
+    pub path: String,
+    pub size: u64,
+    pub modified: u64,
+    /// BLAKE3 hash of the file's contents.
+    pub hash: String,
+    /// Where the file's bytes are, in order. A file can run on into the next volume.
+    pub pieces: Vec<ArchivePiece>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivePiece {
+    pub volume: u32,
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArchiveSummary {
+    pub id: String,
+    pub catalog_path: String,
+    pub created_at: String,
+    pub image_count: Option<u32>,
+    pub volumes: u32,
+    pub files: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VolumeStatus {
+    pub number: u32,
+    pub name: String,
+    pub size: u64,
+    /// Where the volume was written, to tell the user which disk holds it.
+    pub archives_dir: Option<String>,
+    /// Where the volume was found, if it was.
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArchiveRestorePlan {
+    pub archive_id: String,
+    /// The volumes the restore reads from, found or not.
+    pub volumes: Vec<VolumeStatus>,
+    pub missing: Vec<u32>,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[tauri::command]
+pub async fn list_archives(
+    destination_path: String,
+    session_name: String,
+) -> Result<Vec<ArchiveSummary>, FsCommandError> {
+    require_root(&destination_path, RootKind::Destination)?;
+    let archives_dir = session_backup_path(&destination_path, &session_name)?.join(ARCHIVES_DIR);
+
+    let archives = tokio::task::spawn_blocking(move || {
+        let mut archives: Vec<ArchiveSummary> = complete_archives(&archives_dir)
+            .into_iter()
+            .map(|(dir, catalog)| ArchiveSummary {
+                catalog_path: catalog_path(&dir, &catalog.id)
+                    .to_string_lossy()
+                    .to_string(),
+                id: catalog.id,
+                created_at: catalog.created_at,
+                image_count: catalog.image_count,
+                volumes: catalog.volumes.len() as u32,
+                files: catalog.files.len() as u64,
+                total_bytes: catalog.files.iter().map(|f| f.size).sum(),
+            })
+            .collect();
+        archives.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        archives
+    })
+    .await
+    .map_err(|e| format!("Failed to list archives: {}", e))?;
+
+    Ok(archives)
+}
+
+/// Works out which volumes restoring `files` (or the whole archive) needs and
+/// looks for each next to the catalog and in `search_paths`, so the user can be
+/// asked for the disks that are missing.
+#[tauri::command]
+pub async fn plan_archive_restore(
+    catalog_path: String,
+    search_paths: Vec<String>,
+    files: Option<Vec<String>>,
+) -> Result<ArchiveRestorePlan, FsCommandError> {
+    let catalog_file = require_within(&catalog_path, &[RootKind::Destination])?;
+    let search_dirs = search_dirs(&catalog_file, &search_paths)?;
+
+    let plan = tokio::task::spawn_blocking(move || {
+        let catalog = read_catalog(&catalog_file)?;
+        let selected = select_files(&catalog, files.as_deref())?;
+        Ok::<_, String>(restore_plan(&catalog, &selected, &search_dirs))
+    })
+    .await
+    .map_err(|e| format!("Failed to plan restore: {}", e))??;
+
+    Ok(plan)
+}
+
+/// Restores `files` (or the whole archive) into `<target_path>/<session name>`,
+/// which must not exist yet. Fails before writing anything if a volume it
+/// needs can't be found. Every file is checked against its hash, and the
+/// folder only appears once all of them are restored. Progress is reported as
+/// `backup-progress` events for `progress_id`; `cancel_backup` stops it.
+#[tauri::command]
+pub async fn restore_archive(
+    app: AppHandle,
+    catalog_path: String,
+    search_paths: Vec<String>,
+    target_path: String,
+    files: Option<Vec<String>>,
+    progress_id: u64,
+) -> Result<RestoreStats, FsCommandError> {
+    info!("Restoring archive {} to {}", catalog_path, target_path);
+    let catalog_file = require_within(&catalog_path, &[RootKind::Destination])?;
+    let search_dirs = search_dirs(&catalog_file, &search_paths)?;
+    let target = require_within(&target_path, &[RootKind::Destination])?;
+
+    reset_cancellation();
+    let mut progress = progress_reporter(app, progress_id);
+    let stats = tokio::task::spawn_blocking(move || {
+        let catalog = read_catalog(&catalog_file)?;
+        let selected = select_files(&catalog, files.as_deref())?;
+        let plan = restore_plan(&catalog, &selected, &search_dirs);
+        if !plan.missing.is_empty() {
+            let names: Vec<String> = plan.missing.iter().map(|n| n.to_string()).collect();
+            return Err(format!(
+                "Missing archive volumes {}; connect the disks that hold them and try again",
+                names.join(", ")
+            ));
+        }
+        let volumes: HashMap<u32, PathBuf> = plan
+            .volumes
+            .into_iter()
+            .filter_map(|v| v.location.map(|l| (v.number, PathBuf::from(l))))
+            .collect();
+
+        let session_target = safe_join(&target, &catalog.session_name)
+            .ok_or_else(|| format!("Invalid session name: {:?}", catalog.session_name))?;
+        if session_target.exists() {
+            return Err(format!(
+                "{:?} already exists; choose another folder to restore into",
+                session_target
+            ));
+        }
+        let partial = target.join(format!("{}{}", catalog.session_name, PARTIAL_SUFFIX));
+        if partial.exists() {
+            std::fs::remove_dir_all(&partial)
+                .map_err(|e| format!("Failed to clear {:?}: {}", partial, e))?;
+        }
+
+        let mut state = SnapshotProgress {
+            bytes_done: 0,
+            total_bytes: plan.bytes,
+            files_done: 0,
+            total_files: selected.len() as u32,
+        };
+        let restored = (|| {
+            for file in &selected {
+                let file_target = safe_join(&partial, &file.path)
+                    .ok_or_else(|| format!("Archive contains an invalid path: {:?}", file.path))?;
+                if let Some(parent) = file_target.parent() {
+                    std::fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+                }
+                extract_file(&volumes, file, &file_target, |bytes| {
+                    state.bytes_done += bytes;
+                    progress(&state)
+                })?;
+                state.files_done += 1;
+            }
+            std::fs::create_dir_all(&partial)
+                .and_then(|_| std::fs::rename(&partial, &session_target))
+                .map_err(|e| format!("Failed to finish {:?}: {}", session_target, e))
+        })();
+        if let Err(e) = restored {
+            let _ = std::fs::remove_dir_all(&partial);
+            return Err(e);
+        }
+
+        Ok(RestoreStats {
+            path: session_target.to_string_lossy().to_string(),
+            files: state.files_done,
+            bytes: state.bytes_done,
+        })
+    })
+    .await
+    .map_err(|e| format!("Restore failed: {}", e))??;
+
+    info!("Restored {} files to {}", stats.files, stats.path);
+    Ok(stats)
+}
+
+/// Packs the files under `roots` into numbered volumes of at most
+/// `volume_bytes`, in a new folder under the first of `locations` (each a
+/// session's `archives` folder, usually on different disks). When a disk has
+/// no room for the next volume, the archive continues in the next location.
+/// Every location used gets a copy of the catalog, and the folders only get
+/// their final names once all volumes are written. `progress` is called as
+/// data is copied and stops the run when it returns false.
+#[allow(clippy::too_many_arguments)]
+pub fn create_archive(
+    locations: &[PathBuf],
+    source: &Path,
+    roots: &[PathBuf],
+    session_name: &str,
+    image_count: Option<u32>,
+    volume_bytes: u64,
+    mut progress: impl FnMut(&SnapshotProgress) -> bool,
+) -> Result<ArchiveSummary, String> {
+    let mut files = Vec::new();
+    for root in roots {
+        collect_session_files(root, &mut files);
+    }
+    files.sort();
+    files.dedup();
+
+    let id = format!("{}-{}", session_name, new_snapshot_name());
+    if locations.iter().any(|dir| dir.join(&id).exists()) {
+        return Err(format!("Archive {} already exists", id));
+    }
+
+    let mut state = SnapshotProgress {
+        bytes_done: 0,
+        total_bytes: files.iter().map(|(_, size, _)| size).sum(),
+        files_done: 0,
+        total_files: files.len() as u32,
+    };
+    let written = (|| {
+        let mut writer = VolumeWriter::new(
+            locations,
+            &id,
+            volume_bytes.max(MIN_VOLUME_BYTES),
+            state.total_bytes,
+        );
+        let mut entries = Vec::with_capacity(files.len());
+        let mut buffer = vec![0u8; COPY_BUFFER];
+        for (path, _, modified) in files {
+            let Ok(relative) = path.strip_prefix(source) else {
+                continue;
+            };
+            let relative: Vec<String> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+
+            let mut input = std::fs::File::open(&path)
+                .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+            let mut hasher = blake3::Hasher::new();
+            let mut pieces = Vec::new();
+            let mut size = 0;
+            loop {
+                let read = input
+                    .read(&mut buffer)
+                    .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+                writer.write(&buffer[..read], &mut pieces)?;
+                size += read as u64;
+                state.bytes_done += read as u64;
+                if !progress(&state) {
+                    return Err(CANCELLED.to_string());
+                }
+            }
+
+            entries.push(ArchiveFile {
+                path: relative.join("/"),
+                size,
+                modified,
+                hash: hasher.finalize().to_hex().to_string(),
+                pieces,
+            });
+            state.files_done += 1;
+        }
+        let (volumes, used) = writer.finish()?;
+        Ok((volumes, used, entries))
+    })();
+
+    let (volumes, used, entries) = match written {
+        Ok(written) => written,
+        Err(e) => {
+            discard_archive(locations, &id);
+            return Err(e);
+        }
+    };
+
+    let catalog = ArchiveCatalog {
+        version: ARCHIVE_VERSION,
+        id: id.clone(),
+        session_name: session_name.to_string(),
+        source_path: source.to_string_lossy().to_string(),
+        source_host: super::sidecar::host_name(),
+        created_at: timestamp(),
+        image_count,
+        volume_bytes,
+        volumes,
+        files: entries,
+    };
+    let finished = serde_json::to_vec_pretty(&catalog)
+        .map_err(|e| format!("Failed to serialize archive catalog: {}", e))
+        .and_then(|content| {
+            for dir in &used {
+                let partial = partial_dir(dir, &id);
+                let mut file = std::fs::File::create(catalog_path(&partial, &id))
+                    .and_then(|mut f| f.write_all(&content).map(|_| f))
+                    .map_err(|e| format!("Failed to write archive catalog: {}", e))?;
+                file.flush()
+                    .and_then(|_| file.sync_all())
+                    .map_err(|e| format!("Failed to write archive catalog: {}", e))?;
+            }
+            Ok(())
+        })
+        .and_then(|_| {
+            for dir in &used {
+                std::fs::rename(partial_dir(dir, &id), dir.join(&id))
+                    .map_err(|e| format!("Failed to finish archive {}: {}", id, e))?;
+            }
+            Ok(())
+        });
+    if let Err(e) = finished {
+        discard_archive(locations, &id);
+        return Err(e);
+    }
+
+    info!(
+        "Archive {} written in {} volumes on {} disks ({} files)",
+        id,
+        catalog.volumes.len(),
+        used.len(),
+        catalog.files.len()
+    );
+    Ok(ArchiveSummary {
+        catalog_path: catalog_path(&locations[0].join(&id), &id)
+            .to_string_lossy()
+            .to_string(),
+        id,
+        created_at: catalog.created_at,
+        image_count,
+        volumes: catalog.volumes.len() as u32,
+        files: catalog.files.len() as u64,
+        total_bytes: state.bytes_done,
+    })
+}
+
+/// Removes archives beyond `retention` from every location, oldest first, and
+/// any left unfinished. The newest archive is always kept. Without a
+/// retention, the last `DEFAULT_ARCHIVES_KEPT` are.
+pub fn prune_archives(locations: &[PathBuf], retention: &SnapshotRetention) -> u32 {
+    let mut removed = 0;
+    for dir in locations {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(PARTIAL_SUFFIX)
+            {
+                match std::fs::remove_dir_all(entry.path()) {
+                    Ok(()) => removed += 1,
+                    Err(e) => warn!("Failed to remove partial archive {:?}: {}", entry.path(), e),
+                }
+            }
+        }
+    }
+
+    let Some(primary) = locations.first() else {
+        return removed;
+    };
+    let mut archives = complete_archives(primary);
+    archives.sort_by(|(_, a), (_, b)| a.created_at.cmp(&b.created_at));
+
+    let keep_last = match (retention.keep_last, retention.keep_within_days) {
+        (None, None) => DEFAULT_ARCHIVES_KEPT,
+        (keep_last, _) => keep_last.unwrap_or(0),
+    }
+    .max(1) as usize;
+    let cutoff = retention
+        .keep_within_days
+        .map(|days| chrono::Utc::now() - chrono::Duration::days(i64::from(days)));
+    let expired = archives.len().saturating_sub(keep_last);
+
+    for (archive_dir, catalog) in archives.into_iter().take(expired) {
+        let created = chrono::DateTime::parse_from_rfc3339(&catalog.created_at).ok();
+        if let (Some(cutoff), Some(created)) = (cutoff, created) {
+            if created >= cutoff {
+                continue;
+            }
+        }
+        let Some(folder) = archive_dir.file_name().map(|n| n.to_os_string()) else {
+            continue;
+        };
+        if folder != catalog.id.as_str() {
+            warn!(
+                "Skipping archive {:?}: its catalog names {}",
+                archive_dir, catalog.id
+            );
+            continue;
+        }
+        let copies = std::iter::once(archive_dir.clone())
+            .chain(locations[1..].iter().map(|dir| dir.join(&folder)));
+        let mut deleted = false;
+        for copy in copies {
+            if !copy.exists() {
+                continue;
+            }
+            match std::fs::remove_dir_all(&copy) {
+                Ok(()) => deleted = true,
+                Err(e) => warn!("Failed to remove archive {:?}: {}", copy, e),
+            }
+        }
+        if deleted {
+            info!("Removed expired archive {}", catalog.id);
+            removed += 1;
+        }
+    }
+
+    removed
+}
+
+/// The newest finished archive in `archives_dir`, with its folder.
+pub fn latest_archive(archives_dir: &Path) -> Option<(PathBuf, ArchiveCatalog)> {
+    complete_archives(archives_dir)
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.created_at.cmp(&b.created_at))
+}
+
+/// Writes one archived file to `target` from the volumes in `volumes`, checking
+/// it against its hash. `progress` is told how many bytes each step wrote and
+/// stops the copy when it returns false. Returns the number of bytes written.
+pub fn extract_file(
+    volumes: &HashMap<u32, PathBuf>,
+    file: &ArchiveFile,
+    target: &Path,
+    mut progress: impl FnMut(u64) -> bool,
+) -> Result<u64, String> {
+    let mut out = std::fs::File::create(target)
+        .map_err(|e| format!("Failed to create {:?}: {}", target, e))?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0u8; COPY_BUFFER];
+    let mut written = 0;
+
+    for piece in &file.pieces {
+        let volume = volumes
+            .get(&piece.volume)
+            .ok_or_else(|| format!("Volume {} is not available", piece.volume))?;
+        let mut input = std::fs::File::open(volume)
+            .map_err(|e| format!("Failed to open volume {:?}: {}", volume, e))?;
+        input
+            .seek(SeekFrom::Start(piece.offset))
+            .map_err(|e| format!("Failed to read volume {:?}: {}", volume, e))?;
+
+        let mut remaining = piece.length;
+        while remaining > 0 {
+            let len = remaining.min(buffer.len() as u64) as usize;
+            input
+                .read_exact(&mut buffer[..len])
+                .map_err(|e| format!("Failed to read volume {:?}: {}", volume, e))?;
+            hasher.update(&buffer[..len]);
+            out.write_all(&buffer[..len])
+                .map_err(|e| format!("Failed to write {:?}: {}", target, e))?;
+            remaining -= len as u64;
+            written += len as u64;
+            if !progress(len as u64) {
+                return Err(CANCELLED.to_string());
+            }
+        }
+    }
+
+    if hasher.finalize().to_hex().as_str() != file.hash {
+        return Err(format!("{} is damaged in the archive", file.path));
+    }
+    let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(file.modified);
+    if let Err(e) = out.set_modified(modified) {
+        warn!("Failed to set modification time of {:?}: {}", target, e);
+    }
+    Ok(written)
+}
+
+/// Writes data into consecutive volumes, starting a new one when the current
+/// one is full and moving to the next location when a disk can't fit it.
+struct VolumeWriter<'a> {
+    locations: &'a [PathBuf],
+    location: usize,
+    /// Locations that volumes have been written to, in order.
+    used: Vec<PathBuf>,
+    id: &'a str,
+    volume_bytes: u64,
+    /// Bytes still to be written, so the last volume isn't sized as a full one.
+    remaining: u64,
+    current: Option<OpenVolume>,
+    volumes: Vec<ArchiveVolume>,
+}
+
+struct OpenVolume {
+    number: u32,
+    archives_dir: PathBuf,
+    file: std::io::BufWriter<std::fs::File>,
+    written: u64,
+    hasher: blake3::Hasher,
+}
+
+impl<'a> VolumeWriter<'a> {
+    fn new(locations: &'a [PathBuf], id: &'a str, volume_bytes: u64, total_bytes: u64) -> Self {
+        VolumeWriter {
+            locations,
+            location: 0,
+            used: Vec::new(),
+            id,
+            volume_bytes,
+            remaining: total_bytes,
+            current: None,
+            volumes: Vec::new(),
+        }
+    }
+
+    /// Appends `data`, recording where it went in `pieces`.
+    fn write(&mut self, mut data: &[u8], pieces: &mut Vec<ArchivePiece>) -> Result<(), String> {
+        while !data.is_empty() {
+            let full = self
+                .current
+                .as_ref()
+                .is_none_or(|v| v.written >= self.volume_bytes);
+            if full {
+                self.close_volume()?;
+                self.open_volume()?;
+            }
+            let Some(volume) = self.current.as_mut() else {
+                unreachable!("a volume was just opened");
+            };
+
+            let len = (self.volume_bytes - volume.written).min(data.len() as u64) as usize;
+            volume
+                .file
+                .write_all(&data[..len])
+                .map_err(|e| format!("Failed to write volume {}: {}", volume.number, e))?;
+            volume.hasher.update(&data[..len]);
+
+            match pieces.last_mut() {
+                Some(last)
+                    if last.volume == volume.number
+                        && last.offset + last.length == volume.written =>
+                {
+                    last.length += len as u64;
+                }
+                _ => pieces.push(ArchivePiece {
+                    volume: volume.number,
+                    offset: volume.written,
+                    length: len as u64,
+                }),
+            }
+            volume.written += len as u64;
+            self.remaining = self.remaining.saturating_sub(len as u64);
+            data = &data[len..];
+        }
+        Ok(())
+    }
+
+    fn open_volume(&mut self) -> Result<(), String> {
+        let number = self.volumes.len() as u32 + 1;
+        let needed = self.volume_bytes.min(self.remaining.max(1)) + SPACE_MARGIN;
+        while !has_room(&self.locations[self.location], needed) {
+            if self.location + 1 >= self.locations.len() {
+                return Err(format!(
+                    "Not enough space for archive volume {} on {:?}; add another archive disk to the destination",
+                    number, self.locations[self.location]
+                ));
+            }
+            self.location += 1;
+            info!(
+                "Continuing archive {} on {:?}",
+                self.id, self.locations[self.location]
+            );
+        }
+
+        let archives_dir = self.locations[self.location].clone();
+        let partial = partial_dir(&archives_dir, self.id);
+        std::fs::create_dir_all(&partial)
+            .map_err(|e| format!("Failed to create {:?}: {}", partial, e))?;
+        if !self.used.contains(&archives_dir) {
+            self.used.push(archives_dir.clone());
+        }
+
+        let path = partial.join(volume_name(self.id, number));
+        let file = std::fs::File::create(&path)
+            .map_err(|e| format!("Failed to create volume {:?}: {}", path, e))?;
+        self.current = Some(OpenVolume {
+            number,
+            archives_dir,
+            file: std::io::BufWriter::new(file),
+            written: 0,
+            hasher: blake3::Hasher::new(),
+        });
+        Ok(())
+    }
+
+    fn close_volume(&mut self) -> Result<(), String> {
+        let Some(volume) = self.current.take() else {
+            return Ok(());
+        };
+        volume
+            .file
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .and_then(|f| f.sync_all())
+            .map_err(|e| format!("Failed to write volume {}: {}", volume.number, e))?;
+        self.volumes.push(ArchiveVolume {
+            number: volume.number,
+            name: volume_name(self.id, volume.number),
+            size: volume.written,
+            hash: volume.hasher.finalize().to_hex().to_string(),
+            archives_dir: Some(volume.archives_dir.to_string_lossy().to_string()),
+        });
+        Ok(())
+    }
+
+    /// The volumes written and the locations they went to.
+    fn finish(mut self) -> Result<(Vec<ArchiveVolume>, Vec<PathBuf>), String> {
+        self.close_volume()?;
+        if self.used.is_empty() {
+            // An empty session still gets a folder for its catalog
+            let first = self.locations[0].clone();
+            std::fs::create_dir_all(partial_dir(&first, self.id))
+                .map_err(|e| format!("Failed to create {:?}: {}", first, e))?;
+            self.used.push(first);
+        }
+        Ok((self.volumes, self.used))
+    }
+}
+
+/// Whether the disk holding `dir` has `needed` bytes free. A disk that can't
+/// be read, such as one that was unplugged, has no room.
+fn has_room(dir: &Path, needed: u64) -> bool {
+    let Some(existing) = dir.ancestors().find(|p| p.exists()) else {
+        return false;
+    };
+    match read_disk_info(&existing.to_string_lossy()) {
+        Ok(disk) => disk.available_bytes >= needed,
+        Err(e) => {
+            warn!("Failed to check free space on {:?}: {}", dir, e);
+            false
+        }
+    }
+}
+
+fn partial_dir(archives_dir: &Path, id: &str) -> PathBuf {
+    archives_dir.join(format!("{}{}", id, PARTIAL_SUFFIX))
+}
+
+/// Removes every trace of a failed archive from all locations.
+fn discard_archive(locations: &[PathBuf], id: &str) {
+    for dir in locations {
+        for path in [partial_dir(dir, id), dir.join(id)] {
+            if path.exists() {
+                if let Err(e) = std::fs::remove_dir_all(&path) {
+                    warn!("Failed to remove {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+}
+
+fn volume_name(id: &str, number: u32) -> String {
+    format!("{}.vol{:03}", id, number)
+}
+
+fn catalog_path(archive_dir: &Path, id: &str) -> PathBuf {
+    archive_dir.join(format!("{}{}", id, CATALOG_SUFFIX))
+}
+
+/// Reads a catalog and checks that the names it gives for its archive, session
+/// and volumes are plain names matching the catalog file, since they are later
+/// joined onto search folders.
+fn read_catalog(path: &Path) -> Result<ArchiveCatalog, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read archive catalog: {}", e))?;
+    let catalog: ArchiveCatalog = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse archive catalog: {}", e))?;
+
+    require_plain_name(&catalog.id)?;
+    require_plain_name(&catalog.session_name)?;
+    let expected = format!("{}{}", catalog.id, CATALOG_SUFFIX);
+    if path.file_name() != Some(std::ffi::OsStr::new(&expected)) {
+        return Err(format!(
+            "Archive catalog {:?} does not belong to archive {}",
+            path, catalog.id
+        ));
+    }
+    if let Some(volume) = catalog
+        .volumes
+        .iter()
+        .find(|v| v.name != volume_name(&catalog.id, v.number))
+    {
+        return Err(format!(
+            "Archive catalog lists an unexpected volume name: {}",
+            volume.name
+        ));
+    }
+    Ok(catalog)
+}
+
+/// Finished archives in `archives_dir` with their catalogs.
+fn complete_archives(archives_dir: &Path) -> Vec<(PathBuf, ArchiveCatalog)> {
+    let Ok(entries) = std::fs::read_dir(archives_dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|e| !e.file_name().to_string_lossy().ends_with(PARTIAL_SUFFIX))
+        .filter_map(|e| {
+            let dir = e.path();
+            let id = e.file_name().to_string_lossy().to_string();
+            let catalog = read_catalog(&catalog_path(&dir, &id)).ok()?;
+            (catalog.id == id).then_some((dir, catalog))
+        })
+        .collect()
+}
+
+/// The catalog's own folder followed by the other places to look for volumes.
+fn search_dirs(catalog_file: &Path, search_paths: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut dirs: Vec<PathBuf> = catalog_file
+        .parent()
+        .map(Path::to_path_buf)
+        .into_iter()
+        .collect();
+    for path in search_paths {
+        dirs.push(require_within(path, &[RootKind::Destination])?);
+    }
+    Ok(dirs)
+}
+
+fn select_files<'a>(
+    catalog: &'a ArchiveCatalog,
+    files: Option<&[String]>,
+) -> Result<Vec<&'a ArchiveFile>, String> {
+    let Some(files) = files else {
+        return Ok(catalog.files.iter().collect());
+    };
+    files
+        .iter()
+        .map(|path| {
+            catalog
+                .files
+                .iter()
+                .find(|f| &f.path == path)
+                .ok_or_else(|| format!("{} is not in this archive", path))
+        })
+        .collect()
+}
+
+fn restore_plan(
+    catalog: &ArchiveCatalog,
+    files: &[&ArchiveFile],
+    search_dirs: &[PathBuf],
+) -> ArchiveRestorePlan {
+    let needed: BTreeSet<u32> = files
+        .iter()
+        .flat_map(|f| f.pieces.iter().map(|p| p.volume))
+        .collect();
+
+    let volumes: Vec<VolumeStatus> = catalog
+        .volumes
+        .iter()
+        .filter(|v| needed.contains(&v.number))
+        .map(|v| VolumeStatus {
+            number: v.number,
+            name: v.name.clone(),
+            size: v.size,
+            archives_dir: v.archives_dir.clone(),
+            location: volume_candidates(catalog, v, search_dirs)
+                .into_iter()
+                .find(|path| {
+                    std::fs::metadata(path)
+                        .map(|m| m.is_file() && m.len() == v.size)
+                        .unwrap_or(false)
+                })
+                .map(|path| path.to_string_lossy().to_string()),
+        })
+        .collect();
+
+    ArchiveRestorePlan {
+        archive_id: catalog.id.clone(),
+        missing: volumes
+            .iter()
+            .filter(|v| v.location.is_none())
+            .map(|v| v.number)
+            .collect(),
+        volumes,
+        files: files.len() as u64,
+        bytes: files.iter().map(|f| f.size).sum(),
+    }
+}
+
+/// Where a volume may be: in a search folder itself, in its archive's folder
+/// inside one (a session's `archives` folder), or in the session backup on a
+/// destination. The folder it was written to counts too, if still allowed.
+fn volume_candidates(
+    catalog: &ArchiveCatalog,
+    volume: &ArchiveVolume,
+    search_dirs: &[PathBuf],
+) -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    for dir in search_dirs {
+        candidates.push(dir.join(&volume.name));
+        candidates.push(dir.join(&catalog.id).join(&volume.name));
+        candidates.push(
+            dir.join(&catalog.session_name)
+                .join(ARCHIVES_DIR)
+                .join(&catalog.id)
+                .join(&volume.name),
+        );
+    }
+    if let Some(written) = &volume.archives_dir {
+        if let Ok(dir) = require_within(written, &[RootKind::Destination]) {
+            candidates.push(dir.join(&catalog.id).join(&volume.name));
+        }
+    }
+    candidates
+}
@@ -0,0 +1,234 @@
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter};
+
+use super::backup::timestamp;
+use super::manifest::{hash_file, list_backup_files, read_manifest, ManifestEntry};
+use super::policy::{require_root, FsCommandError, RootKind};
+use super::quarantine::session_backup_path;
+use super::receipt::{record_audit, AuditRecord};
+use super::scan::{scan_cache_path, scan_sessions};
+
+static AUDIT_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Serialize)]
+pub struct AuditProgress {
+    pub session_name: String,
+    pub percent: f64,
+    pub files_checked: u64,
+    pub total_files: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionAudit {
+    pub session_name: String,
+    pub path: String,
+    pub files_checked: u64,
+    pub total_files: u64,
+    /// Files whose size or contents no longer match the manifest.
+    pub corrupted: Vec<String>,
+    /// Files in the manifest that are gone from the backup.
+    pub missing: Vec<String>,
+    /// Files in the backup the manifest doesn't know about.
+    pub unexpected: Vec<String>,
+    /// Set when the folder couldn't be audited at all, e.g. it has no manifest.
+    pub error: Option<String>,
+    pub passed: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditReport {
+    pub destination_path: String,
+    pub sample_percent: u8,
+    pub sessions: Vec<SessionAudit>,
+    pub passed: bool,
+}
+
+/// Re-hashes session backups against their manifests, either one session or
+/// every session on the destination. With `sample_percent` below 100 only a
+/// random share of the files is read, for a quick spot check; missing and
+/// unexpected files are always checked in full. Each result is recorded in the
+/// session's receipt.
+#[tauri::command]
+pub async fn audit_destination(
+    app: AppHandle,
+    destination_path: String,
+    session_name: Option<String>,
+    sample_percent: Option<u8>,
+) -> Result<AuditReport, FsCommandError> {
+    let sample_percent = sample_percent.unwrap_or(100).clamp(1, 100);
+    info!(
+        "Auditing {} (session: {:?}, sample: {}%)",
+        destination_path, session_name, sample_percent
+    );
+    let root = require_root(&destination_path, RootKind::Destination)?;
+    let folders: Vec<(String, PathBuf)> = match session_name {
+        Some(name) => {
+            let path = session_backup_path(&destination_path, &name)?;
+            vec![(name, path)]
+        }
+        None => {
+            let cache_path = scan_cache_path(&app);
+            let (sessions, _) = tokio::task::spawn_blocking(move || {
+                scan_sessions(&root, cache_path.as_deref(), false)
+            })
+            .await
+            .map_err(|e| format!("Destination scan failed: {}", e))?;
+            sessions
+                .into_iter()
+                .map(|s| (s.name, PathBuf::from(s.path)))
+                .collect()
+        }
+    };
+
+    AUDIT_CANCELLED.store(false, Ordering::SeqCst);
+    let sessions = tokio::task::spawn_blocking(move || {
+        let mut sessions = Vec::with_capacity(folders.len());
+        for (name, folder) in folders {
+            if AUDIT_CANCELLED.load(Ordering::SeqCst) {
+                return Err("Audit cancelled".to_string());
+            }
+            let audit = audit_session(&app, &name, &folder, sample_percent)?;
+            sessions.push(audit);
+        }
+        Ok(sessions)
+    })
+    .await
+    .map_err(|e| format!("Audit failed: {}", e))??;
+
+    let passed = sessions.iter().all(|s| s.passed);
+    info!(
+        "Audit of {} {}: {} sessions checked",
+        destination_path,
+        if passed { "passed" } else { "failed" },
+        sessions.len()
+    );
+    Ok(AuditReport {
+        destination_path,
+        sample_percent,
+        sessions,
+        passed,
+    })
+}
+
+#[tauri::command]
+pub fn cancel_audit() {
+    info!("COMMAND: cancel_audit received");
+    AUDIT_CANCELLED.store(true, Ordering::SeqCst);
+}
+
+fn audit_session(
+    app: &AppHandle,
+    session_name: &str,
+    folder: &Path,
+    sample_percent: u8,
+) -> Result<SessionAudit, String> {
+    let mut audit = SessionAudit {
+        session_name: session_name.to_string(),
+        path: folder.to_string_lossy().to_string(),
+        files_checked: 0,
+        total_files: 0,
+        corrupted: Vec::new(),
+        missing: Vec::new(),
+        unexpected: Vec::new(),
+        error: None,
+        passed: false,
+    };
+
+    let Some(manifest) = read_manifest(folder).filter(|m| m.algorithm == "blake3") else {
+        warn!("No manifest to audit in {:?}", folder);
+        audit.error = Some("No manifest in this backup".to_string());
+        return Ok(audit);
+    };
+
+    let mut on_disk: HashMap<String, (PathBuf, u64)> = list_backup_files(folder)
+        .into_iter()
+        .map(|(relative, path, size, _)| (relative, (path, size)))
+        .collect();
+
+    // Missing files and size changes are found from the listing alone
+    let mut to_hash: Vec<(&ManifestEntry, PathBuf)> = Vec::new();
+    for entry in &manifest.files {
+        match on_disk.remove(&entry.path) {
+            None => audit.missing.push(entry.path.clone()),
+            Some((_, size)) if size != entry.size => audit.corrupted.push(entry.path.clone()),
+            Some((path, _)) if in_sample(sample_percent) => to_hash.push((entry, path)),
+            Some(_) => {}
+        }
+    }
+    audit.unexpected = on_disk.into_keys().collect();
+    audit.unexpected.sort();
+
+    audit.total_files = to_hash.len() as u64;
+    let total_bytes: u64 = to_hash.iter().map(|(entry, _)| entry.size).sum();
+    let mut bytes_done = 0;
+    let mut last_percent = -1.0;
+    for (entry, path) in to_hash {
+        if AUDIT_CANCELLED.load(Ordering::SeqCst) {
+            return Err("Audit cancelled".to_string());
+        }
+        match hash_file(&path) {
+            Ok(hash) if hash == entry.hash => {}
+            Ok(_) => audit.corrupted.push(entry.path.clone()),
+            Err(e) => {
+                warn!("Failed to read {:?} during audit: {}", path, e);
+                audit.corrupted.push(entry.path.clone());
+            }
+        }
+        audit.files_checked += 1;
+        bytes_done += entry.size;
+
+        let percent = if total_bytes > 0 {
+            (bytes_done as f64 / total_bytes as f64) * 100.0
+        } else {
+            100.0
+        };
+        if percent - last_percent >= 0.1 || percent == 100.0 {
+            last_percent = percent;
+            let _ = app.emit(
+                "audit-progress",
+                AuditProgress {
+                    session_name: session_name.to_string(),
+                    percent,
+                    files_checked: audit.files_checked,
+                    total_files: audit.total_files,
+                },
+            );
+        }
+    }
+
+    audit.passed =
+        audit.corrupted.is_empty() && audit.missing.is_empty() && audit.unexpected.is_empty();
+    if !audit.passed {
+        warn!(
+            "Audit of {:?}: {} corrupted, {} missing, {} unexpected",
+            folder,
+            audit.corrupted.len(),
+            audit.missing.len(),
+            audit.unexpected.len()
+        );
+    }
+
+    let record = AuditRecord {
+        audited_at: timestamp(),
+        host: super::sidecar::host_name(),
+        sample_percent,
+        files_checked: audit.files_checked,
+        corrupted: audit.corrupted.len() as u64,
+        missing: audit.missing.len() as u64,
+        unexpected: audit.unexpected.len() as u64,
+        passed: audit.passed,
+    };
+    if let Err(e) = record_audit(folder, record) {
+        warn!("Failed to record audit for {:?}: {}", folder, e);
+    }
+
+    Ok(audit)
+}
+
+fn in_sample(sample_percent: u8) -> bool {
+    sample_percent >= 100 || uuid::Uuid::new_v4().as_u128() % 100 < u128::from(sample_percent)
+}
@@ -1,10 +1,35 @@
-use log::{error, info};
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use tauri::{AppHandle, Emitter};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
 
+use super::archive::{
+    create_archive, latest_archive, prune_archives, ARCHIVES_DIR, DEFAULT_VOLUME_BYTES,
+};
+use super::compat::{
+    check_session, destination_target, fs_family, rclone_options, CompatReport, FsFamily,
+};
+use super::crypt::{crypt_target, EncryptionSettings};
+use super::manifest::{update_manifest, MANIFEST_FILTER};
+use super::mounts::{find_mount_for, read_mount_table};
+use super::parity::{write_parity, PARITY_FILTER};
+use super::policy::{register_destination_root, require_root, RootKind};
+use super::probe::{probe_path, run_blocking_with_timeout, ProbeStatus, PROBE_TIMEOUT};
+use super::quarantine::session_backup_path;
+use super::rclone::rclone_command;
+use super::receipt::{folder_totals, read_receipt, write_receipt, BackupReceipt, RECEIPT_FILTER};
+use super::remote::{probe_remote, remote_target, RemoteDestination};
+use super::repository::{create_snapshot, open_or_init_repository, SnapshotProgress};
+use super::session::{session_config_path, BackupAttempt, BackupOutcome};
+use super::sidecar::update_session_config_waiting;
+use super::snapshots::{
+    apply_snapshot_retention, latest_snapshot, link_unchanged, new_snapshot_name, BackupMode,
+    SnapshotRetention, PARTIAL_SUFFIX, SNAPSHOTS_DIR,
+};
+
 static BACKUP_CANCELLED: AtomicBool = AtomicBool::new(false);
 
 #[derive(Clone, Serialize)]
@@ -43,6 +68,48 @@ pub struct BackupDestination {
     pub id: u64,
     pub path: String,
     pub enabled: bool,
+    #[serde(default)]
+    pub volume_id: Option<String>,
+    #[serde(default)]
+    pub mode: BackupMode,
+    #[serde(default)]
+    pub snapshot_retention: SnapshotRetention,
+    #[serde(default)]
+    pub parity_percent: Option<u8>,
+    #[serde(default)]
+    pub archive_volume_bytes: Option<u64>,
+    #[serde(default)]
+    pub archive_spill_paths: Vec<String>,
+    #[serde(default)]
+    pub encryption: Option<EncryptionSettings>,
+    #[serde(default)]
+    pub remote: Option<RemoteDestination>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DestinationRelocated {
+    pub destination_id: u64,
+    pub path: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupPreview {
+    pub destinations: Vec<DestinationPreview>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DestinationPreview {
+    pub destination_id: u64,
+    pub path: String,
+    pub compatibility: Option<CompatReport>,
+    pub error: Option<String>,
+}
+
+/// What a finished rclone run moved, taken from its final stats line.
+#[derive(Debug)]
+struct TransferTotals {
+    files: u32,
+    bytes: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -68,6 +135,8 @@ struct RcloneJsonLog {
 #[tauri::command]
 pub async fn start_backup(app: AppHandle, request: BackupRequest) -> Result<(), String> {
     info!("Starting backup for session: {}", request.session_path);
+    require_root(&request.session_path, RootKind::Session)?;
+    let config_path = session_config_path(&request.session_path, &request.session_name)?;
     BACKUP_CANCELLED.store(false, Ordering::SeqCst);
 
     let enabled_destinations: Vec<_> = request.destinations.iter().filter(|d| d.enabled).collect();
@@ -76,6 +145,9 @@ pub async fn start_backup(app: AppHandle, request: BackupRequest) -> Result<(),
         return Err("No destinations enabled".to_string());
     }
 
+    let filters = build_filters(&request.session_path, &request.selected_paths);
+    let roots = selected_roots(&request.session_path, &request.selected_paths);
+
     for (index, dest) in enabled_destinations.iter().enumerate() {
         info!(
             "Processing destination {}/{} (ID: {})",
@@ -92,81 +164,222 @@ pub async fn start_backup(app: AppHandle, request: BackupRequest) -> Result<(),
             return Err("Backup cancelled".to_string());
         }
 
-        // Verify destination exists
-        if !std::path::Path::new(&dest.path).exists() {
-            error!("Destination not accessible: {}", dest.path);
+        let started_at = timestamp();
+
+        // Remote destinations have no local path; rclone reaches them directly
+        if let Some(remote) = &dest.remote {
+            let result = run_remote_backup(
+                &app,
+                &request.session_path,
+                &request.session_name,
+                dest,
+                remote,
+                filters.clone(),
+                request.image_count,
+            )
+            .await;
+            record_result(
+                &app,
+                &config_path,
+                dest.id,
+                &dest.path,
+                started_at,
+                request.image_count,
+                result,
+            )?;
+            continue;
+        }
+
+        // Verify destination exists and is the volume it was added on
+        let dest_path = match prepare_destination(dest).await {
+            Ok(path) => path,
+            Err(e) => {
+                error!("{}", e);
+                record_attempt(
+                    &config_path,
+                    dest.id,
+                    failed_attempt(started_at, BackupOutcome::Failed, &e),
+                );
+                app.emit(
+                    "backup-error",
+                    BackupComplete {
+                        destination_id: dest.id,
+                        success: false,
+                        files_copied: 0,
+                        size_transferred: "0".to_string(),
+                        error: Some(e),
+                        image_count: None,
+                    },
+                )
+                .ok();
+                continue;
+            }
+        };
+
+        if dest_path != dest.path {
             app.emit(
-                "backup-error",
-                BackupComplete {
+                "destination-relocated",
+                DestinationRelocated {
                     destination_id: dest.id,
-                    success: false,
-                    files_copied: 0,
-                    size_transferred: "0".to_string(),
-                    error: Some(format!("Destination not accessible: {}", dest.path)),
-                    image_count: None,
+                    path: dest_path.clone(),
                 },
             )
             .ok();
-            continue;
         }
 
-        info!("Running backup to destination: {}", dest.path);
+        info!("Running backup to destination: {}", dest_path);
 
         // Create a subfolder in the destination for this session
-        let session_dest_path = std::path::Path::new(&dest.path).join(&request.session_name);
-        let session_dest_str = session_dest_path.to_str().unwrap_or(&dest.path);
-
-        // Generate filters based on selected_paths
-        let mut filters = Vec::new();
-        let session_path_obj = std::path::Path::new(&request.session_path);
-
-        let mut sync_all = false;
+        let session_dest_path = std::path::Path::new(&dest_path).join(&request.session_name);
+        let session_dest_str = session_dest_path.to_str().unwrap_or(&dest_path);
 
-        // If selected_paths contains the session root, we sync everything (no filters needed)
-        for path_str in &request.selected_paths {
-            if path_str == &request.session_path {
-                sync_all = true;
-                break;
-            }
+        // Adapt the sync to what the destination filesystem can store
+        let fs_type = destination_fs_type(&dest_path);
+        let family = fs_family(fs_type.as_deref());
+        let extra_args = rclone_options(family);
+        if !extra_args.is_empty() {
+            info!(
+                "Destination filesystem {:?} needs options: {:?}",
+                fs_type, extra_args
+            );
         }
 
-        if !sync_all && !request.selected_paths.is_empty() {
-            for path_str in &request.selected_paths {
-                let p = std::path::Path::new(path_str);
-                if let Ok(rel) = p.strip_prefix(session_path_obj) {
-                    let rel_str = rel.to_string_lossy().replace("\\", "/");
-                    if !rel_str.is_empty() {
-                        filters.push(format!("+ /{}", rel_str));
-                        filters.push(format!("+ /{}/**", rel_str));
-                    }
+        let encrypted = dest.encryption.is_some();
+        let result = match check_mode(dest.mode, family, &session_dest_path, encrypted) {
+            Err(e) => Err(e),
+            Ok(BackupMode::Mirror) => match &dest.encryption {
+                Some(encryption) => {
+                    run_encrypted_backup(
+                        &app,
+                        &request.session_path,
+                        &dest_path,
+                        &request.session_name,
+                        dest.id,
+                        filters.clone(),
+                        extra_args,
+                        family,
+                        request.image_count,
+                        encryption,
+                    )
+                    .await
                 }
+                None => {
+                    run_rclone_backup(
+                        &app,
+                        &request.session_path,
+                        session_dest_str,
+                        dest.id,
+                        filters.clone(),
+                        extra_args,
+                        family,
+                        request.image_count,
+                        dest.parity_percent,
+                    )
+                    .await
+                }
+            },
+            Ok(BackupMode::Snapshots) => {
+                run_snapshot_backup(
+                    &app,
+                    &request.session_path,
+                    session_dest_str,
+                    dest.id,
+                    filters.clone(),
+                    roots.clone(),
+                    extra_args,
+                    family,
+                    request.image_count,
+                    &dest.snapshot_retention,
+                )
+                .await
             }
-            // If we added any specific includes, we must exclude everything else
-            if !filters.is_empty() {
-                filters.push("- /**".to_string());
+            Ok(BackupMode::Repository) => {
+                run_repository_backup(
+                    &app,
+                    &request.session_path,
+                    &dest_path,
+                    dest.id,
+                    roots.clone(),
+                    &request.session_name,
+                    request.image_count,
+                )
+                .await
             }
-        }
+            Ok(BackupMode::Archive) => {
+                run_archive_backup(
+                    &app,
+                    &request.session_path,
+                    &session_dest_path,
+                    dest.id,
+                    roots.clone(),
+                    &request.session_name,
+                    request.image_count,
+                    archive_volume_bytes(dest.archive_volume_bytes, family),
+                    &dest.archive_spill_paths,
+                    &dest.snapshot_retention,
+                )
+                .await
+            }
+        };
 
-        if let Err(e) = run_rclone_backup(
+        record_result(
             &app,
-            &request.session_path,
-            session_dest_str,
+            &config_path,
             dest.id,
-            filters,
+            &dest_path,
+            started_at,
             request.image_count,
-        )
-        .await
-        {
-            if e == "Backup cancelled" {
-                info!("Backup loop aborted due to cancellation");
-                return Err("Backup cancelled".to_string());
-            }
+            result,
+        )?;
+    }
+
+    Ok(())
+}
 
-            error!("Backup failed for {}: {}", dest.path, e);
+/// Records how a destination's run ended and reports failures to the frontend.
+/// A cancellation is passed on so the remaining destinations are skipped.
+fn record_result(
+    app: &AppHandle,
+    config_path: &Path,
+    dest_id: u64,
+    dest_path: &str,
+    started_at: String,
+    image_count: Option<u32>,
+    result: Result<TransferTotals, String>,
+) -> Result<(), String> {
+    match result {
+        Ok(totals) => {
+            let attempt = BackupAttempt {
+                started_at,
+                finished_at: timestamp(),
+                outcome: BackupOutcome::Success,
+                files: totals.files,
+                bytes: totals.bytes,
+                image_count,
+                error: None,
+            };
+            record_attempt(config_path, dest_id, attempt);
+        }
+        Err(e) if e == "Backup cancelled" => {
+            record_attempt(
+                config_path,
+                dest_id,
+                failed_attempt(started_at, BackupOutcome::Cancelled, &e),
+            );
+            info!("Backup loop aborted due to cancellation");
+            return Err("Backup cancelled".to_string());
+        }
+        Err(e) => {
+            error!("Backup failed for {}: {}", dest_path, e);
+            record_attempt(
+                config_path,
+                dest_id,
+                failed_attempt(started_at, BackupOutcome::Failed, &e),
+            );
             app.emit(
                 "backup-error",
                 BackupComplete {
-                    destination_id: dest.id,
+                    destination_id: dest_id,
                     success: false,
                     files_copied: 0,
                     size_transferred: "0".to_string(),
@@ -177,10 +390,244 @@ pub async fn start_backup(app: AppHandle, request: BackupRequest) -> Result<(),
             .ok();
         }
     }
-
     Ok(())
 }
 
+/// Refuses combinations that would lose data: snapshots need hard links, which
+/// FAT and exFAT lack, and a mirror sync would delete existing snapshots or
+/// archives. Encryption goes through rclone crypt, which only mirror mode uses.
+fn check_mode(
+    mode: BackupMode,
+    family: FsFamily,
+    session_dest: &Path,
+    encrypted: bool,
+) -> Result<BackupMode, String> {
+    match mode {
+        BackupMode::Snapshots | BackupMode::Repository | BackupMode::Archive if encrypted => {
+            Err("Encrypted destinations only support mirror mode".to_string())
+        }
+        BackupMode::Snapshots if matches!(family, FsFamily::Fat | FsFamily::ExFat) => Err(
+            "Snapshots need a filesystem with hard links; this destination is FAT or exFAT"
+                .to_string(),
+        ),
+        BackupMode::Mirror if latest_snapshot(&session_dest.join(SNAPSHOTS_DIR)).is_some() => {
+            Err(format!(
+                "{:?} holds snapshots; switch the destination back to snapshot mode or move them first",
+                session_dest
+            ))
+        }
+        BackupMode::Mirror if latest_archive(&session_dest.join(ARCHIVES_DIR)).is_some() => {
+            Err(format!(
+                "{:?} holds archives; switch the destination back to archive mode or move them first",
+                session_dest
+            ))
+        }
+        mode => Ok(mode),
+    }
+}
+
+/// Writes a destination's backup result into the session sidecar, waiting for
+/// the lock if another editor is saving at the same moment.
+fn record_attempt(config_path: &Path, dest_id: u64, attempt: BackupAttempt) {
+    let result = update_session_config_waiting(config_path, |config| {
+        let dest = config
+            .destinations
+            .iter_mut()
+            .find(|d| d.id == dest_id)
+            .ok_or_else(|| format!("destination {} is not in the session config", dest_id))?;
+
+        if attempt.outcome == BackupOutcome::Success {
+            dest.has_existing_backup = true;
+            dest.image_count_at_last_backup = attempt.image_count;
+            dest.last_synced = Some(attempt.finished_at.clone());
+            dest.last_success = Some(attempt.clone());
+            config.last_synced = Some(attempt.finished_at.clone());
+            config.image_count_at_last_backup = attempt.image_count;
+        }
+        dest.last_attempt = Some(attempt);
+        Ok(())
+    });
+
+    if let Err(e) = result {
+        warn!("Failed to record backup result for {}: {}", dest_id, e);
+    }
+}
+
+fn failed_attempt(started_at: String, outcome: BackupOutcome, error: &str) -> BackupAttempt {
+    BackupAttempt {
+        started_at,
+        finished_at: timestamp(),
+        outcome,
+        files: 0,
+        bytes: 0,
+        image_count: None,
+        error: Some(error.to_string()),
+    }
+}
+
+/// Current time in the same format as JavaScript's `Date.toISOString()`.
+pub fn timestamp() -> String {
+    format_timestamp(chrono::Utc::now())
+}
+
+/// `time` in the format of `timestamp`.
+pub fn format_timestamp(time: impl Into<chrono::DateTime<chrono::Utc>>) -> String {
+    time.into()
+        .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// Returns the path to back up to right now, after checking it can actually be
+/// written. Destinations with a volume ID are looked up by their marker; older
+/// ones without it fall back to the stored path. Every filesystem call here is
+/// bounded by a timeout so a dead network mount fails instead of hanging.
+pub async fn prepare_destination(dest: &BackupDestination) -> Result<String, String> {
+    if dest.remote.is_some() {
+        return Err(format!("{} is a remote destination", dest.path));
+    }
+    let stored_path = dest.path.clone();
+    let volume_id = dest.volume_id.clone();
+    let dest_path = run_blocking_with_timeout(PROBE_TIMEOUT, move || {
+        require_root(&stored_path, RootKind::Destination)?;
+        match volume_id {
+            Some(volume_id) => super::volume::resolve_destination_path(&stored_path, &volume_id),
+            None => Ok(stored_path),
+        }
+    })
+    .await
+    .map_err(|e| format!("Destination not accessible: {} ({})", dest.path, e))??;
+
+    // The marker proved this is the same volume, so its new mount path is allowed too
+    if dest_path != dest.path {
+        register_destination_root(&dest_path);
+    }
+
+    let probe = probe_path(&dest_path).await;
+    match probe.status {
+        ProbeStatus::Reachable => Ok(dest_path),
+        ProbeStatus::Slow => {
+            warn!(
+                "Destination {} is responding slowly ({}ms)",
+                dest_path, probe.latency_ms
+            );
+            Ok(dest_path)
+        }
+        ProbeStatus::ReadOnly => Err(format!("Destination is read-only: {}", dest_path)),
+        ProbeStatus::Unreachable => Err(probe
+            .error
+            .unwrap_or_else(|| format!("Destination not accessible: {}", dest_path))),
+    }
+}
+
+/// Converts the selected paths into rclone filter rules. An empty result means
+/// the whole session is synced.
+pub fn build_filters(session_path: &str, selected_paths: &[String]) -> Vec<String> {
+    let mut filters = Vec::new();
+
+    // If selected_paths contains the session root, we sync everything (no filters needed)
+    if selected_paths.is_empty() || selected_paths.iter().any(|p| p == session_path) {
+        return filters;
+    }
+
+    for rel_str in selected_relative_paths(session_path, selected_paths) {
+        filters.push(format!("+ /{}", rel_str));
+        filters.push(format!("+ /{}/**", rel_str));
+    }
+
+    // If we added any specific includes, we must exclude everything else
+    if !filters.is_empty() {
+        filters.push("- /**".to_string());
+    }
+
+    filters
+}
+
+/// The folders that will actually be backed up, as absolute paths.
+pub fn selected_roots(session_path: &str, selected_paths: &[String]) -> Vec<PathBuf> {
+    if selected_paths.is_empty() || selected_paths.iter().any(|p| p == session_path) {
+        return vec![PathBuf::from(session_path)];
+    }
+
+    let roots: Vec<PathBuf> = selected_relative_paths(session_path, selected_paths)
+        .into_iter()
+        .map(|rel| Path::new(session_path).join(rel))
+        .collect();
+
+    if roots.is_empty() {
+        vec![PathBuf::from(session_path)]
+    } else {
+        roots
+    }
+}
+
+fn selected_relative_paths(session_path: &str, selected_paths: &[String]) -> Vec<String> {
+    selected_paths
+        .iter()
+        .filter_map(|p| Path::new(p).strip_prefix(session_path).ok())
+        .map(|rel| rel.to_string_lossy().replace("\\", "/"))
+        .filter(|rel| !rel.is_empty())
+        .collect()
+}
+
+fn destination_fs_type(dest_path: &str) -> Option<String> {
+    let mounts = read_mount_table();
+    find_mount_for(dest_path, &mounts).map(|m| m.fs_type.clone())
+}
+
+/// What kind of filesystem `dest_path` is on, for the names and options it needs.
+pub fn destination_family(dest_path: &str) -> FsFamily {
+    fs_family(destination_fs_type(dest_path).as_deref())
+}
+
+#[tauri::command]
+pub async fn preview_backup(
+    app: AppHandle,
+    request: BackupRequest,
+) -> Result<BackupPreview, String> {
+    info!("Previewing backup for session: {}", request.session_path);
+    require_root(&request.session_path, RootKind::Session)?;
+
+    let roots = selected_roots(&request.session_path, &request.selected_paths);
+    let mut destinations = Vec::new();
+
+    for dest in request.destinations.iter().filter(|d| d.enabled) {
+        // A remote has no local filesystem to check names against
+        if let Some(remote) = &dest.remote {
+            let probe = probe_remote(&app, remote).await;
+            destinations.push(DestinationPreview {
+                destination_id: dest.id,
+                path: dest.path.clone(),
+                compatibility: None,
+                error: probe.error,
+            });
+            continue;
+        }
+
+        let (path, compatibility, error) = match prepare_destination(dest).await {
+            Ok(dest_path) => {
+                let fs_type = destination_fs_type(&dest_path);
+                let session_path = PathBuf::from(&request.session_path);
+                let task_roots = roots.clone();
+                let report = tokio::task::spawn_blocking(move || {
+                    check_session(&session_path, &task_roots, fs_type.as_deref())
+                })
+                .await
+                .map_err(|e| format!("Compatibility check failed: {}", e))?;
+                (dest_path, Some(report), None)
+            }
+            Err(e) => (dest.path.clone(), None, Some(e)),
+        };
+
+        destinations.push(DestinationPreview {
+            destination_id: dest.id,
+            path,
+            compatibility,
+            error,
+        });
+    }
+
+    Ok(BackupPreview { destinations })
+}
+
 #[tauri::command]
 pub fn cancel_backup() {
     info!("COMMAND: cancel_backup received");
@@ -205,40 +652,495 @@ fn format_speed(bytes_per_sec: f64) -> String {
     }
 }
 
+/// Mirrors the session into `dest_path` with `rclone sync`.
+#[allow(clippy::too_many_arguments)]
 async fn run_rclone_backup(
     app: &AppHandle,
     source: &str,
     dest_path: &str,
     dest_id: u64,
     filters: Vec<String>,
+    extra_args: Vec<String>,
+    family: FsFamily,
     image_count: Option<u32>,
-) -> Result<(), String> {
-    // Ensure source and destination have trailing slashes for rclone sync
-    let src = format!("{}/", source.trim_end_matches('/'));
-    let dst = format!("{}/", dest_path.trim_end_matches('/'));
-
-    info!("Starting rclone sync: {} -> {}", src, dst);
-    if !filters.is_empty() {
-        info!("Applying filters: {:?}", filters);
-    }
+    parity_percent: Option<u8>,
+) -> Result<TransferTotals, String> {
+    let started_at = timestamp();
 
     // Create the session subfolder (parent destination was already verified by the caller)
-    let dest = std::path::Path::new(dest_path);
+    let dest = Path::new(dest_path);
     if !dest.exists() {
         std::fs::create_dir(dest_path)
             .map_err(|e| format!("Failed to create session folder: {}", e))?;
     }
 
-    // Get rclone sidecar command
-    // In packaged apps (macOS), the externalBin is placed in the same directory as the executable (Contents/MacOS)
-    // In dev, it's also in the target/debug directory next to the executable
-    let rclone_cmd = std::env::current_exe()
-        .map_err(|e| format!("Failed to get current executable path: {}", e))?
-        .parent()
-        .ok_or("Failed to get parent directory of executable")?
-        .join("rclone");
+    let target = destination_target(family, dest_path);
+    let totals = run_rclone_sync(app, source, &target, dest_id, &filters, extra_args, &[]).await?;
+    info!("Backup completed successfully for destination {}", dest_id);
+
+    // Hash what actually landed on the destination, reusing unchanged entries
+    let manifest_folder = dest.to_path_buf();
+    let manifest = tokio::task::spawn_blocking(move || {
+        update_manifest(&manifest_folder, || {
+            !BACKUP_CANCELLED.load(Ordering::SeqCst)
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+    match manifest {
+        Err(e) if e == "Backup cancelled" => return Err(e),
+        Err(e) => warn!("Failed to update manifest for {}: {}", dest_path, e),
+        Ok(()) => {}
+    }
+
+    // Parity data describes the exact bytes on disk, so it is rebuilt after every sync
+    if let Some(percent) = parity_percent {
+        let parity_folder = dest.to_path_buf();
+        let parity = tokio::task::spawn_blocking(move || {
+            write_parity(&parity_folder, percent, |_| {
+                !BACKUP_CANCELLED.load(Ordering::SeqCst)
+            })
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|r| r);
+        match parity {
+            Err(_) if BACKUP_CANCELLED.load(Ordering::SeqCst) => {
+                return Err("Backup cancelled".to_string())
+            }
+            Err(e) => warn!("Failed to update parity data for {}: {}", dest_path, e),
+            Ok(_) => {}
+        }
+    }
+
+    let mut receipt = BackupReceipt::new(&folder_name(dest), source, started_at, timestamp());
+    receipt.filters = filters;
+    receipt.image_count = image_count;
+    receipt.files_transferred = totals.files;
+    receipt.bytes_transferred = totals.bytes;
+    write_receipts(dest.to_path_buf(), vec![dest.to_path_buf()], receipt).await;
+
+    emit_complete(app, dest_id, &totals, image_count);
+    Ok(totals)
+}
+
+/// Mirrors the session straight to a remote destination after probing it.
+/// Only mirror mode is available, as the other modes write through the local
+/// filesystem. Like encrypted backups, no manifest or receipt is written.
+async fn run_remote_backup(
+    app: &AppHandle,
+    source: &str,
+    session_name: &str,
+    dest: &BackupDestination,
+    remote: &RemoteDestination,
+    filters: Vec<String>,
+    image_count: Option<u32>,
+) -> Result<TransferTotals, String> {
+    if dest.mode != BackupMode::Mirror {
+        return Err("Remote destinations only support mirror mode".to_string());
+    }
+    if dest.encryption.is_some() {
+        return Err("Encryption isn't available for remote destinations yet".to_string());
+    }
+
+    let probe = probe_remote(app, remote).await;
+    match probe.status {
+        ProbeStatus::Reachable => {}
+        ProbeStatus::Slow => warn!(
+            "Destination {} is responding slowly ({}ms)",
+            dest.path, probe.latency_ms
+        ),
+        ProbeStatus::ReadOnly | ProbeStatus::Unreachable => {
+            return Err(probe
+                .error
+                .unwrap_or_else(|| format!("Destination not accessible: {}", dest.path)))
+        }
+    }
+
+    info!("Running backup to remote destination: {}", dest.path);
+    let target = remote_target(app, remote)?;
+    let totals = run_rclone_sync(
+        app,
+        source,
+        &target.path(session_name),
+        dest.id,
+        &filters,
+        Vec::new(),
+        target.env(),
+    )
+    .await?;
+    info!(
+        "Remote backup completed successfully for destination {}",
+        dest.id
+    );
+
+    emit_complete(app, dest.id, &totals, image_count);
+    Ok(totals)
+}
+
+/// Mirrors the session through an rclone crypt remote over the destination, so
+/// only encrypted contents (and, if chosen, names) reach the disk. The manifest,
+/// parity data and receipt are skipped, as they would record the session's
+/// file names and checksums in the clear.
+#[allow(clippy::too_many_arguments)]
+async fn run_encrypted_backup(
+    app: &AppHandle,
+    source: &str,
+    dest_path: &str,
+    session_name: &str,
+    dest_id: u64,
+    filters: Vec<String>,
+    extra_args: Vec<String>,
+    family: FsFamily,
+    image_count: Option<u32>,
+    encryption: &EncryptionSettings,
+) -> Result<TransferTotals, String> {
+    let remote = crypt_target(app, Path::new(dest_path), family, encryption)?;
+    let totals = run_rclone_sync(
+        app,
+        source,
+        &remote.path(session_name),
+        dest_id,
+        &filters,
+        extra_args,
+        remote.env(),
+    )
+    .await?;
+    info!(
+        "Encrypted backup completed successfully for destination {}",
+        dest_id
+    );
+
+    emit_complete(app, dest_id, &totals, image_count);
+    Ok(totals)
+}
+
+/// Writes a new snapshot of the session under `<dest_path>/snapshots`. rclone
+/// copies only new and changed files, compared against the previous snapshot,
+/// and everything else is hard-linked from it afterwards.
+#[allow(clippy::too_many_arguments)]
+async fn run_snapshot_backup(
+    app: &AppHandle,
+    source: &str,
+    dest_path: &str,
+    dest_id: u64,
+    filters: Vec<String>,
+    roots: Vec<PathBuf>,
+    extra_args: Vec<String>,
+    family: FsFamily,
+    image_count: Option<u32>,
+    retention: &SnapshotRetention,
+) -> Result<TransferTotals, String> {
+    let started_at = timestamp();
+    let dest = Path::new(dest_path);
+    let snapshots_dir = dest.join(SNAPSHOTS_DIR);
+    std::fs::create_dir_all(&snapshots_dir)
+        .map_err(|e| format!("Failed to create snapshots folder: {}", e))?;
+
+    let previous = latest_snapshot(&snapshots_dir);
+    let name = new_snapshot_name();
+    let snapshot = snapshots_dir.join(&name);
+    if snapshot.exists() {
+        return Err(format!("Snapshot {} already exists", name));
+    }
+    let partial = snapshots_dir.join(format!("{}{}", name, PARTIAL_SUFFIX));
+    std::fs::create_dir(&partial)
+        .map_err(|e| format!("Failed to create snapshot folder: {}", e))?;
+
+    let mut args = extra_args;
+    if let Some(previous) = &previous {
+        args.push("--compare-dest".to_string());
+        args.push(destination_target(family, &previous.to_string_lossy()));
+    }
+
+    let target = destination_target(family, &partial.to_string_lossy());
+    let totals = match run_rclone_sync(app, source, &target, dest_id, &filters, args, &[]).await {
+        Ok(totals) => totals,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&partial);
+            return Err(e);
+        }
+    };
+
+    let link_target = partial.clone();
+    let source_path = PathBuf::from(source);
+    let linked = tokio::task::spawn_blocking(move || match previous {
+        Some(previous) => link_unchanged(&previous, &link_target, &source_path, &roots, family),
+        None => Ok(0),
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+    let linked = match linked {
+        Ok(linked) => linked,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&partial);
+            return Err(format!("Failed to link unchanged files: {}", e));
+        }
+    };
+
+    std::fs::rename(&partial, &snapshot)
+        .map_err(|e| format!("Failed to finish snapshot {}: {}", name, e))?;
+    info!(
+        "Snapshot {} completed for destination {} ({} unchanged files linked)",
+        name, dest_id, linked
+    );
+
+    let mut receipt = BackupReceipt::new(&folder_name(dest), source, started_at, timestamp());
+    receipt.snapshot = Some(name);
+    receipt.filters = filters;
+    receipt.image_count = image_count;
+    receipt.files_transferred = totals.files;
+    receipt.bytes_transferred = totals.bytes;
+    write_receipts(
+        snapshot.clone(),
+        vec![dest.to_path_buf(), snapshot],
+        receipt,
+    )
+    .await;
+
+    let retention = retention.clone();
+    let removed =
+        tokio::task::spawn_blocking(move || apply_snapshot_retention(&snapshots_dir, &retention))
+            .await
+            .unwrap_or(0);
+    if removed > 0 {
+        info!(
+            "Removed {} old snapshots for destination {}",
+            removed, dest_id
+        );
+    }
+
+    emit_complete(app, dest_id, &totals, image_count);
+    Ok(totals)
+}
+
+/// Stores the session in the destination's chunk repository. Only chunks the
+/// repository doesn't already hold are written, whichever session they came from.
+async fn run_repository_backup(
+    app: &AppHandle,
+    source: &str,
+    dest_path: &str,
+    dest_id: u64,
+    roots: Vec<PathBuf>,
+    session_name: &str,
+    image_count: Option<u32>,
+) -> Result<TransferTotals, String> {
+    info!("Starting repository snapshot: {} -> {}", source, dest_path);
+
+    let progress_app = app.clone();
+    let destination_root = PathBuf::from(dest_path);
+    let source_path = PathBuf::from(source);
+    let session_name = session_name.to_string();
+    let stats = tokio::task::spawn_blocking(move || {
+        let repo = open_or_init_repository(&destination_root)?;
+        create_snapshot(
+            &repo,
+            &source_path,
+            &roots,
+            &session_name,
+            image_count,
+            progress_reporter(progress_app, dest_id),
+        )
+    })
+    .await
+    .map_err(|e| format!("Repository backup failed: {}", e))??;
+
+    info!(
+        "Repository snapshot {} completed for destination {}",
+        stats.snapshot_id, dest_id
+    );
+    let totals = TransferTotals {
+        files: stats.files,
+        bytes: stats.new_bytes,
+    };
+    emit_complete(app, dest_id, &totals, image_count);
+    Ok(totals)
+}
+
+/// Packs the session into a new set of numbered volumes under the session's
+/// `archives` folder, with a catalog of which volume holds each file. Once the
+/// destination is full the archive continues on `spill_paths`, other
+/// destination folders, in order. Archives beyond `retention` are removed after.
+#[allow(clippy::too_many_arguments)]
+async fn run_archive_backup(
+    app: &AppHandle,
+    source: &str,
+    session_dest: &Path,
+    dest_id: u64,
+    roots: Vec<PathBuf>,
+    session_name: &str,
+    image_count: Option<u32>,
+    volume_bytes: u64,
+    spill_paths: &[String],
+    retention: &SnapshotRetention,
+) -> Result<TransferTotals, String> {
+    info!(
+        "Starting archive backup: {} -> {:?} ({} byte volumes)",
+        source, session_dest, volume_bytes
+    );
+
+    let mut locations = vec![session_dest.join(ARCHIVES_DIR)];
+    for path in spill_paths {
+        let task_path = path.clone();
+        let name = session_name.to_string();
+        let location = run_blocking_with_timeout(PROBE_TIMEOUT, move || {
+            require_root(&task_path, RootKind::Destination)?;
+            session_backup_path(&task_path, &name)
+        })
+        .await
+        .and_then(|location| location);
+        match location {
+            Ok(location) => locations.push(location.join(ARCHIVES_DIR)),
+            Err(e) => warn!("Archive disk {} is not available: {}", path, e),
+        }
+    }
+
+    let progress_app = app.clone();
+    let source_path = PathBuf::from(source);
+    let session_name = session_name.to_string();
+    let retention = retention.clone();
+    let summary = tokio::task::spawn_blocking(move || {
+        let summary = create_archive(
+            &locations,
+            &source_path,
+            &roots,
+            &session_name,
+            image_count,
+            volume_bytes,
+            progress_reporter(progress_app, dest_id),
+        )?;
+        let removed = prune_archives(&locations, &retention);
+        if removed > 0 {
+            info!("Removed {} old archives", removed);
+        }
+        Ok::<_, String>(summary)
+    })
+    .await
+    .map_err(|e| format!("Archive backup failed: {}", e))??;
 
-    info!("Resolved rclone path: {:?}", rclone_cmd);
+    info!(
+        "Archive {} completed for destination {} ({} volumes)",
+        summary.id, dest_id, summary.volumes
+    );
+    let totals = TransferTotals {
+        files: summary.files as u32,
+        bytes: summary.total_bytes,
+    };
+    emit_complete(app, dest_id, &totals, image_count);
+    Ok(totals)
+}
+
+/// The destination's volume size, kept under the 4 GiB file size limit on FAT.
+fn archive_volume_bytes(configured: Option<u64>, family: FsFamily) -> u64 {
+    let bytes = configured.unwrap_or(DEFAULT_VOLUME_BYTES);
+    if family == FsFamily::Fat {
+        bytes.min(DEFAULT_VOLUME_BYTES)
+    } else {
+        bytes
+    }
+}
+
+/// Clears an earlier cancellation before a run that uses `progress_reporter`
+/// outside `start_backup`.
+pub fn reset_cancellation() {
+    BACKUP_CANCELLED.store(false, Ordering::SeqCst);
+}
+
+pub fn is_cancelled() -> bool {
+    BACKUP_CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Progress callback for backups that jsync writes itself rather than rclone.
+/// Emits `backup-progress` events and stops the run once it is cancelled.
+pub fn progress_reporter(app: AppHandle, dest_id: u64) -> impl FnMut(&SnapshotProgress) -> bool {
+    let mut last_percent = 0.0;
+    move |progress| {
+        if BACKUP_CANCELLED.load(Ordering::SeqCst) {
+            return false;
+        }
+        let percent = if progress.total_bytes > 0 {
+            (progress.bytes_done as f64 / progress.total_bytes as f64) * 100.0
+        } else {
+            100.0
+        };
+        if (percent - last_percent).abs() >= 0.1 || percent == 100.0 {
+            last_percent = percent;
+            let _ = app.emit(
+                "backup-progress",
+                BackupProgress {
+                    destination_id: dest_id,
+                    percent,
+                    current_file: String::new(),
+                    transfer_rate: String::new(),
+                    files_transferred: progress.files_done,
+                    total_files: progress.total_files,
+                },
+            );
+        }
+        true
+    }
+}
+
+/// Fills in the totals of `counted` and writes the receipt into each folder,
+/// carrying over the audit history of the receipt it replaces.
+async fn write_receipts(counted: PathBuf, folders: Vec<PathBuf>, mut receipt: BackupReceipt) {
+    let written = tokio::task::spawn_blocking(move || {
+        (receipt.total_files, receipt.total_bytes) = folder_totals(&counted);
+        folders.iter().try_for_each(|folder| {
+            receipt.audits = read_receipt(folder).map(|r| r.audits).unwrap_or_default();
+            write_receipt(folder, &receipt)
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+    if let Err(e) = written {
+        warn!("Failed to write backup receipt: {}", e);
+    }
+}
+
+fn emit_complete(app: &AppHandle, dest_id: u64, totals: &TransferTotals, image_count: Option<u32>) {
+    // Emit completion event with final stats
+    let _ = app.emit(
+        "backup-complete",
+        BackupComplete {
+            destination_id: dest_id,
+            success: true,
+            files_copied: totals.files,
+            size_transferred: String::new(), // Will be shown in final progress update
+            error: None,
+            image_count,
+        },
+    );
+}
+
+fn folder_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Runs `rclone sync` from `source` into `dest_path`, reporting progress as it
+/// goes. `env` is added to rclone's environment, e.g. to configure a remote.
+async fn run_rclone_sync(
+    app: &AppHandle,
+    source: &str,
+    dest_path: &str,
+    dest_id: u64,
+    filters: &[String],
+    extra_args: Vec<String>,
+    env: &[(String, String)],
+) -> Result<TransferTotals, String> {
+    // Ensure source and destination have trailing slashes for rclone sync
+    let src = format!("{}/", source.trim_end_matches('/'));
+    let dst = format!("{}/", dest_path.trim_end_matches('/'));
+
+    info!("Starting rclone sync: {} -> {}", src, dst);
+    if !filters.is_empty() {
+        info!("Applying filters: {:?}", filters);
+    }
 
     let mut args = vec![
         "sync".to_string(),
@@ -257,13 +1159,18 @@ async fn run_rclone_backup(
         "8".to_string(),
     ];
 
-    for filter in filters {
+    // jsync's own files go first so the selection filters can't override them
+    let protected = [RECEIPT_FILTER, MANIFEST_FILTER, PARITY_FILTER].map(String::from);
+    for filter in protected.into_iter().chain(filters.iter().cloned()) {
         args.push("--filter".to_string());
         args.push(filter);
     }
 
-    let mut child = Command::new(rclone_cmd)
+    args.extend(extra_args);
+
+    let mut child = Command::from(rclone_command()?)
         .args(args)
+        .envs(env.iter().cloned())
         .stdout(std::process::Stdio::null())
         .stderr(std::process::Stdio::piped())
         .spawn()
@@ -275,6 +1182,7 @@ async fn run_rclone_backup(
 
     let mut last_percent = 0.0;
     let mut total_files = 0u32;
+    let mut bytes_transferred = 0u64;
 
     loop {
         // Check for cancellation
@@ -302,6 +1210,7 @@ async fn run_rclone_backup(
                                 };
 
                                 total_files = stats.total_transfers + stats.total_checks;
+                                bytes_transferred = stats.bytes;
                                 let files_transferred = stats.transfers + stats.checks;
 
                                 if (percent - last_percent).abs() >= 0.1 || percent == 100.0 {
@@ -351,20 +1260,8 @@ async fn run_rclone_backup(
         return Err(format!("rclone failed with status: {}", status));
     }
 
-    info!("Backup completed successfully for destination {}", dest_id);
-
-    // Emit completion event with final stats
-    let _ = app.emit(
-        "backup-complete",
-        BackupComplete {
-            destination_id: dest_id,
-            success: true,
-            files_copied: total_files,
-            size_transferred: String::new(), // Will be shown in final progress update
-            error: None,
-            image_count,
-        },
-    );
-
-    Ok(())
+    Ok(TransferTotals {
+        files: total_files,
+        bytes: bytes_transferred,
+    })
 }
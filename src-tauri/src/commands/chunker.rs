@@ -0,0 +1,205 @@
+use std::io::Read;
+
+/// Chunks never get smaller than this, except the last one of a file.
+const MIN_CHUNK_SIZE: usize = 512 * 1024;
+
+/// A cut is forced here when no content boundary turned up.
+const MAX_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// A boundary is a position where the top `AVG_CHUNK_BITS` bits of the rolling
+/// hash are zero, which gives chunks of about 1 MiB past the minimum.
+const AVG_CHUNK_BITS: u32 = 20;
+
+/// Random values mixed into the rolling hash, one per byte value. Generated at
+/// compile time from a fixed seed: changing it would change every chunk boundary
+/// and stop new backups deduplicating against existing repositories.
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x6a73_796e_6320_6364; // "jsync cd"
+    let mut i = 0;
+    while i < 256 {
+        // splitmix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Splits a stream into content-defined chunks, so inserting or removing bytes
+/// only changes the chunks around the edit instead of shifting every later one.
+pub struct Chunker<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> Chunker<R> {
+    pub fn new(reader: R) -> Self {
+        Chunker {
+            reader,
+            buffer: Vec::with_capacity(MAX_CHUNK_SIZE),
+            eof: false,
+        }
+    }
+
+    /// Returns the next chunk, or `None` at the end of the stream.
+    pub fn next_chunk(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        self.fill()?;
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+
+        let cut = find_boundary(&self.buffer);
+        let rest = self.buffer.split_off(cut);
+        let chunk = std::mem::replace(&mut self.buffer, rest);
+        Ok(Some(chunk))
+    }
+
+    fn fill(&mut self) -> std::io::Result<()> {
+        while !self.eof && self.buffer.len() < MAX_CHUNK_SIZE {
+            let start = self.buffer.len();
+            self.buffer.resize(MAX_CHUNK_SIZE, 0);
+            match self.reader.read(&mut self.buffer[start..]) {
+                Ok(0) => {
+                    self.buffer.truncate(start);
+                    self.eof = true;
+                }
+                Ok(read) => self.buffer.truncate(start + read),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                    self.buffer.truncate(start);
+                }
+                Err(e) => {
+                    self.buffer.truncate(start);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn find_boundary(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK_SIZE {
+        return data.len();
+    }
+
+    // The hash only needs to cover a window before the minimum size is reached
+    let mut hash: u64 = 0;
+    for &byte in &data[MIN_CHUNK_SIZE - 64..MIN_CHUNK_SIZE] {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+    }
+
+    let end = data.len().min(MAX_CHUNK_SIZE);
+    for (offset, &byte) in data[MIN_CHUNK_SIZE..end].iter().enumerate() {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+        if hash >> (64 - AVG_CHUNK_BITS) == 0 {
+            return MIN_CHUNK_SIZE + offset + 1;
+        }
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_bytes(len: usize, mut seed: u64) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                (seed >> 24) as u8
+            })
+            .collect()
+    }
+
+    fn chunks(reader: impl Read) -> Vec<Vec<u8>> {
+        let mut chunker = Chunker::new(reader);
+        let mut chunks = Vec::new();
+        while let Some(chunk) = chunker.next_chunk().unwrap() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    /// Hands out at most `step` bytes per read, like a slow network volume.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = self.step.min(buf.len()).min(self.data.len());
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn chunks_reassemble_within_size_limits() {
+        let data = random_bytes(16 << 20, 1);
+        let chunks = chunks(&data[..]);
+        assert!(chunks.len() > 4);
+        assert_eq!(chunks.concat(), data);
+        let (last, rest) = chunks.split_last().unwrap();
+        assert!(rest
+            .iter()
+            .all(|c| (MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&c.len())));
+        assert!(last.len() <= MAX_CHUNK_SIZE);
+    }
+
+    #[test]
+    fn small_and_empty_inputs() {
+        assert!(chunks(&[][..]).is_empty());
+        let data = random_bytes(MIN_CHUNK_SIZE, 2);
+        assert_eq!(chunks(&data[..]), [data]);
+    }
+
+    #[test]
+    fn boundaries_do_not_depend_on_read_sizes() {
+        let data = random_bytes(6 << 20, 3);
+        let whole = chunks(&data[..]);
+        let trickled = chunks(Trickle {
+            data: &data,
+            step: 4093,
+        });
+        assert_eq!(whole, trickled);
+    }
+
+    #[test]
+    fn uniform_data_stays_within_the_maximum() {
+        let data = vec![0u8; 2 * MAX_CHUNK_SIZE + 10];
+        let sizes: Vec<usize> = chunks(&data[..]).iter().map(Vec::len).collect();
+        assert!(sizes.iter().all(|&s| s <= MAX_CHUNK_SIZE));
+        assert_eq!(sizes.iter().sum::<usize>(), data.len());
+    }
+
+    #[test]
+    fn an_insertion_only_changes_nearby_chunks() {
+        let data = random_bytes(16 << 20, 4);
+        let mut edited = data.clone();
+        edited.splice(3 << 20..3 << 20, random_bytes(1000, 5));
+
+        let before = chunks(&data[..]);
+        let after = chunks(&edited[..]);
+        let changed = after.iter().filter(|c| !before.contains(c)).count();
+        assert!(
+            changed <= 2,
+            "{} of {} chunks changed",
+            changed,
+            after.len()
+        );
+        // Everything after the edit lines up again
+        assert_eq!(before.last(), after.last());
+        assert!(before.len().abs_diff(after.len()) <= 1);
+    }
+}
@@ -0,0 +1,260 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// FAT32 can't store a file of 4 GiB or more.
+const FAT_MAX_FILE_SIZE: u64 = 4 * 1024 * 1024 * 1024 - 1;
+
+/// Only the first findings are returned to the preview; the total is reported separately.
+const MAX_REPORTED_FINDINGS: usize = 50;
+
+/// Characters that FAT, exFAT and SMB (Windows) volumes reject in names.
+const INVALID_NAME_CHARS: &[char] = &['"', '*', ':', '<', '>', '?', '\\', '|'];
+
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Maps the characters above to their full-width lookalikes, the same set rclone
+/// uses for Windows, so names stay readable instead of failing to copy.
+const WINDOWS_NAME_ENCODING: &str =
+    "Slash,LtGt,DoubleQuote,Colon,Question,Asterisk,Pipe,BackSlash,Ctl,RightSpace,RightPeriod,InvalidUtf8,Dot";
+
+/// Offsets rclone adds to encoded characters: ASCII punctuation moves to its
+/// full-width form and control characters to their Unicode control pictures.
+const FULLWIDTH_OFFSET: u32 = 0xFEE0;
+const CONTROL_PICTURES_OFFSET: u32 = 0x2400;
+
+/// Marks a lookalike character that was in the name before encoding.
+const ENCODING_QUOTE: char = '\u{201B}';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FsFamily {
+    Fat,
+    ExFat,
+    Smb,
+    Native,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompatFinding {
+    pub kind: String,     // "file_too_large" | "invalid_name" | "reserved_name"
+    pub severity: String, // "warning" | "error"
+    pub path: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompatReport {
+    pub fs_type: Option<String>,
+    pub findings: Vec<CompatFinding>,
+    pub total_findings: usize,
+    pub rclone_options: Vec<String>,
+    /// rclone encoding applied to names written to the destination, if any.
+    pub name_encoding: Option<String>,
+}
+
+pub fn fs_family(fs_type: Option<&str>) -> FsFamily {
+    match fs_type.map(|t| t.to_lowercase()).as_deref() {
+        Some("msdos" | "vfat" | "fat" | "fat32") => FsFamily::Fat,
+        Some("exfat") => FsFamily::ExFat,
+        Some("smbfs" | "cifs" | "smb3") => FsFamily::Smb,
+        _ => FsFamily::Native,
+    }
+}
+
+/// rclone options that keep a sync working on the destination filesystem.
+/// Name encoding isn't one of them, since `--local-encoding` would also apply
+/// to the session being read; see `destination_target`.
+pub fn rclone_options(family: FsFamily) -> Vec<String> {
+    // FAT stores mtimes in 2 second steps; exFAT and SMB servers commonly round to 1 second
+    let modify_window = match family {
+        FsFamily::Fat => "2s",
+        FsFamily::ExFat | FsFamily::Smb => "1s",
+        FsFamily::Native => return Vec::new(),
+    };
+
+    vec!["--modify-window".to_string(), modify_window.to_string()]
+}
+
+fn name_encoding(family: FsFamily) -> Option<&'static str> {
+    match family {
+        FsFamily::Native => None,
+        _ => Some(WINDOWS_NAME_ENCODING),
+    }
+}
+
+/// A local destination path as rclone should be given it. Where names need
+/// encoding, it becomes an on-the-fly local remote with that encoding, so only
+/// names written to the destination are changed.
+pub fn destination_target(family: FsFamily, path: &str) -> String {
+    match name_encoding(family) {
+        Some(encoding) => format!(":local,encoding='{}':{}", encoding, path),
+        None => path.to_string(),
+    }
+}
+
+/// The original name of a file written through `destination_target`, undoing
+/// rclone's encoding so it can be matched with the session again.
+pub fn decode_name(family: FsFamily, name: &str) -> String {
+    if name_encoding(family).is_none() {
+        return name.to_string();
+    }
+    match name {
+        "．" => return ".".to_string(),
+        "．．" => return "..".to_string(),
+        _ => {}
+    }
+
+    let chars: Vec<char> = name.chars().collect();
+    let mut decoded = String::with_capacity(name.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let last = i + 1 == chars.len();
+        // rclone quotes a lookalike that was already in the original name
+        if chars[i] == ENCODING_QUOTE {
+            if let Some(&next) = chars.get(i + 1) {
+                if decode_char(next, i + 2 == chars.len()).is_some() {
+                    decoded.push(next);
+                    i += 2;
+                    continue;
+                }
+            }
+        }
+        decoded.push(decode_char(chars[i], last).unwrap_or(chars[i]));
+        i += 1;
+    }
+    decoded
+}
+
+/// The character `WINDOWS_NAME_ENCODING` replaced with `c`, if any. Trailing
+/// spaces and periods are only encoded at the end of a name.
+fn decode_char(c: char, last: bool) -> Option<char> {
+    match c {
+        '＂' | '＊' | '／' | '：' | '＜' | '＞' | '？' | '＼' | '｜' => {
+            char::from_u32(c as u32 - FULLWIDTH_OFFSET)
+        }
+        '\u{2400}'..='\u{241F}' => char::from_u32(c as u32 - CONTROL_PICTURES_OFFSET),
+        '\u{2420}' if last => Some(' '),
+        '．' if last => Some('.'),
+        _ => None,
+    }
+}
+
+/// Walks the parts of the session that will be backed up and reports anything
+/// the destination filesystem can't store as-is.
+pub fn check_session(
+    session_path: &Path,
+    roots: &[PathBuf],
+    fs_type: Option<&str>,
+) -> CompatReport {
+    let family = fs_family(fs_type);
+    let mut findings = Vec::new();
+    let mut total_findings = 0;
+
+    if family != FsFamily::Native {
+        for root in roots {
+            check_recursive(
+                session_path,
+                root,
+                family,
+                &mut findings,
+                &mut total_findings,
+            );
+        }
+    }
+
+    CompatReport {
+        fs_type: fs_type.map(|t| t.to_string()),
+        findings,
+        total_findings,
+        rclone_options: rclone_options(family),
+        name_encoding: name_encoding(family).map(str::to_string),
+    }
+}
+
+fn check_recursive(
+    session_path: &Path,
+    path: &Path,
+    family: FsFamily,
+    findings: &mut Vec<CompatFinding>,
+    total: &mut usize,
+) {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return;
+    };
+
+    let relative = path
+        .strip_prefix(session_path)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string();
+
+    if path != session_path {
+        if let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_string()) {
+            for finding in check_name(&name, &relative) {
+                push_finding(findings, total, finding);
+            }
+        }
+    }
+
+    if metadata.is_dir() {
+        if let Ok(entries) = std::fs::read_dir(path) {
+            for entry in entries.flatten() {
+                check_recursive(session_path, &entry.path(), family, findings, total);
+            }
+        }
+    } else if family == FsFamily::Fat && metadata.len() > FAT_MAX_FILE_SIZE {
+        push_finding(
+            findings,
+            total,
+            CompatFinding {
+                kind: "file_too_large".to_string(),
+                severity: "error".to_string(),
+                path: relative,
+                detail: format!(
+                    "{:.1} GB exceeds the 4 GB FAT32 file size limit and will fail to copy",
+                    metadata.len() as f64 / (1024.0 * 1024.0 * 1024.0)
+                ),
+            },
+        );
+    }
+}
+
+fn check_name(name: &str, relative: &str) -> Vec<CompatFinding> {
+    let mut found = Vec::new();
+
+    let has_invalid_char = name
+        .chars()
+        .any(|c| INVALID_NAME_CHARS.contains(&c) || c.is_control());
+    if has_invalid_char || name.ends_with('.') || name.ends_with(' ') {
+        found.push(CompatFinding {
+            kind: "invalid_name".to_string(),
+            severity: "warning".to_string(),
+            path: relative.to_string(),
+            detail: "Name contains characters this filesystem doesn't allow; they will be replaced with lookalikes".to_string(),
+        });
+    }
+
+    let stem = name.split('.').next().unwrap_or(name).to_uppercase();
+    if RESERVED_NAMES.contains(&stem.as_str()) {
+        found.push(CompatFinding {
+            kind: "reserved_name".to_string(),
+            severity: "error".to_string(),
+            path: relative.to_string(),
+            detail: format!(
+                "'{}' is a reserved name on this filesystem and will fail to copy",
+                name
+            ),
+        });
+    }
+
+    found
+}
+
+fn push_finding(findings: &mut Vec<CompatFinding>, total: &mut usize, finding: CompatFinding) {
+    *total += 1;
+    if findings.len() < MAX_REPORTED_FINDINGS {
+        findings.push(finding);
+    }
+}
@@ -0,0 +1,261 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tauri::AppHandle;
+
+use super::backup::{build_filters, destination_family};
+use super::compat::{destination_target, FsFamily};
+use super::policy::{require_plain_name, require_root, require_within, FsCommandError, RootKind};
+use super::rclone::{last_error_line, rclone_output, RcloneTarget};
+use super::repository::RestoreStats;
+use super::secrets::{delete_secret, read_secret, store_secret};
+use super::session::collect_session_files;
+
+/// Passphrases shorter than this are refused.
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+/// How a destination encrypts its backups. The passphrase itself is kept in
+/// the app's secret store under `key_id`, never in the session's `.jsync` file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionSettings {
+    pub key_id: String,
+    /// Encrypt file and folder names as well as contents.
+    #[serde(default)]
+    pub encrypt_names: bool,
+}
+
+/// An rclone crypt remote layered over a destination folder. rclone reads its
+/// settings, including the obscured passphrase, from the environment, so the
+/// passphrase never appears on a command line. Paths in it use unencrypted names.
+/// The names it writes are encoded for `family` like those of a plain mirror.
+pub fn crypt_target(
+    app: &AppHandle,
+    root: &Path,
+    family: FsFamily,
+    settings: &EncryptionSettings,
+) -> Result<RcloneTarget, String> {
+    let password = read_secret(app, &secret_key(&settings.key_id))?.ok_or_else(|| {
+        "No passphrase is stored for this encrypted destination; enter it again in the destination's settings"
+            .to_string()
+    })?;
+    let (filename_encryption, directory_name_encryption) = if settings.encrypt_names {
+        ("standard", "true")
+    } else {
+        ("off", "false")
+    };
+    let env = vec![
+        (
+            "RCLONE_CRYPT_REMOTE".to_string(),
+            destination_target(family, &root.to_string_lossy()),
+        ),
+        ("RCLONE_CRYPT_PASSWORD".to_string(), password),
+        (
+            "RCLONE_CRYPT_FILENAME_ENCRYPTION".to_string(),
+            filename_encryption.to_string(),
+        ),
+        (
+            "RCLONE_CRYPT_DIRECTORY_NAME_ENCRYPTION".to_string(),
+            directory_name_encryption.to_string(),
+        ),
+    ];
+    Ok(RcloneTarget::new(":crypt:".to_string(), env))
+}
+
+#[derive(Debug, Serialize)]
+pub struct EncryptedBackupCheck {
+    pub matched: u64,
+    /// Files whose encrypted copy doesn't match the source.
+    pub differ: Vec<String>,
+    /// Source files with no encrypted copy.
+    pub missing: Vec<String>,
+    /// Encrypted files with no source file.
+    pub unexpected: Vec<String>,
+    /// Files rclone couldn't check.
+    pub errors: Vec<String>,
+    pub passed: bool,
+}
+
+/// Stores the passphrase for an encrypted destination in the app's secret
+/// store and returns the key to keep in the destination's settings. Pass the
+/// existing `key_id` to replace a passphrase; a new one is created otherwise.
+#[tauri::command]
+pub fn set_encryption_passphrase(
+    app: AppHandle,
+    key_id: Option<String>,
+    passphrase: String,
+) -> Result<String, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!(
+            "Passphrase must be at least {} characters",
+            MIN_PASSPHRASE_LEN
+        ));
+    }
+    let key_id = key_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    store_secret(&app, &secret_key(&key_id), &passphrase)?;
+    Ok(key_id)
+}
+
+#[tauri::command]
+pub fn has_encryption_passphrase(app: AppHandle, key_id: String) -> Result<bool, String> {
+    Ok(read_secret(&app, &secret_key(&key_id))?.is_some())
+}
+
+/// Removes a stored passphrase. Backups encrypted with it can only be read
+/// again once it is entered anew.
+#[tauri::command]
+pub fn forget_encryption_passphrase(app: AppHandle, key_id: String) -> Result<(), String> {
+    delete_secret(&app, &secret_key(&key_id))
+}
+
+/// Decrypts a session backup from an encrypted destination into
+/// `<target_path>/<session_name>`, which must not exist yet.
+#[tauri::command]
+pub async fn restore_encrypted_backup(
+    app: AppHandle,
+    destination_path: String,
+    session_name: String,
+    encryption: EncryptionSettings,
+    target_path: String,
+) -> Result<RestoreStats, FsCommandError> {
+    info!(
+        "Restoring encrypted backup of {} from {} to {}",
+        session_name, destination_path, target_path
+    );
+    let root = require_root(&destination_path, RootKind::Destination)?;
+    require_plain_name(&session_name)?;
+    let target = require_within(&target_path, &[RootKind::Destination])?;
+    let session_target = target.join(&session_name);
+    if session_target.exists() {
+        return Err(format!(
+            "{:?} already exists; choose another folder to restore into",
+            session_target
+        )
+        .into());
+    }
+    let remote = crypt_target(
+        &app,
+        &root,
+        destination_family(&root.to_string_lossy()),
+        &encryption,
+    )?;
+
+    let stats = tokio::task::spawn_blocking(move || {
+        let args = [
+            "copy".to_string(),
+            remote.path(&session_name),
+            session_target.to_string_lossy().to_string(),
+            "--create-empty-src-dirs".to_string(),
+        ];
+        let output = rclone_output(&args, remote.env())?;
+        if !output.status.success() {
+            return Err(format!("Restore failed: {}", last_error_line(&output)));
+        }
+
+        let mut files = Vec::new();
+        collect_session_files(&session_target, &mut files);
+        Ok::<_, String>(RestoreStats {
+            path: session_target.to_string_lossy().to_string(),
+            files: files.len() as u32,
+            bytes: files.iter().map(|(_, size, _)| size).sum(),
+        })
+    })
+    .await
+    .map_err(|e| format!("Restore failed: {}", e))??;
+
+    info!("Restored {} files to {}", stats.files, stats.path);
+    Ok(stats)
+}
+
+/// Checks an encrypted session backup against the session with
+/// `rclone cryptcheck`, which compares checksums without decrypting the files.
+/// `selected_paths` limits the check to what the backup was made from.
+#[tauri::command]
+pub async fn verify_encrypted_backup(
+    app: AppHandle,
+    session_path: String,
+    selected_paths: Vec<String>,
+    destination_path: String,
+    session_name: String,
+    encryption: EncryptionSettings,
+) -> Result<EncryptedBackupCheck, FsCommandError> {
+    info!(
+        "Verifying encrypted backup of {} on {}",
+        session_path, destination_path
+    );
+    require_root(&session_path, RootKind::Session)?;
+    let root = require_root(&destination_path, RootKind::Destination)?;
+    require_plain_name(&session_name)?;
+    let remote = crypt_target(
+        &app,
+        &root,
+        destination_family(&root.to_string_lossy()),
+        &encryption,
+    )?;
+    let filters = build_filters(&session_path, &selected_paths);
+
+    let check = tokio::task::spawn_blocking(move || {
+        let mut args = vec![
+            "cryptcheck".to_string(),
+            format!("{}/", session_path.trim_end_matches('/')),
+            remote.path(&session_name),
+            "--combined".to_string(),
+            "-".to_string(),
+        ];
+        for filter in filters {
+            args.push("--filter".to_string());
+            args.push(filter);
+        }
+        let output = rclone_output(&args, remote.env())?;
+
+        let mut check = EncryptedBackupCheck {
+            matched: 0,
+            differ: Vec::new(),
+            missing: Vec::new(),
+            unexpected: Vec::new(),
+            errors: Vec::new(),
+            passed: false,
+        };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let Some((marker, path)) = line.split_once(' ') else {
+                continue;
+            };
+            let path = path.to_string();
+            match marker {
+                "=" => check.matched += 1,
+                "*" => check.differ.push(path),
+                "-" => check.missing.push(path),
+                "+" => check.unexpected.push(path),
+                "!" => check.errors.push(path),
+                _ => {}
+            }
+        }
+        let found_problems = !check.differ.is_empty()
+            || !check.missing.is_empty()
+            || !check.unexpected.is_empty()
+            || !check.errors.is_empty();
+        if !output.status.success() && !found_problems {
+            return Err(format!("Verification failed: {}", last_error_line(&output)));
+        }
+        check.passed = output.status.success() && !found_problems;
+        Ok::<_, String>(check)
+    })
+    .await
+    .map_err(|e| format!("Verification failed: {}", e))??;
+
+    if check.passed {
+        info!("Encrypted backup verified ({} files)", check.matched);
+    } else {
+        warn!(
+            "Encrypted backup failed verification: {} differ, {} missing, {} unexpected, {} errors",
+            check.differ.len(),
+            check.missing.len(),
+            check.unexpected.len(),
+            check.errors.len()
+        );
+    }
+    Ok(check)
+}
+
+fn secret_key(key_id: &str) -> String {
+    format!("encryption/{}", key_id)
+}
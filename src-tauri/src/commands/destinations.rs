@@ -1,35 +1,70 @@
-use log::info;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use tauri::AppHandle;
 
+use super::mounts::{classify_path, find_mount_for, read_mount_table, VolumeClassification};
+use super::policy::{
+    register_destination_root, require_root, require_within, FsCommandError, RootKind,
+};
+use super::probe::{run_blocking_with_timeout, PROBE_TIMEOUT};
+use super::quarantine::{purge_expired, quarantine_backup, QuarantineEntry, QUARANTINE_DIR};
+use super::remote::RemoteDestination;
+
 #[derive(Debug, Serialize)]
 pub struct DiskInfo {
     pub total_bytes: u64,
+    pub free_bytes: u64,
     pub available_bytes: u64,
+    pub block_size: u64,
+    pub fs_type: Option<String>,
+    pub read_only: bool,
 }
 
 #[tauri::command]
-pub fn get_disk_info(path: String) -> Result<DiskInfo, String> {
-    let output = std::process::Command::new("df")
-        .args(["-k", &path])
-        .output()
-        .map_err(|e| format!("Failed to run df: {}", e))?;
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let line = stdout.lines().nth(1).ok_or("No df output")?;
-    let parts: Vec<&str> = line.split_whitespace().collect();
-
-    if parts.len() < 4 {
-        return Err("Unexpected df output format".to_string());
+pub async fn get_disk_info(path: String) -> Result<DiskInfo, String> {
+    let task_path = path.clone();
+    run_blocking_with_timeout(PROBE_TIMEOUT, move || read_disk_info(&task_path))
+        .await
+        .map_err(|e| {
+            warn!("Reading disk info for {} failed: {}", path, e);
+            format!("Failed to read disk info for {}: {}", path, e)
+        })?
+}
+
+pub fn read_disk_info(path: &str) -> Result<DiskInfo, String> {
+    let c_path = CString::new(Path::new(path).as_os_str().as_bytes())
+        .map_err(|_| "Path contains a NUL byte".to_string())?;
+
+    // SAFETY: `stat` is plain old data and `c_path` is a valid NUL-terminated string
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    let rc = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    if rc != 0 {
+        return Err(format!(
+            "Failed to read disk info for {}: {}",
+            path,
+            std::io::Error::last_os_error()
+        ));
     }
 
-    let total_1k: u64 = parts[1].parse().map_err(|_| "Failed to parse total".to_string())?;
-    let available_1k: u64 = parts[3].parse().map_err(|_| "Failed to parse available".to_string())?;
+    // f_frsize is the unit for the block counts; some filesystems leave it at 0
+    let block_size = match stat.f_frsize as u64 {
+        0 => stat.f_bsize as u64,
+        size => size,
+    };
+
+    let mounts = read_mount_table();
+    let fs_type = find_mount_for(path, &mounts).map(|m| m.fs_type.clone());
 
     Ok(DiskInfo {
-        total_bytes: total_1k * 1024,
-        available_bytes: available_1k * 1024,
+        total_bytes: stat.f_blocks as u64 * block_size,
+        free_bytes: stat.f_bfree as u64 * block_size,
+        available_bytes: stat.f_bavail as u64 * block_size,
+        block_size,
+        fs_type,
+        read_only: stat.f_flag & libc::ST_RDONLY != 0,
     })
 }
 
@@ -40,6 +75,12 @@ pub struct Destination {
     pub label: String,
     pub destination_type: String, // "external" | "cloud" | "local" | "network"
     pub enabled: bool,
+    pub volume_id: Option<String>,
+    pub fs_type: Option<String>,
+    pub removable: bool,
+    pub volume_name: Option<String>,
+    #[serde(default)]
+    pub remote: Option<RemoteDestination>,
 }
 
 #[tauri::command]
@@ -50,103 +91,115 @@ pub async fn open_folder_picker(_app: AppHandle) -> Result<Option<String>, Strin
     let result = crate::macos_dialog::open_folder_picker().await;
 
     info!("Folder selection result: {:?}", result);
+
+    // A folder the user picked in the native dialog becomes an allowed destination root
+    if let Some(path) = &result {
+        register_destination_root(path);
+    }
     Ok(result)
 }
 
 #[tauri::command]
-pub fn parse_destination(path: String) -> Destination {
+pub fn parse_destination(
+    path: String,
+    existing_ids: Option<Vec<u64>>,
+) -> Result<Destination, FsCommandError> {
     info!("Parsing destination path: {}", path);
+    require_root(&path, RootKind::Destination)?;
 
-    let id = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64;
+    let id = new_destination_id(&existing_ids.unwrap_or_default());
 
-    let (label, destination_type) = detect_destination_type(&path);
+    let volume = detect_destination_type(&path);
 
-    Destination {
+    // Tie the destination to its volume so a different drive mounted under the same name is never used
+    let volume_id = match super::volume::ensure_volume_marker(&path) {
+        Ok(volume_id) => Some(volume_id),
+        Err(e) => {
+            warn!("Could not mark destination volume at {}: {}", path, e);
+            None
+        }
+    };
+
+    Ok(Destination {
         id,
         path,
-        label,
-        destination_type,
+        label: volume.label,
+        destination_type: volume.destination_type,
         enabled: true,
+        volume_id,
+        fs_type: volume.fs_type,
+        removable: volume.removable,
+        volume_name: volume.volume_name,
+        remote: None,
+    })
+}
+
+/// Generates a random destination ID that isn't in `existing_ids`.
+///
+/// IDs are kept below 2^53 so they survive the round trip through JavaScript
+/// numbers, and stay `u64` so IDs in older `.jsync` files remain valid.
+pub fn new_destination_id(existing_ids: &[u64]) -> u64 {
+    const JS_SAFE_INTEGER_MASK: u64 = (1 << 53) - 1;
+
+    loop {
+        let id = (uuid::Uuid::new_v4().as_u128() as u64) & JS_SAFE_INTEGER_MASK;
+        if id != 0 && !existing_ids.contains(&id) {
+            return id;
+        }
     }
 }
 
+/// Moves the session's backup folder into the destination's quarantine rather
+/// than deleting it, so it can be restored until the quarantine expires.
 #[tauri::command]
 pub async fn delete_backup_folder(
     destination_path: String,
     session_name: String,
-) -> Result<(), String> {
+) -> Result<QuarantineEntry, FsCommandError> {
     info!(
         "Deleting backup folder for session '{}' at '{}'",
         session_name, destination_path
     );
 
-    let backup_path = Path::new(&destination_path).join(&session_name);
+    require_root(&destination_path, RootKind::Destination)?;
+    let entry = quarantine_backup(&destination_path, &session_name)?;
 
-    if !backup_path.exists() {
-        return Err(format!("Backup folder does not exist: {:?}", backup_path));
+    // Opportunistically clear out anything whose undo window has passed
+    if let Ok(root) = std::fs::canonicalize(&destination_path) {
+        purge_expired(&root.join(QUARANTINE_DIR));
     }
 
-    std::fs::remove_dir_all(&backup_path).map_err(|e| format!("Failed to delete backup: {}", e))?;
-
-    info!("Successfully deleted backup at {:?}", backup_path);
-    Ok(())
+    info!("Moved backup for '{}' to quarantine", session_name);
+    Ok(entry)
 }
 
+/// A path on a volume that doesn't answer in time is reported as missing.
 #[tauri::command]
-pub fn check_path_exists(path: String) -> bool {
-    Path::new(&path).exists()
+pub async fn check_path_exists(path: String) -> Result<bool, FsCommandError> {
+    let exists = run_blocking_with_timeout(PROBE_TIMEOUT, move || {
+        let allowed = require_within(&path, &[RootKind::Session, RootKind::Destination])?;
+        Ok::<_, FsCommandError>(allowed.exists())
+    })
+    .await;
+    match exists {
+        Ok(result) => result,
+        Err(e) => {
+            warn!("Path check did not finish: {}", e);
+            Ok(false)
+        }
+    }
 }
 
 #[tauri::command]
-pub fn create_directory(path: String) -> Result<(), String> {
+pub fn create_directory(path: String) -> Result<(), FsCommandError> {
     info!("Creating directory: {}", path);
-    std::fs::create_dir_all(&path).map_err(|e| format!("Failed to create directory: {}", e))
+    let allowed = require_within(&path, &[RootKind::Destination])?;
+    std::fs::create_dir_all(&allowed)
+        .map_err(|e| format!("Failed to create directory: {}", e).into())
 }
 
-fn detect_destination_type(path: &str) -> (String, String) {
-    let path_lower = path.to_lowercase();
-
-    // Check for cloud providers
-    if path_lower.contains("dropbox") {
-        return ("Dropbox".to_string(), "cloud".to_string());
-    }
-    if path_lower.contains("google drive") || path_lower.contains("googledrive") {
-        return ("Google Drive".to_string(), "cloud".to_string());
-    }
-    if path_lower.contains("onedrive") {
-        return ("OneDrive".to_string(), "cloud".to_string());
-    }
-    if path_lower.contains("icloud") {
-        return ("iCloud".to_string(), "cloud".to_string());
-    }
-
-    // Check for external volumes
-    if path.starts_with("/Volumes/") {
-        let volume_name = path
-            .strip_prefix("/Volumes/")
-            .and_then(|s| s.split('/').next())
-            .unwrap_or("External Drive")
-            .to_string();
-        return (volume_name, "external".to_string());
-    }
-
-    // Check for network paths
-    if path.starts_with("/net/") || path.starts_with("smb://") || path.starts_with("afp://") {
-        let label = Path::new(&path)
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or("Network".to_string());
-        return (label, "network".to_string());
-    }
-
-    // Default to local
-    let label = Path::new(&path)
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or("Local".to_string());
-
-    (label, "local".to_string())
+fn detect_destination_type(path: &str) -> VolumeClassification {
+    let mounts = read_mount_table();
+    let home = std::env::var("HOME").unwrap_or_default();
+    classify_path(path, &mounts, &home)
 }
@@ -0,0 +1,371 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use super::archive::{extract_file, latest_archive, ArchiveFile, ARCHIVES_DIR};
+use super::backup::{destination_family, prepare_destination, timestamp, BackupDestination};
+use super::crypt::crypt_target;
+use super::manifest::{hash_file, list_backup_files, read_manifest};
+use super::policy::{require_root, RootKind};
+use super::rclone::RcloneTarget;
+use super::remote::remote_target;
+use super::repository::{latest_session_snapshot, restore_file};
+use super::session::{session_config_path, RestoreDrill};
+use super::sidecar::update_session_config;
+use super::snapshots::{latest_snapshot, BackupMode, SNAPSHOTS_DIR};
+
+/// How many files each drill restores.
+const DRILL_FILES: usize = 5;
+
+#[derive(Debug, Deserialize)]
+pub struct DrillRequest {
+    pub session_path: String,
+    pub session_name: String,
+    pub destinations: Vec<BackupDestination>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DrillResult {
+    pub destination_id: u64,
+    /// `None` when the drill didn't run.
+    pub drill: Option<RestoreDrill>,
+    /// Why the drill didn't run, e.g. the destination isn't connected.
+    pub not_run: Option<String>,
+}
+
+/// Restores a few random files of the session from each enabled destination
+/// into a temporary folder and checks them against the destination's manifest,
+/// or against the source where the file hasn't changed since the backup.
+/// Encrypted destinations are decrypted through rclone, the way a real restore
+/// would. Destinations without a backup of the session are skipped, and those
+/// that can't be reached are reported as not run rather than failed. Every
+/// drill that ran is recorded in the session config.
+#[tauri::command]
+pub async fn run_restore_drill(
+    app: AppHandle,
+    request: DrillRequest,
+) -> Result<Vec<DrillResult>, String> {
+    info!(
+        "Running restore drill for session: {}",
+        request.session_path
+    );
+    let source = require_root(&request.session_path, RootKind::Session)?;
+    let config_path = session_config_path(&request.session_path, &request.session_name)?;
+
+    let mut results = Vec::new();
+    for dest in request.destinations.iter().filter(|d| d.enabled) {
+        // Remote and encrypted backups are read back through rclone
+        let prepared = match &dest.remote {
+            Some(remote) => {
+                remote_target(&app, remote).map(|target| (dest.path.clone(), Some(target)))
+            }
+            None => {
+                // An unplugged drive says nothing about whether its backup restores
+                let dest_path = match prepare_destination(dest).await {
+                    Ok(dest_path) => dest_path,
+                    Err(e) => {
+                        info!("Restore drill not run on destination {}: {}", dest.id, e);
+                        results.push(DrillResult {
+                            destination_id: dest.id,
+                            drill: None,
+                            not_run: Some(e),
+                        });
+                        continue;
+                    }
+                };
+                dest.encryption
+                    .as_ref()
+                    .map(|settings| {
+                        crypt_target(
+                            &app,
+                            Path::new(&dest_path),
+                            destination_family(&dest_path),
+                            settings,
+                        )
+                    })
+                    .transpose()
+                    .map(|remote| (dest_path, remote))
+            }
+        };
+        let drill = match prepared {
+            Ok((dest_path, remote)) => {
+                let source = source.clone();
+                let session_name = request.session_name.clone();
+                let mode = dest.mode;
+                tokio::task::spawn_blocking(move || match remote {
+                    Some(remote) => drill_rclone(&source, &session_name, remote),
+                    None => drill_destination(&source, &session_name, Path::new(&dest_path), mode),
+                })
+                .await
+                .unwrap_or_else(|e| Err(format!("Restore drill failed: {}", e)))
+            }
+            Err(e) => Err(e),
+        };
+
+        let drill = match drill {
+            Ok(Some(drill)) => drill,
+            Ok(None) => {
+                info!("No backup to drill on destination {}", dest.id);
+                continue;
+            }
+            Err(e) => RestoreDrill {
+                drilled_at: timestamp(),
+                passed: false,
+                files_checked: 0,
+                failures: Vec::new(),
+                error: Some(e),
+            },
+        };
+
+        if drill.passed {
+            info!(
+                "Restore drill passed on destination {} ({} files)",
+                dest.id, drill.files_checked
+            );
+        } else {
+            warn!(
+                "Restore drill failed on destination {}: {:?} {:?}",
+                dest.id, drill.error, drill.failures
+            );
+        }
+        record_drill(&config_path, dest.id, &drill);
+        results.push(DrillResult {
+            destination_id: dest.id,
+            drill: Some(drill),
+            not_run: None,
+        });
+    }
+
+    Ok(results)
+}
+
+fn drill_destination(
+    source: &Path,
+    session_name: &str,
+    dest_path: &Path,
+    mode: BackupMode,
+) -> Result<Option<RestoreDrill>, String> {
+    let backup_folder = dest_path.join(session_name);
+    let copy = match mode {
+        BackupMode::Mirror => Some(BackupCopy::Folder(backup_folder)),
+        BackupMode::Snapshots => {
+            latest_snapshot(&backup_folder.join(SNAPSHOTS_DIR)).map(BackupCopy::Folder)
+        }
+        BackupMode::Repository => {
+            latest_session_snapshot(dest_path, session_name, &source.to_string_lossy())
+                .map(|(repo, snapshot)| BackupCopy::Repository(repo, snapshot.files))
+        }
+        BackupMode::Archive => {
+            latest_archive(&backup_folder.join(ARCHIVES_DIR)).map(|(dir, catalog)| {
+                let volumes = catalog
+                    .volumes
+                    .iter()
+                    .map(|v| (v.number, dir.join(&v.name)))
+                    .collect();
+                BackupCopy::Archive(volumes, catalog.files)
+            })
+        }
+    };
+    let Some(copy) = copy.filter(BackupCopy::exists) else {
+        return Ok(None);
+    };
+    run_drill(source, &copy).map(Some)
+}
+
+fn drill_rclone(
+    source: &Path,
+    session_name: &str,
+    target: RcloneTarget,
+) -> Result<Option<RestoreDrill>, String> {
+    let files = target.list_files(session_name)?;
+    let copy = BackupCopy::Rclone(target, session_name.to_string(), files);
+    if !copy.exists() {
+        return Ok(None);
+    }
+    run_drill(source, &copy).map(Some)
+}
+
+fn run_drill(source: &Path, copy: &BackupCopy) -> Result<RestoreDrill, String> {
+    let temp = std::env::temp_dir().join(format!("jsync-drill-{}", uuid::Uuid::new_v4().simple()));
+    std::fs::create_dir_all(&temp)
+        .map_err(|e| format!("Failed to create drill folder {:?}: {}", temp, e))?;
+    let drill = copy.drill(source, &temp);
+    if let Err(e) = std::fs::remove_dir_all(&temp) {
+        warn!("Failed to remove drill folder {:?}: {}", temp, e);
+    }
+    Ok(drill)
+}
+
+/// Where a destination keeps the session: a plain folder for mirror and
+/// snapshot mode, a repository snapshot's file list, an archive's volumes
+/// and catalog, or a session folder only rclone can read, such as one behind
+/// a crypt remote.
+enum BackupCopy {
+    Folder(PathBuf),
+    Repository(PathBuf, Vec<super::repository::RepoFile>),
+    Archive(HashMap<u32, PathBuf>, Vec<ArchiveFile>),
+    Rclone(RcloneTarget, String, Vec<(String, u64, u64)>),
+}
+
+impl BackupCopy {
+    fn exists(&self) -> bool {
+        match self {
+            BackupCopy::Folder(folder) => folder.is_dir(),
+            BackupCopy::Repository(_, files) => !files.is_empty(),
+            BackupCopy::Archive(_, files) => !files.is_empty(),
+            BackupCopy::Rclone(_, _, files) => !files.is_empty(),
+        }
+    }
+
+    fn drill(&self, source: &Path, temp: &Path) -> RestoreDrill {
+        let mut drill = RestoreDrill {
+            drilled_at: timestamp(),
+            passed: false,
+            files_checked: 0,
+            failures: Vec::new(),
+            error: None,
+        };
+
+        // (relative path, size, modified time, hash from the manifest)
+        let mut candidates: Vec<(String, u64, u64, Option<String>)> = match self {
+            BackupCopy::Folder(folder) => {
+                let manifest: HashMap<String, (u64, u64, String)> = read_manifest(folder)
+                    .filter(|m| m.algorithm == "blake3")
+                    .map(|m| {
+                        m.files
+                            .into_iter()
+                            .map(|e| (e.path, (e.size, e.modified, e.hash)))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                list_backup_files(folder)
+                    .into_iter()
+                    .map(|(relative, _, size, modified)| {
+                        let hash = manifest
+                            .get(&relative)
+                            .filter(|(s, m, _)| *s == size && *m == modified)
+                            .map(|(_, _, hash)| hash.clone());
+                        (relative, size, modified, hash)
+                    })
+                    .collect()
+            }
+            BackupCopy::Repository(_, files) => files
+                .iter()
+                .map(|f| (f.path.clone(), f.size, f.modified, None))
+                .collect(),
+            BackupCopy::Archive(_, files) => files
+                .iter()
+                .map(|f| (f.path.clone(), f.size, f.modified, Some(f.hash.clone())))
+                .collect(),
+            BackupCopy::Rclone(_, _, files) => files
+                .iter()
+                .map(|(path, size, modified)| (path.clone(), *size, *modified, None))
+                .collect(),
+        };
+        candidates.sort_by_cached_key(|_| uuid::Uuid::new_v4().as_u128());
+
+        for (relative, size, modified, manifest_hash) in candidates {
+            if drill.files_checked as usize >= DRILL_FILES {
+                break;
+            }
+            // Without a manifest entry, the source is only a reference while
+            // it still matches the backed-up copy
+            let Some(expected) =
+                manifest_hash.or_else(|| unchanged_source_hash(source, &relative, size, modified))
+            else {
+                continue;
+            };
+
+            let restored = temp.join(drill.files_checked.to_string());
+            drill.files_checked += 1;
+            match self.restore(&relative, &restored).and_then(|_| {
+                hash_file(&restored).map_err(|e| format!("Failed to read {:?}: {}", restored, e))
+            }) {
+                Ok(hash) if hash == expected => {}
+                Ok(_) => drill
+                    .failures
+                    .push(format!("{}: restored copy does not match", relative)),
+                Err(e) => drill.failures.push(format!("{}: {}", relative, e)),
+            }
+        }
+
+        if drill.files_checked == 0 {
+            drill.error =
+                Some("No backed-up file could be checked against a manifest or the source".into());
+        }
+        drill.passed = drill.error.is_none() && drill.failures.is_empty();
+        drill
+    }
+
+    fn restore(&self, relative: &str, target: &Path) -> Result<(), String> {
+        match self {
+            BackupCopy::Folder(folder) => {
+                let path = folder.join(relative);
+                std::fs::copy(&path, target)
+                    .map(|_| ())
+                    .map_err(|e| format!("Failed to copy {:?}: {}", path, e))
+            }
+            BackupCopy::Repository(repo, files) => {
+                let file = files
+                    .iter()
+                    .find(|f| f.path == relative)
+                    .ok_or_else(|| format!("{} is not in the snapshot", relative))?;
+                restore_file(repo, file, target, |_| true).map(|_| ())
+            }
+            BackupCopy::Archive(volumes, files) => {
+                let file = files
+                    .iter()
+                    .find(|f| f.path == relative)
+                    .ok_or_else(|| format!("{} is not in the archive", relative))?;
+                extract_file(volumes, file, target, |_| true).map(|_| ())
+            }
+            BackupCopy::Rclone(remote, session_name, _) => {
+                remote.copy_file(&format!("{}/{}", session_name, relative), target)
+            }
+        }
+    }
+}
+
+/// Hash of the source file at `relative`, if it still has the size and
+/// modification time it was backed up with.
+fn unchanged_source_hash(
+    source: &Path,
+    relative: &str,
+    size: u64,
+    modified: u64,
+) -> Option<String> {
+    let path = source.join(relative);
+    let metadata = std::fs::metadata(&path).ok()?;
+    let source_modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    if metadata.len() != size || source_modified != modified {
+        return None;
+    }
+    hash_file(&path).ok()
+}
+
+fn record_drill(config_path: &Path, dest_id: u64, drill: &RestoreDrill) {
+    let result = update_session_config(config_path, |config| {
+        let dest = config
+            .destinations
+            .iter_mut()
+            .find(|d| d.id == dest_id)
+            .ok_or_else(|| format!("destination {} is not in the session config", dest_id))?;
+
+        if drill.passed {
+            dest.last_passed_drill = Some(drill.drilled_at.clone());
+        }
+        dest.last_drill = Some(drill.clone());
+        Ok(())
+    });
+
+    if let Err(e) = result {
+        warn!("Failed to record restore drill for {}: {}", dest_id, e);
+    }
+}
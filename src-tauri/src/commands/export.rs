@@ -0,0 +1,625 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use super::backup::{progress_reporter, reset_cancellation, selected_roots, timestamp};
+use super::manifest::{Manifest, ManifestEntry, MANIFEST_FILE, MANIFEST_VERSION};
+use super::policy::{require_plain_name, require_root, require_within, FsCommandError, RootKind};
+use super::repository::{safe_join, RestoreStats, SnapshotProgress};
+use super::session::collect_session_files;
+use super::snapshots::{new_snapshot_name, PARTIAL_SUFFIX};
+
+const ARCHIVE_EXTENSION: &str = ".tar.zst";
+
+/// Sidecar holding the archive's BLAKE3 hash in `b3sum` format.
+const CHECKSUM_EXTENSION: &str = ".b3";
+
+const ZSTD_LEVEL: i32 = 3;
+
+const CANCELLED: &str = "Backup cancelled";
+
+#[derive(Debug, Deserialize)]
+pub struct ExportRequest {
+    pub session_path: String,
+    pub session_name: String,
+    #[serde(default)]
+    pub selected_paths: Vec<String>,
+    /// Folder the archive and its checksum file are written to.
+    pub output_path: String,
+    /// Reported as the destination id in `backup-progress` events.
+    pub progress_id: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionExport {
+    pub archive_path: String,
+    pub checksum_path: String,
+    pub hash: String,
+    pub files: u64,
+    /// Size of the session files before compression.
+    pub bytes: u64,
+    pub archive_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionArchiveCheck {
+    pub archive_path: String,
+    pub session_name: Option<String>,
+    /// Whether the archive matches its checksum file; `None` when there is none.
+    pub checksum_matches: Option<bool>,
+    pub files_checked: u64,
+    /// Files whose size or contents don't match the embedded manifest.
+    pub corrupted: Vec<String>,
+    /// Files in the manifest that aren't in the archive.
+    pub missing: Vec<String>,
+    /// Files in the archive the manifest doesn't list.
+    pub unexpected: Vec<String>,
+    /// Set when the archive couldn't be read to the end.
+    pub error: Option<String>,
+    pub passed: bool,
+}
+
+/// Writes the session, or its selected folders, into a single zstd-compressed
+/// tar file in `output_path` with the session's manifest embedded at the end,
+/// plus a checksum file next to it. Can be stopped with `cancel_backup`.
+#[tauri::command]
+pub async fn export_session_archive(
+    app: AppHandle,
+    request: ExportRequest,
+) -> Result<SessionExport, FsCommandError> {
+    info!(
+        "Exporting session {} to {}",
+        request.session_path, request.output_path
+    );
+    let source = require_root(&request.session_path, RootKind::Session)?;
+    let output_dir = require_within(&request.output_path, &[RootKind::Destination])?;
+    require_plain_name(&request.session_name)?;
+
+    let roots = selected_roots(&request.session_path, &request.selected_paths);
+    let archive_name = format!(
+        "{}-{}{}",
+        request.session_name,
+        new_snapshot_name(),
+        ARCHIVE_EXTENSION
+    );
+    let archive_path = output_dir.join(&archive_name);
+    if archive_path.exists() {
+        return Err(format!("{:?} already exists", archive_path).into());
+    }
+
+    reset_cancellation();
+    let progress = progress_reporter(app, request.progress_id);
+    let session_name = request.session_name.clone();
+    let export = tokio::task::spawn_blocking(move || {
+        let partial = output_dir.join(format!("{}{}", archive_name, PARTIAL_SUFFIX));
+        let written =
+            write_archive(&partial, &source, &roots, &session_name, progress).and_then(|export| {
+                std::fs::rename(&partial, &archive_path)
+                    .map_err(|e| format!("Failed to finish {:?}: {}", archive_path, e))?;
+                Ok(export)
+            });
+        let (hash, files, bytes, archive_bytes) = match written {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = std::fs::remove_file(&partial);
+                return Err(e);
+            }
+        };
+
+        let checksum_path = checksum_path(&archive_path);
+        std::fs::write(&checksum_path, format!("{}  {}\n", hash, archive_name))
+            .map_err(|e| format!("Failed to write {:?}: {}", checksum_path, e))?;
+
+        Ok(SessionExport {
+            archive_path: archive_path.to_string_lossy().to_string(),
+            checksum_path: checksum_path.to_string_lossy().to_string(),
+            hash,
+            files,
+            bytes,
+            archive_bytes,
+        })
+    })
+    .await
+    .map_err(|e| format!("Export failed: {}", e))??;
+
+    info!(
+        "Exported {} files ({} bytes, {} compressed) to {}",
+        export.files, export.bytes, export.archive_bytes, export.archive_path
+    );
+    Ok(export)
+}
+
+/// Reads an exported archive to the end, checking it against its checksum file
+/// and every file against the embedded manifest.
+#[tauri::command]
+pub async fn verify_session_archive(
+    archive_path: String,
+) -> Result<SessionArchiveCheck, FsCommandError> {
+    info!("Verifying session archive {}", archive_path);
+    let archive = require_within(&archive_path, &[RootKind::Destination])?;
+
+    let check = tokio::task::spawn_blocking(move || {
+        let read = read_archive(&archive, |_, _, _, entry| {
+            std::io::copy(entry, &mut std::io::sink())
+                .map(|_| ())
+                .map_err(|e| format!("Failed to read archive: {}", e))
+        });
+        let mut check = SessionArchiveCheck {
+            archive_path: archive.to_string_lossy().to_string(),
+            session_name: None,
+            checksum_matches: None,
+            files_checked: 0,
+            corrupted: Vec::new(),
+            missing: Vec::new(),
+            unexpected: Vec::new(),
+            error: None,
+            passed: false,
+        };
+        match read {
+            Ok(read) => {
+                check.checksum_matches = read_checksum(&archive).map(|h| h == read.archive_hash);
+                check.files_checked = read.files.len() as u64;
+                check.session_name = read.session_name.clone();
+                if let Err(e) = compare_manifest(&read, &mut check) {
+                    check.error = Some(e);
+                }
+            }
+            Err(e) => check.error = Some(e),
+        }
+        check.passed = check.error.is_none()
+            && check.checksum_matches != Some(false)
+            && check.corrupted.is_empty()
+            && check.missing.is_empty()
+            && check.unexpected.is_empty();
+        check
+    })
+    .await
+    .map_err(|e| format!("Verification failed: {}", e))?;
+
+    if check.passed {
+        info!("Session archive {} verified", check.archive_path);
+    } else {
+        warn!(
+            "Session archive {} failed verification: {:?}, {} corrupted, {} missing, {} unexpected",
+            check.archive_path,
+            check.error,
+            check.corrupted.len(),
+            check.missing.len(),
+            check.unexpected.len()
+        );
+    }
+    Ok(check)
+}
+
+/// Unpacks an exported archive into `<target_path>/<session name>`, which must
+/// not exist yet. The folder only appears once every file has been checked
+/// against the embedded manifest.
+#[tauri::command]
+pub async fn extract_session_archive(
+    archive_path: String,
+    target_path: String,
+) -> Result<RestoreStats, FsCommandError> {
+    info!(
+        "Extracting session archive {} to {}",
+        archive_path, target_path
+    );
+    let archive = require_within(&archive_path, &[RootKind::Destination])?;
+    let target = require_within(&target_path, &[RootKind::Destination])?;
+
+    let stats = tokio::task::spawn_blocking(move || {
+        // (session folder, partial folder being written)
+        let mut output: Option<(PathBuf, PathBuf)> = None;
+        let mut stats = RestoreStats {
+            path: String::new(),
+            files: 0,
+            bytes: 0,
+        };
+
+        let read = read_archive(&archive, |session_name, relative, modified, entry| {
+            if output.is_none() {
+                let folder = target.join(session_name);
+                if folder.exists() {
+                    return Err(format!(
+                        "{:?} already exists; choose another folder to extract into",
+                        folder
+                    ));
+                }
+                let partial = target.join(format!("{}{}", session_name, PARTIAL_SUFFIX));
+                if partial.exists() {
+                    std::fs::remove_dir_all(&partial)
+                        .map_err(|e| format!("Failed to clear {:?}: {}", partial, e))?;
+                }
+                output = Some((folder, partial));
+            }
+            let Some((_, partial)) = &output else {
+                unreachable!("the output folder was just chosen");
+            };
+
+            let file_target = safe_join(partial, relative)
+                .ok_or_else(|| format!("Archive contains an invalid path: {:?}", relative))?;
+            if let Some(parent) = file_target.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+            }
+            let mut file = std::fs::File::create(&file_target)
+                .map_err(|e| format!("Failed to create {:?}: {}", file_target, e))?;
+            stats.bytes += std::io::copy(entry, &mut file)
+                .map_err(|e| format!("Failed to extract {}: {}", relative, e))?;
+            stats.files += 1;
+            let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(modified);
+            if let Err(e) = file.set_modified(modified) {
+                warn!(
+                    "Failed to set modification time of {:?}: {}",
+                    file_target, e
+                );
+            }
+            Ok(())
+        });
+
+        let checked = read.and_then(|read| {
+            let mut check = SessionArchiveCheck {
+                archive_path: String::new(),
+                session_name: None,
+                checksum_matches: None,
+                files_checked: 0,
+                corrupted: Vec::new(),
+                missing: Vec::new(),
+                unexpected: Vec::new(),
+                error: None,
+                passed: false,
+            };
+            compare_manifest(&read, &mut check)?;
+            let damaged: Vec<String> = check
+                .corrupted
+                .into_iter()
+                .chain(check.missing)
+                .chain(check.unexpected)
+                .collect();
+            if !damaged.is_empty() {
+                return Err(format!(
+                    "Archive doesn't match its manifest: {}",
+                    damaged.join(", ")
+                ));
+            }
+            Ok(())
+        });
+
+        let Some((folder, partial)) = output else {
+            return checked.and(Err("Archive contains no session files".to_string()));
+        };
+        if let Err(e) = checked.and_then(|_| {
+            std::fs::rename(&partial, &folder)
+                .map_err(|e| format!("Failed to finish {:?}: {}", folder, e))
+        }) {
+            let _ = std::fs::remove_dir_all(&partial);
+            return Err(e);
+        }
+        stats.path = folder.to_string_lossy().to_string();
+        Ok(stats)
+    })
+    .await
+    .map_err(|e| format!("Extract failed: {}", e))??;
+
+    info!("Extracted {} files to {}", stats.files, stats.path);
+    Ok(stats)
+}
+
+/// Streams the files under `roots` into a compressed tar at `path` and returns
+/// the archive's hash, the file count, the bytes archived and the archive size.
+fn write_archive(
+    path: &Path,
+    source: &Path,
+    roots: &[PathBuf],
+    session_name: &str,
+    mut progress: impl FnMut(&SnapshotProgress) -> bool,
+) -> Result<(String, u64, u64, u64), String> {
+    let mut files = Vec::new();
+    for root in roots {
+        collect_session_files(root, &mut files);
+    }
+    files.sort();
+    files.dedup();
+
+    let output =
+        std::fs::File::create(path).map_err(|e| format!("Failed to create {:?}: {}", path, e))?;
+    let writer = HashingWriter {
+        inner: std::io::BufWriter::new(output),
+        hasher: blake3::Hasher::new(),
+        written: 0,
+    };
+    let encoder = zstd::stream::write::Encoder::new(writer, ZSTD_LEVEL)
+        .map_err(|e| format!("Failed to start compression: {}", e))?;
+    let mut builder = tar::Builder::new(encoder);
+
+    let mut state = SnapshotProgress {
+        bytes_done: 0,
+        total_bytes: files.iter().map(|(_, size, _)| size).sum(),
+        files_done: 0,
+        total_files: files.len() as u32,
+    };
+    let mut entries = Vec::with_capacity(files.len());
+    for (file_path, _, modified) in files {
+        let Ok(relative) = file_path.strip_prefix(source) else {
+            continue;
+        };
+        let relative: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        let relative = relative.join("/");
+
+        let input = std::fs::File::open(&file_path)
+            .map_err(|e| format!("Failed to read {:?}: {}", file_path, e))?;
+        let size = input
+            .metadata()
+            .map_err(|e| format!("Failed to read {:?}: {}", file_path, e))?
+            .len();
+        let mut header = file_header(size, modified);
+
+        let mut reader = HashingReader::new(input.take(size), |read| {
+            state.bytes_done += read as u64;
+            progress(&state)
+        });
+        builder
+            .append_data(
+                &mut header,
+                format!("{}/{}", session_name, relative),
+                &mut reader,
+            )
+            .map_err(|e| match e.to_string() {
+                message if message.contains(CANCELLED) => CANCELLED.to_string(),
+                message => format!("Failed to archive {:?}: {}", file_path, message),
+            })?;
+        if reader.read != size {
+            return Err(format!(
+                "{:?} changed while it was being archived",
+                file_path
+            ));
+        }
+        entries.push(ManifestEntry {
+            path: relative,
+            size,
+            modified,
+            hash: reader.finish(),
+        });
+        state.files_done += 1;
+    }
+
+    let bytes = state.bytes_done;
+    let file_count = entries.len() as u64;
+    let manifest = Manifest {
+        version: MANIFEST_VERSION,
+        algorithm: "blake3".to_string(),
+        updated_at: timestamp(),
+        files: entries,
+    };
+    let content = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+    let mut header = file_header(content.len() as u64, 0);
+    builder
+        .append_data(&mut header, MANIFEST_FILE, content.as_slice())
+        .map_err(|e| format!("Failed to write manifest: {}", e))?;
+
+    let writer = builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .map_err(|e| format!("Failed to finish archive: {}", e))?;
+    let HashingWriter {
+        inner,
+        hasher,
+        written,
+    } = writer;
+    inner
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .and_then(|file| file.sync_all())
+        .map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+
+    Ok((
+        hasher.finalize().to_hex().to_string(),
+        file_count,
+        bytes,
+        written,
+    ))
+}
+
+fn file_header(size: u64, modified: u64) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_size(size);
+    header.set_mtime(modified);
+    header.set_mode(0o644);
+    header
+}
+
+/// What reading an archive from start to end found.
+struct ArchiveContents {
+    session_name: Option<String>,
+    manifest: Option<Manifest>,
+    /// (path relative to the session folder, size, hash) of each file.
+    files: Vec<(String, u64, String)>,
+    archive_hash: String,
+}
+
+/// Reads the archive at `path` in one pass, handing each session file to
+/// `on_file` as (session name, relative path, modification time, contents) and
+/// hashing everything on the way.
+fn read_archive(
+    path: &Path,
+    mut on_file: impl FnMut(&str, &str, u64, &mut dyn Read) -> Result<(), String>,
+) -> Result<ArchiveContents, String> {
+    let input =
+        std::fs::File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let raw = HashingReader::new(input, |_| true);
+    let decoder = zstd::stream::read::Decoder::new(raw)
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let mut archive = tar::Archive::new(decoder);
+
+    let mut contents = ArchiveContents {
+        session_name: None,
+        manifest: None,
+        files: Vec::new(),
+        archive_hash: String::new(),
+    };
+    let entries = archive
+        .entries()
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Failed to read archive: {}", e))?;
+        let name = entry
+            .path()
+            .map_err(|e| format!("Failed to read archive: {}", e))?
+            .to_string_lossy()
+            .to_string();
+
+        if name == MANIFEST_FILE {
+            let mut content = Vec::new();
+            entry
+                .read_to_end(&mut content)
+                .map_err(|e| format!("Failed to read manifest: {}", e))?;
+            let manifest = serde_json::from_slice(&content)
+                .map_err(|e| format!("Failed to parse manifest: {}", e))?;
+            contents.manifest = Some(manifest);
+            continue;
+        }
+        if entry.header().entry_type() != tar::EntryType::Regular {
+            continue;
+        }
+
+        let Some((session_name, relative)) = name.split_once('/') else {
+            return Err(format!("Unexpected entry in archive: {}", name));
+        };
+        match &contents.session_name {
+            None => {
+                require_plain_name(session_name)?;
+                contents.session_name = Some(session_name.to_string());
+            }
+            Some(expected) if expected != session_name => {
+                return Err(format!("Unexpected entry in archive: {}", name));
+            }
+            Some(_) => {}
+        }
+
+        let modified = entry.header().mtime().unwrap_or(0);
+        let mut reader = HashingReader::new(&mut entry, |_| true);
+        on_file(session_name, relative, modified, &mut reader)?;
+        // Whatever `on_file` left unread still counts towards the hash
+        std::io::copy(&mut reader, &mut std::io::sink())
+            .map_err(|e| format!("Failed to read archive: {}", e))?;
+        let size = reader.read;
+        contents
+            .files
+            .push((relative.to_string(), size, reader.finish()));
+    }
+
+    // Hash the end of the file too, past the last entry the tar reader needed
+    let mut decoder = archive.into_inner();
+    std::io::copy(&mut decoder, &mut std::io::sink())
+        .map_err(|e| format!("Failed to read archive: {}", e))?;
+    let mut raw = decoder.finish().into_inner();
+    std::io::copy(&mut raw, &mut std::io::sink())
+        .map_err(|e| format!("Failed to read archive: {}", e))?;
+    contents.archive_hash = raw.finish();
+
+    Ok(contents)
+}
+
+/// Sorts the differences between the archived files and the manifest into `check`.
+fn compare_manifest(
+    contents: &ArchiveContents,
+    check: &mut SessionArchiveCheck,
+) -> Result<(), String> {
+    let manifest = contents
+        .manifest
+        .as_ref()
+        .filter(|m| m.algorithm == "blake3")
+        .ok_or_else(|| "Archive has no manifest".to_string())?;
+
+    let mut archived: HashMap<&str, (u64, &str)> = contents
+        .files
+        .iter()
+        .map(|(path, size, hash)| (path.as_str(), (*size, hash.as_str())))
+        .collect();
+    for entry in &manifest.files {
+        match archived.remove(entry.path.as_str()) {
+            None => check.missing.push(entry.path.clone()),
+            Some((size, hash)) if size != entry.size || hash != entry.hash => {
+                check.corrupted.push(entry.path.clone())
+            }
+            Some(_) => {}
+        }
+    }
+    check.unexpected = archived.into_keys().map(str::to_string).collect();
+    check.unexpected.sort();
+    Ok(())
+}
+
+fn checksum_path(archive: &Path) -> PathBuf {
+    let mut name = archive.as_os_str().to_os_string();
+    name.push(CHECKSUM_EXTENSION);
+    PathBuf::from(name)
+}
+
+fn read_checksum(archive: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(checksum_path(archive)).ok()?;
+    content.split_whitespace().next().map(str::to_lowercase)
+}
+
+/// Hashes and counts everything read through it. `on_read` is told how many
+/// bytes each read returned and fails the read when it returns false.
+struct HashingReader<R, F> {
+    inner: R,
+    hasher: blake3::Hasher,
+    read: u64,
+    on_read: F,
+}
+
+impl<R: Read, F: FnMut(usize) -> bool> HashingReader<R, F> {
+    fn new(inner: R, on_read: F) -> Self {
+        HashingReader {
+            inner,
+            hasher: blake3::Hasher::new(),
+            read: 0,
+            on_read,
+        }
+    }
+
+    fn finish(self) -> String {
+        self.hasher.finalize().to_hex().to_string()
+    }
+}
+
+impl<R: Read, F: FnMut(usize) -> bool> Read for HashingReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        self.read += read as u64;
+        if !(self.on_read)(read) {
+            return Err(std::io::Error::other(CANCELLED));
+        }
+        Ok(read)
+    }
+}
+
+/// Hashes and counts everything written through it.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: blake3::Hasher,
+    written: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
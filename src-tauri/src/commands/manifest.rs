@@ -0,0 +1,167 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::backup::timestamp;
+use super::parity::PARITY_DIR;
+use super::receipt::RECEIPT_FILE;
+use super::sidecar::write_atomic;
+
+/// Checksums of every file in a session backup, so the copy can be checked
+/// years later without the source.
+pub const MANIFEST_FILE: &str = ".jsync-manifest";
+
+/// rclone filter rule that keeps the manifest out of the sync and its delete pass.
+pub const MANIFEST_FILTER: &str = "- /.jsync-manifest";
+
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub algorithm: String,
+    pub updated_at: String,
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Relative to the backup folder, with `/` separators.
+    pub path: String,
+    pub size: u64,
+    pub modified: u64,
+    pub hash: String,
+}
+
+pub fn read_manifest(backup_folder: &Path) -> Option<Manifest> {
+    let content = std::fs::read_to_string(backup_folder.join(MANIFEST_FILE)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Brings the manifest in line with the files now in `backup_folder`. Files
+/// whose size and modification time match their previous entry keep their
+/// hash; only new and changed files are read. Stops without writing anything
+/// when `should_continue` returns false.
+pub fn update_manifest(
+    backup_folder: &Path,
+    should_continue: impl Fn() -> bool,
+) -> Result<(), String> {
+    let previous: HashMap<String, ManifestEntry> = read_manifest(backup_folder)
+        .filter(|m| m.algorithm == "blake3")
+        .map(|m| m.files.into_iter().map(|e| (e.path.clone(), e)).collect())
+        .unwrap_or_default();
+
+    let files = list_backup_files(backup_folder);
+
+    let mut entries = Vec::with_capacity(files.len());
+    let mut hashed = 0;
+    for (relative, path, size, modified) in files {
+        if !should_continue() {
+            return Err("Backup cancelled".to_string());
+        }
+
+        let unchanged = previous
+            .get(&relative)
+            .filter(|e| e.size == size && e.modified == modified);
+        let hash = match unchanged {
+            Some(entry) => entry.hash.clone(),
+            None => {
+                hashed += 1;
+                hash_file(&path).map_err(|e| format!("Failed to hash {:?}: {}", path, e))?
+            }
+        };
+        entries.push(ManifestEntry {
+            path: relative,
+            size,
+            modified,
+            hash,
+        });
+    }
+
+    let manifest = Manifest {
+        version: MANIFEST_VERSION,
+        algorithm: "blake3".to_string(),
+        updated_at: timestamp(),
+        files: entries,
+    };
+    write_manifest(backup_folder, &manifest)?;
+
+    info!(
+        "Manifest for {:?} covers {} files ({} hashed)",
+        backup_folder,
+        manifest.files.len(),
+        hashed
+    );
+    Ok(())
+}
+
+pub fn hash_file(path: &Path) -> std::io::Result<String> {
+    let file = std::fs::File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(file)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Whether a file or folder at the top of a backup folder is jsync's own
+/// metadata rather than part of the session.
+pub fn is_backup_metadata(name: &std::ffi::OsStr) -> bool {
+    name == RECEIPT_FILE || name == MANIFEST_FILE || name == PARITY_DIR
+}
+
+fn write_manifest(backup_folder: &Path, manifest: &Manifest) -> Result<(), String> {
+    let content =
+        serde_json::to_vec(manifest).map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+
+    write_atomic(&backup_folder.join(MANIFEST_FILE), &content)
+        .map_err(|e| format!("Failed to write manifest: {}", e))?;
+    Ok(())
+}
+
+/// Every file in a backup folder apart from jsync's own, as (relative path,
+/// full path, size, modification time), sorted by relative path.
+pub fn list_backup_files(backup_folder: &Path) -> Vec<(String, PathBuf, u64, u64)> {
+    let mut files = Vec::new();
+    collect_files(backup_folder, backup_folder, &mut files);
+    files.sort();
+    files
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf, u64, u64)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if dir == root && is_backup_metadata(&entry.file_name()) {
+            continue;
+        }
+        if file_type.is_dir() {
+            collect_files(root, &path, files);
+            continue;
+        }
+        if !file_type.is_file() {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let Ok(relative) = path.strip_prefix(root) else {
+            continue;
+        };
+        let relative: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        files.push((relative.join("/"), path, metadata.len(), modified));
+    }
+}
@@ -1,7 +1,32 @@
+pub mod archive;
+pub mod audit;
 pub mod backup;
+pub mod chunker;
+pub mod compat;
+pub mod crypt;
 pub mod destinations;
+pub mod drill;
+pub mod export;
+pub mod manifest;
+pub mod mounts;
+pub mod parity;
 pub mod permissions;
+pub mod policy;
+pub mod probe;
+pub mod prune;
+pub mod quarantine;
+pub mod rclone;
+pub mod rclone_config;
+pub mod receipt;
+pub mod reed_solomon;
+pub mod remote;
+pub mod repository;
+pub mod scan;
+pub mod secrets;
 pub mod session;
+pub mod sidecar;
+pub mod snapshots;
+pub mod volume;
 
 use tauri::Manager;
 
@@ -0,0 +1,439 @@
+use log::{error, info};
+use std::path::{Component, Path};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MountEntry {
+    pub device: String,
+    pub mount_point: String,
+    pub fs_type: String,
+    pub options: Vec<String>,
+}
+
+/// How a destination is classified from the mount it lives on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeClassification {
+    pub label: String,
+    pub destination_type: String, // "external" | "cloud" | "local" | "network"
+    pub fs_type: Option<String>,
+    pub removable: bool,
+    pub volume_name: Option<String>,
+}
+
+/// Reads the live mount table for this machine.
+pub fn read_mount_table() -> Vec<MountEntry> {
+    #[cfg(target_os = "linux")]
+    {
+        match std::fs::read_to_string("/proc/self/mountinfo") {
+            Ok(content) => parse_mountinfo(&content),
+            Err(e) => {
+                error!("Failed to read /proc/self/mountinfo: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        match std::process::Command::new("/sbin/mount").output() {
+            Ok(output) => parse_bsd_mount_output(&String::from_utf8_lossy(&output.stdout)),
+            Err(e) => {
+                error!("Failed to run mount: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Parses the Linux `/proc/self/mountinfo` format:
+/// `id parent major:minor root mount_point options [optional...] - fs_type source super_options`,
+/// with spaces escaped as `\040`. Per-mount and superblock options are merged.
+/// Both parsers are built on every platform so captured tables can be checked anywhere.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn parse_mountinfo(content: &str) -> Vec<MountEntry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (mount_fields, fs_fields) = line.split_once(" - ")?;
+            let mut mount_fields = mount_fields.split_whitespace().skip(4);
+            let mount_point = unescape_octal(mount_fields.next()?);
+            let mut options: Vec<String> = mount_fields
+                .next()
+                .map(|o| o.split(',').map(|s| s.to_string()).collect())
+                .unwrap_or_default();
+
+            let mut fs_fields = fs_fields.split_whitespace();
+            let fs_type = fs_fields.next()?.to_string();
+            let device = unescape_octal(fs_fields.next()?);
+            for option in fs_fields.next().unwrap_or("").split(',') {
+                if !option.is_empty() && !options.iter().any(|o| o == option) {
+                    options.push(option.to_string());
+                }
+            }
+
+            Some(MountEntry {
+                device,
+                mount_point,
+                fs_type,
+                options,
+            })
+        })
+        .collect()
+}
+
+/// Parses the output of the BSD/macOS `mount` command:
+/// `/dev/disk4s1 on /Volumes/Backup Drive (exfat, local, nodev, nosuid, noowners)`.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+pub fn parse_bsd_mount_output(content: &str) -> Vec<MountEntry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (device, rest) = line.split_once(" on ")?;
+            // Mount points can contain spaces and parentheses, so split on the last " ("
+            let open = rest.rfind(" (")?;
+            let mount_point = &rest[..open];
+            let details = rest[open + 2..].trim_end().trim_end_matches(')');
+
+            let mut parts = details.split(',').map(|s| s.trim().to_string());
+            let fs_type = parts.next()?;
+            let options = parts.collect();
+
+            Some(MountEntry {
+                device: device.to_string(),
+                mount_point: mount_point.to_string(),
+                fs_type,
+                options,
+            })
+        })
+        .collect()
+}
+
+fn unescape_octal(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() {
+            let digits = std::str::from_utf8(&bytes[i + 1..i + 4]).unwrap_or("");
+            if let Ok(value) = u8::from_str_radix(digits, 8) {
+                out.push(value);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Returns the mount that contains `path`, i.e. the entry with the longest
+/// mount point that is a component-wise prefix of the path.
+pub fn find_mount_for<'a>(path: &str, mounts: &'a [MountEntry]) -> Option<&'a MountEntry> {
+    let path = Path::new(path);
+    mounts
+        .iter()
+        .filter(|m| path.starts_with(&m.mount_point))
+        .max_by_key(|m| Path::new(&m.mount_point).components().count())
+}
+
+/// Classifies `path` from the mount table rather than from its name, so a
+/// folder called "Dropbox archive" on an external drive stays external.
+pub fn classify_path(path: &str, mounts: &[MountEntry], home: &str) -> VolumeClassification {
+    let folder_label = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string());
+
+    // File Provider folders live on the system volume, so they are found by location
+    if let Some(provider) = file_provider_name(path, home) {
+        let mount = find_mount_for(path, mounts);
+        return VolumeClassification {
+            label: provider,
+            destination_type: "cloud".to_string(),
+            fs_type: mount.map(|m| m.fs_type.clone()),
+            removable: false,
+            volume_name: None,
+        };
+    }
+
+    let Some(mount) = find_mount_for(path, mounts) else {
+        // Unmounted network URLs can still be added as destinations
+        let destination_type = if path.starts_with("smb://")
+            || path.starts_with("afp://")
+            || path.starts_with("/net/")
+        {
+            "network"
+        } else {
+            "local"
+        };
+        return VolumeClassification {
+            label: folder_label.unwrap_or_else(|| "Local".to_string()),
+            destination_type: destination_type.to_string(),
+            fs_type: None,
+            removable: false,
+            volume_name: None,
+        };
+    };
+
+    let fs_type = mount.fs_type.to_lowercase();
+    let volume_name = Path::new(&mount.mount_point)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string());
+
+    let (destination_type, removable) =
+        if is_network_fs(&fs_type) || is_gvfs_network_share(&fs_type, &mount.mount_point, path) {
+            ("network", false)
+        } else if is_cloud_fuse(&fs_type, &mount.device) {
+            ("cloud", false)
+        } else if is_external_mount_point(&mount.mount_point) {
+            ("external", true)
+        } else {
+            ("local", false)
+        };
+
+    let label = match destination_type {
+        "external" | "network" | "cloud" => volume_name
+            .clone()
+            .or(folder_label)
+            .unwrap_or_else(|| "Volume".to_string()),
+        _ => folder_label.unwrap_or_else(|| "Local".to_string()),
+    };
+
+    info!(
+        "Classified {} as {} on {} ({})",
+        path, destination_type, mount.mount_point, fs_type
+    );
+
+    VolumeClassification {
+        label,
+        destination_type: destination_type.to_string(),
+        fs_type: Some(fs_type),
+        removable,
+        volume_name,
+    }
+}
+
+fn is_network_fs(fs_type: &str) -> bool {
+    matches!(
+        fs_type,
+        "smbfs" | "cifs" | "smb3" | "nfs" | "nfs4" | "afpfs" | "webdav" | "fuse.sshfs"
+    )
+}
+
+/// GNOME's gvfs exposes every share it has open as a folder of one FUSE mount,
+/// named after the backend, e.g. `smb-share:server=nas,share=studio`.
+const GVFS_NETWORK_BACKENDS: &[&str] = &["smb-share", "sftp", "dav", "ftp", "afp-volume", "nfs"];
+
+fn is_gvfs_network_share(fs_type: &str, mount_point: &str, path: &str) -> bool {
+    if fs_type != "fuse.gvfsd-fuse" {
+        return false;
+    }
+    let Some(share) = Path::new(path)
+        .strip_prefix(mount_point)
+        .ok()
+        .and_then(|rest| rest.components().next())
+    else {
+        return false;
+    };
+    let share = share.as_os_str().to_string_lossy();
+    let backend = share.split(':').next().unwrap_or_default();
+    // "davs" is WebDAV over TLS; "ftps" likewise
+    GVFS_NETWORK_BACKENDS
+        .iter()
+        .any(|b| backend == *b || backend.strip_suffix('s') == Some(*b))
+}
+
+/// Cloud storage clients that mount through FUSE on Linux, by their type.
+const CLOUD_FUSE_TYPES: &[&str] = &[
+    "fuse.rclone",
+    "fuse.s3fs",
+    "fuse.gcsfuse",
+    "fuse.goofys",
+    "fuse.google-drive-ocamlfuse",
+    "fuse.onedriver",
+];
+
+/// Whether a FUSE mount is a cloud drive. Most FUSE filesystems are not:
+/// `fuseblk` is how Linux mounts NTFS and exFAT drives, and NTFS drivers on
+/// macOS mount through macFUSE too. Those are backed by a `/dev` disk, while
+/// cloud clients on macFUSE (such as rclone) name their remote as the device.
+fn is_cloud_fuse(fs_type: &str, device: &str) -> bool {
+    if CLOUD_FUSE_TYPES.contains(&fs_type) || fs_type.starts_with("dfsfuse") {
+        return true;
+    }
+    matches!(fs_type, "macfuse" | "osxfuse" | "fuse") && !device.starts_with("/dev/")
+}
+
+fn is_external_mount_point(mount_point: &str) -> bool {
+    let path = Path::new(mount_point);
+
+    // macOS mounts every non-system volume under /Volumes
+    if let Ok(rest) = path.strip_prefix("/Volumes") {
+        return rest.components().next().is_some();
+    }
+
+    // Linux desktop automounters
+    ["/media", "/run/media", "/mnt"].iter().any(|root| {
+        path.strip_prefix(root)
+            .map(|rest| rest.components().next().is_some())
+            .unwrap_or(false)
+    })
+}
+
+/// Names the cloud provider for paths inside macOS File Provider locations:
+/// `~/Library/CloudStorage/<Provider>-<account>` and iCloud Drive.
+fn file_provider_name(path: &str, home: &str) -> Option<String> {
+    if home.is_empty() {
+        return None;
+    }
+    let path = Path::new(path);
+    let home = Path::new(home);
+
+    if path.starts_with(home.join("Library/Mobile Documents")) {
+        return Some("iCloud".to_string());
+    }
+
+    let rest = path.strip_prefix(home.join("Library/CloudStorage")).ok()?;
+    let folder = match rest.components().next()? {
+        Component::Normal(name) => name.to_string_lossy().to_string(),
+        _ => return None,
+    };
+
+    let provider = folder.split('-').next().unwrap_or(&folder);
+    let label = match provider {
+        "GoogleDrive" => "Google Drive",
+        "OneDrive" => "OneDrive",
+        "Dropbox" => "Dropbox",
+        "Box" => "Box",
+        _ => provider,
+    };
+    Some(label.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINUX_MOUNTINFO: &str = include_str!("testdata/mountinfo-linux.txt");
+    const MACOS_MOUNT: &str = include_str!("testdata/mount-macos.txt");
+    const HOME: &str = "/home/jesse";
+
+    fn classify(path: &str, mounts: &[MountEntry]) -> (String, bool) {
+        let class = classify_path(path, mounts, HOME);
+        (class.destination_type, class.removable)
+    }
+
+    #[test]
+    fn parses_linux_mountinfo() {
+        let mounts = parse_mountinfo(LINUX_MOUNTINFO);
+        assert_eq!(mounts.len(), 15);
+
+        let usb = find_mount_for("/media/jesse/Backup Drive/Sessions", &mounts).unwrap();
+        assert_eq!(usb.mount_point, "/media/jesse/Backup Drive");
+        assert_eq!(usb.fs_type, "fuseblk");
+        assert_eq!(usb.device, "/dev/sdb1");
+        assert!(usb.options.iter().any(|o| o == "allow_other"));
+
+        let root = find_mount_for("/srv/data", &mounts).unwrap();
+        assert_eq!(root.mount_point, "/");
+        assert_eq!(root.options, ["rw", "relatime", "errors=remount-ro"]);
+    }
+
+    #[test]
+    fn classifies_linux_mounts() {
+        let mounts = parse_mountinfo(LINUX_MOUNTINFO);
+        let external = ("external".to_string(), true);
+
+        // NTFS and exFAT drives mount as fuseblk through ntfs-3g and similar drivers
+        assert_eq!(
+            classify("/media/jesse/Backup Drive/Sessions", &mounts),
+            external
+        );
+        assert_eq!(classify("/media/jesse/CARD", &mounts), external);
+        assert_eq!(classify("/mnt/gdrive/Backups", &mounts).0, "cloud");
+        assert_eq!(classify("/mnt/studio/Backups", &mounts).0, "network");
+        assert_eq!(classify("/mnt/archive", &mounts).0, "network");
+        assert_eq!(classify("/home/jesse/remote/backups", &mounts).0, "network");
+        assert_eq!(
+            classify("/run/user/1000/gvfs/smb-share", &mounts).0,
+            "network"
+        );
+        assert_eq!(
+            classify(
+                "/run/user/1000/gvfs/smb-share:server=nas.local,share=studio/Backups",
+                &mounts
+            )
+            .0,
+            "network"
+        );
+        assert_eq!(
+            classify("/run/user/1000/gvfs/sftp:host=server/srv", &mounts).0,
+            "network"
+        );
+        assert_eq!(
+            classify("/run/user/1000/gvfs/davs:host=cloud.example.com", &mounts).0,
+            "network"
+        );
+        assert_eq!(
+            classify("/run/user/1000/gvfs/mtp:host=Phone/DCIM", &mounts).0,
+            "local"
+        );
+        assert_eq!(classify("/home/jesse/Backups", &mounts).0, "local");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn parses_live_mount_table() {
+        let mounts = read_mount_table();
+        let root = find_mount_for("/", &mounts).expect("no root mount");
+        assert_eq!(root.mount_point, "/");
+        assert!(!root.fs_type.is_empty());
+    }
+
+    #[test]
+    fn parses_macos_mount_output() {
+        let mounts = parse_bsd_mount_output(MACOS_MOUNT);
+        assert_eq!(mounts.len(), 12);
+
+        let shoot = find_mount_for("/Volumes/Shoot (Day 2)/Backups", &mounts).unwrap();
+        assert_eq!(shoot.mount_point, "/Volumes/Shoot (Day 2)");
+        assert_eq!(shoot.fs_type, "apfs");
+        assert_eq!(shoot.device, "/dev/disk6s1");
+        assert!(shoot.options.iter().any(|o| o == "noowners"));
+    }
+
+    #[test]
+    fn classifies_macos_mounts() {
+        let mounts = parse_bsd_mount_output(MACOS_MOUNT);
+        let external = ("external".to_string(), true);
+
+        assert_eq!(
+            classify("/Volumes/Backup Drive/Sessions", &mounts),
+            external
+        );
+        assert_eq!(classify("/Volumes/Shoot (Day 2)", &mounts), external);
+        // An NTFS driver on macFUSE is still a disk
+        assert_eq!(classify("/Volumes/WinDisk/Backups", &mounts), external);
+        assert_eq!(classify("/Volumes/gdrive/Backups", &mounts).0, "cloud");
+        assert_eq!(
+            classify("/Volumes/GoogleDrive/My Drive", &mounts).0,
+            "cloud"
+        );
+        assert_eq!(classify("/Volumes/Studio/Backups", &mounts).0, "network");
+        assert_eq!(
+            classify("/System/Volumes/Data/Users/jesse/Backups", &mounts).0,
+            "local"
+        );
+    }
+
+    #[test]
+    fn classifies_file_provider_folders_as_cloud() {
+        let mounts = parse_bsd_mount_output(MACOS_MOUNT);
+        let class = classify_path(
+            "/home/jesse/Library/CloudStorage/Dropbox-Studio/Backups",
+            &mounts,
+            HOME,
+        );
+        assert_eq!(class.destination_type, "cloud");
+        assert_eq!(class.label, "Dropbox");
+    }
+}
@@ -0,0 +1,1138 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+
+use super::backup::{is_cancelled, reset_cancellation, timestamp};
+use super::manifest::list_backup_files;
+use super::policy::{require_plain_name, require_root, FsCommandError, RootKind};
+use super::quarantine::session_backup_path;
+use super::reed_solomon::{coefficient, invert, mul_add, MAX_GROUP_BLOCKS};
+use super::repository::safe_join;
+use super::sidecar::write_atomic;
+
+/// Folder inside a session backup that holds its parity data.
+pub const PARITY_DIR: &str = ".jsync-parity";
+
+/// rclone filter rule that keeps the parity data out of the sync and its delete pass.
+pub const PARITY_FILTER: &str = "- /.jsync-parity/**";
+
+const PARITY_VERSION: u32 = 1;
+
+/// The index is written twice, so one bad sector in it can't make the parity
+/// data unusable.
+const INDEX_FILES: [&str; 2] = ["index.json", "index.copy.json"];
+
+/// Files are protected in blocks of this size. A damaged sector costs the
+/// block around it, and a group's parity blocks are held in memory while it
+/// is encoded.
+const BLOCK_SIZE: u64 = 512 * 1024;
+
+const MAX_REDUNDANCY_PERCENT: u8 = 50;
+
+const CHECK_CANCELLED: &str = "Parity check cancelled";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ParityIndex {
+    version: u32,
+    created_at: String,
+    redundancy_percent: u8,
+    block_size: u64,
+    /// Data blocks per group; the last group may have fewer.
+    group_size: usize,
+    parity_file: String,
+    files: Vec<ProtectedFile>,
+    /// Truncated BLAKE3 hashes of every data block and every parity block.
+    data_hashes: Vec<String>,
+    parity_hashes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProtectedFile {
+    /// Relative to the backup folder, with `/` separators.
+    path: String,
+    size: u64,
+    modified: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ParityInfo {
+    pub path: String,
+    pub files: u64,
+    pub data_blocks: u64,
+    pub parity_blocks: u64,
+    pub parity_bytes: u64,
+    pub redundancy_percent: u8,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ParityReport {
+    pub path: String,
+    pub created_at: String,
+    pub redundancy_percent: u8,
+    pub data_blocks: u64,
+    pub parity_blocks: u64,
+    pub damaged_blocks: u64,
+    pub damaged_parity_blocks: u64,
+    /// Files that are missing or have damaged blocks.
+    pub damaged_files: Vec<String>,
+    /// Files modified since the parity data was created, which it no longer covers.
+    pub changed_files: Vec<String>,
+    pub repairable: bool,
+    pub repaired_files: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ParityProgress {
+    pub session_name: String,
+    pub operation: String,
+    pub percent: f64,
+}
+
+/// Writes parity data for a session backup, replacing any it had. With
+/// `redundancy_percent` at 10, up to a tenth of the blocks in each group can
+/// be lost and still rebuilt.
+#[tauri::command]
+pub async fn create_parity(
+    app: AppHandle,
+    destination_path: String,
+    session_name: String,
+    redundancy_percent: u8,
+) -> Result<ParityInfo, FsCommandError> {
+    info!(
+        "Creating {}% parity for {} on {}",
+        redundancy_percent, session_name, destination_path
+    );
+    require_root(&destination_path, RootKind::Destination)?;
+    let folder = session_backup_path(&destination_path, &session_name)?;
+    reset_cancellation();
+
+    let info = tokio::task::spawn_blocking(move || {
+        let progress = progress_emitter(app, session_name, "create");
+        write_parity(&folder, redundancy_percent, progress)
+    })
+    .await
+    .map_err(|e| format!("Parity creation failed: {}", e))??;
+
+    Ok(info)
+}
+
+/// Reads every block of a session backup and its parity data and reports
+/// what is damaged and whether it can be repaired.
+#[tauri::command]
+pub async fn verify_parity(
+    app: AppHandle,
+    destination_path: String,
+    session_name: String,
+) -> Result<ParityReport, FsCommandError> {
+    info!(
+        "Verifying parity for {} on {}",
+        session_name, destination_path
+    );
+    require_root(&destination_path, RootKind::Destination)?;
+    let folder = session_backup_path(&destination_path, &session_name)?;
+    reset_cancellation();
+
+    let report = tokio::task::spawn_blocking(move || {
+        let mut progress = progress_emitter(app, session_name, "verify");
+        let index = read_index(&folder)?;
+        let damage = inspect(&folder, &index, &mut progress)?;
+        Ok::<_, String>(report(&folder, &index, &damage, Vec::new()))
+    })
+    .await
+    .map_err(|e| format!("Parity verification failed: {}", e))??;
+
+    if report.damaged_files.is_empty() && report.damaged_parity_blocks == 0 {
+        info!("Parity verification of {} found no damage", report.path);
+    } else {
+        warn!(
+            "Parity verification of {}: {} damaged blocks in {} files",
+            report.path,
+            report.damaged_blocks,
+            report.damaged_files.len()
+        );
+    }
+    Ok(report)
+}
+
+/// Rebuilds damaged and missing files of a session backup from its parity
+/// data, along with any damaged parity blocks. Refuses when files changed
+/// since the parity was created, since rebuilding them would undo the change.
+#[tauri::command]
+pub async fn repair_from_parity(
+    app: AppHandle,
+    destination_path: String,
+    session_name: String,
+) -> Result<ParityReport, FsCommandError> {
+    info!(
+        "Repairing {} on {} from parity",
+        session_name, destination_path
+    );
+    require_root(&destination_path, RootKind::Destination)?;
+    let folder = session_backup_path(&destination_path, &session_name)?;
+    reset_cancellation();
+
+    let report = tokio::task::spawn_blocking(move || {
+        let mut progress = progress_emitter(app, session_name, "repair");
+        repair(&folder, &mut progress)
+    })
+    .await
+    .map_err(|e| format!("Parity repair failed: {}", e))??;
+
+    info!(
+        "Repaired {} files in {}",
+        report.repaired_files.len(),
+        report.path
+    );
+    Ok(report)
+}
+
+/// Creates parity data for `folder`. `progress` gets the percentage done and
+/// stops the run when it returns false.
+///
+/// Groups whose blocks are unchanged since the previous parity data keep
+/// their parity blocks, so only groups touched by new or modified files are
+/// encoded again. Files already covered keep their place in the layout, with
+/// new ones after them, so an added file doesn't shift every later block.
+pub fn write_parity(
+    folder: &Path,
+    redundancy_percent: u8,
+    mut progress: impl FnMut(f64) -> bool,
+) -> Result<ParityInfo, String> {
+    if !(1..=MAX_REDUNDANCY_PERCENT).contains(&redundancy_percent) {
+        return Err(format!(
+            "Redundancy must be between 1% and {}%",
+            MAX_REDUNDANCY_PERCENT
+        ));
+    }
+
+    let group_size = MAX_GROUP_BLOCKS * 100 / (100 + usize::from(redundancy_percent));
+    let previous = read_index(folder).ok().filter(|index| {
+        index.block_size == BLOCK_SIZE
+            && index.redundancy_percent == redundancy_percent
+            && index.group_size == group_size
+    });
+
+    let mut files: Vec<ProtectedFile> = list_backup_files(folder)
+        .into_iter()
+        .map(|(path, _, size, modified)| ProtectedFile {
+            path,
+            size,
+            modified,
+        })
+        .collect();
+    if let Some(previous) = &previous {
+        let positions: HashMap<&str, usize> = previous
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| (file.path.as_str(), i))
+            .collect();
+        files.sort_by_key(|file| {
+            positions
+                .get(file.path.as_str())
+                .copied()
+                .unwrap_or(usize::MAX)
+        });
+    }
+    let layout = Layout::new(folder, &files, BLOCK_SIZE);
+    let data_blocks = layout.block_count();
+    if data_blocks == 0 {
+        return Err("There is nothing in this backup to protect".to_string());
+    }
+
+    let parity_dir = folder.join(PARITY_DIR);
+    std::fs::create_dir_all(&parity_dir)
+        .map_err(|e| format!("Failed to create {:?}: {}", parity_dir, e))?;
+    let parity_file = format!("parity-{}.dat", uuid::Uuid::new_v4().simple());
+    let parity_path = parity_dir.join(&parity_file);
+
+    let written = (|| {
+        let mut out = std::io::BufWriter::new(
+            std::fs::File::create(&parity_path)
+                .map_err(|e| format!("Failed to create {:?}: {}", parity_path, e))?,
+        );
+        let mut block = vec![0u8; BLOCK_SIZE as usize];
+        let mut data_hashes = Vec::with_capacity(data_blocks);
+        let mut parity_hashes = Vec::new();
+        let mut reused = 0;
+
+        let previous = previous.as_ref().map(|index| {
+            let old_groups = groups(index.data_hashes.len(), group_size, redundancy_percent);
+            let old_parity = std::fs::File::open(parity_dir.join(&index.parity_file)).ok();
+            (
+                index,
+                Layout::new(folder, &index.files, BLOCK_SIZE),
+                old_groups,
+                old_parity,
+            )
+        });
+
+        for (g, group) in groups(data_blocks, group_size, redundancy_percent)
+            .into_iter()
+            .enumerate()
+        {
+            if let Some((index, old_layout, old_groups, Some(old_parity))) = &previous {
+                let kept = old_groups
+                    .get(g)
+                    .filter(|old| old.data == group.data && old.parity == group.parity)
+                    .filter(|_| {
+                        group
+                            .data
+                            .clone()
+                            .all(|b| same_block(&layout, old_layout, b))
+                    })
+                    .and_then(|old| copy_parity(old_parity, index, old.parity.clone(), &mut out));
+                if let Some(hashes) = kept {
+                    data_hashes.extend_from_slice(&index.data_hashes[group.data.clone()]);
+                    parity_hashes.extend(hashes);
+                    reused += 1;
+                    if !progress(group.data.end as f64 / data_blocks as f64 * 100.0) {
+                        return Err("Parity creation cancelled".to_string());
+                    }
+                    continue;
+                }
+            }
+
+            let mut parity = vec![vec![0u8; BLOCK_SIZE as usize]; group.parity.len()];
+            for (i, b) in group.data.enumerate() {
+                layout
+                    .read_block(b, &mut block)
+                    .map_err(|e| format!("Failed to read block {}: {}", b, e))?;
+                data_hashes.push(block_hash(&block));
+                for (j, acc) in parity.iter_mut().enumerate() {
+                    mul_add(acc, &block, coefficient(j, i));
+                }
+                if !progress((b + 1) as f64 / data_blocks as f64 * 100.0) {
+                    return Err("Parity creation cancelled".to_string());
+                }
+            }
+            for acc in parity {
+                parity_hashes.push(block_hash(&acc));
+                out.write_all(&acc)
+                    .map_err(|e| format!("Failed to write parity data: {}", e))?;
+            }
+        }
+
+        out.into_inner()
+            .map_err(|e| e.into_error())
+            .and_then(|f| f.sync_all())
+            .map_err(|e| format!("Failed to write parity data: {}", e))?;
+        if reused > 0 {
+            info!("Kept the parity of {} unchanged groups", reused);
+        }
+        Ok((data_hashes, parity_hashes))
+    })();
+    let (data_hashes, parity_hashes) = match written {
+        Ok(hashes) => hashes,
+        Err(e) => {
+            let _ = std::fs::remove_file(&parity_path);
+            return Err(e);
+        }
+    };
+
+    let index = ParityIndex {
+        version: PARITY_VERSION,
+        created_at: timestamp(),
+        redundancy_percent,
+        block_size: BLOCK_SIZE,
+        group_size,
+        parity_file,
+        files,
+        data_hashes,
+        parity_hashes,
+    };
+    write_index(&parity_dir, &index)?;
+    remove_old_parity(&parity_dir, &index.parity_file);
+
+    let info = ParityInfo {
+        path: folder.to_string_lossy().to_string(),
+        files: index.files.len() as u64,
+        data_blocks: index.data_hashes.len() as u64,
+        parity_blocks: index.parity_hashes.len() as u64,
+        parity_bytes: index.parity_hashes.len() as u64 * BLOCK_SIZE,
+        redundancy_percent,
+    };
+    info!(
+        "Wrote {} parity blocks for {} data blocks in {:?}",
+        info.parity_blocks, info.data_blocks, folder
+    );
+    Ok(info)
+}
+
+/// Whether data block `block` is made of the same unchanged file ranges in
+/// both layouts, so its content and hash are the same as before.
+fn same_block(layout: &Layout, old: &Layout, block: usize) -> bool {
+    let (now, before) = (layout.segments(block), old.segments(block));
+    now.len() == before.len()
+        && now.iter().zip(&before).all(|(a, b)| {
+            let (file, old_file) = (&layout.files[a.file], &old.files[b.file]);
+            file.path == old_file.path
+                && file.size == old_file.size
+                && file.modified == old_file.modified
+                && a.file_offset == b.file_offset
+                && a.block_offset == b.block_offset
+                && a.len == b.len
+        })
+}
+
+/// Copies a group's parity blocks from the previous parity file, if they are
+/// all intact, and returns their hashes.
+fn copy_parity(
+    old_parity: &std::fs::File,
+    index: &ParityIndex,
+    blocks: Range<usize>,
+    out: &mut impl Write,
+) -> Option<Vec<String>> {
+    let mut file = old_parity;
+    let mut group = Vec::with_capacity(blocks.len());
+    for p in blocks {
+        let mut block = vec![0u8; index.block_size as usize];
+        file.seek(SeekFrom::Start(p as u64 * index.block_size))
+            .and_then(|_| file.read_exact(&mut block))
+            .ok()?;
+        if block_hash(&block) != index.parity_hashes[p] {
+            return None;
+        }
+        group.push(block);
+    }
+    for block in &group {
+        out.write_all(block).ok()?;
+    }
+    Some(group.iter().map(|block| block_hash(block)).collect())
+}
+
+/// What `inspect` found wrong with a backup and its parity data.
+struct Damage {
+    data: Vec<usize>,
+    parity: Vec<usize>,
+    missing_files: Vec<usize>,
+    changed_files: Vec<usize>,
+}
+
+fn inspect(
+    folder: &Path,
+    index: &ParityIndex,
+    progress: &mut impl FnMut(f64) -> bool,
+) -> Result<Damage, String> {
+    let mut damage = Damage {
+        data: Vec::new(),
+        parity: Vec::new(),
+        missing_files: Vec::new(),
+        changed_files: Vec::new(),
+    };
+
+    let layout = Layout::new(folder, &index.files, index.block_size);
+    for (i, file) in index.files.iter().enumerate() {
+        match layout.path(i).and_then(std::fs::metadata) {
+            Err(_) => damage.missing_files.push(i),
+            Ok(metadata) => {
+                if metadata.len() != file.size || modified_secs(&metadata) != file.modified {
+                    damage.changed_files.push(i);
+                }
+            }
+        }
+    }
+
+    let total = (index.data_hashes.len() + index.parity_hashes.len()) as f64;
+    let mut block = vec![0u8; index.block_size as usize];
+    for (b, expected) in index.data_hashes.iter().enumerate() {
+        if layout.read_block(b, &mut block).is_err() || block_hash(&block) != *expected {
+            damage.data.push(b);
+        }
+        if !progress((b + 1) as f64 / total * 100.0) {
+            return Err(CHECK_CANCELLED.to_string());
+        }
+    }
+
+    let mut parity = std::fs::File::open(folder.join(PARITY_DIR).join(&index.parity_file)).ok();
+    for (p, expected) in index.parity_hashes.iter().enumerate() {
+        let intact = parity.as_mut().is_some_and(|f| {
+            read_parity_block(f, p, &mut block).is_ok() && block_hash(&block) == *expected
+        });
+        if !intact {
+            damage.parity.push(p);
+        }
+        if !progress((index.data_hashes.len() + p + 1) as f64 / total * 100.0) {
+            return Err(CHECK_CANCELLED.to_string());
+        }
+    }
+
+    Ok(damage)
+}
+
+fn repair(folder: &Path, progress: &mut impl FnMut(f64) -> bool) -> Result<ParityReport, String> {
+    let index = read_index(folder)?;
+    let damage = inspect(folder, &index, progress)?;
+    let before = report(folder, &index, &damage, Vec::new());
+    if damage.data.is_empty() && damage.parity.is_empty() {
+        return Ok(before);
+    }
+    if !before.changed_files.is_empty() {
+        return Err(format!(
+            "Files changed since the parity data was created, so it can't repair them safely: {}",
+            before.changed_files.join(", ")
+        ));
+    }
+    if !before.repairable {
+        return Err(format!(
+            "Too much damage to repair: {} damaged blocks with {}% redundancy",
+            before.damaged_blocks, index.redundancy_percent
+        ));
+    }
+
+    let layout = Layout::new(folder, &index.files, index.block_size);
+    let parity_path = folder.join(PARITY_DIR).join(&index.parity_file);
+    let block_size = index.block_size as usize;
+    let mut block = vec![0u8; block_size];
+
+    // Missing files are recreated at their recorded size and filled in below
+    for &i in &damage.missing_files {
+        let path = layout
+            .path(i)
+            .map_err(|e| format!("Failed to recreate {}: {}", index.files[i].path, e))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        std::fs::File::create(&path)
+            .and_then(|f| f.set_len(index.files[i].size))
+            .map_err(|e| format!("Failed to recreate {:?}: {}", path, e))?;
+    }
+
+    let mut parity_file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&parity_path)
+        .map_err(|e| format!("Failed to open parity data: {}", e))?;
+
+    for group in groups(
+        index.data_hashes.len(),
+        index.group_size,
+        index.redundancy_percent,
+    ) {
+        let lost: Vec<usize> = group
+            .data
+            .clone()
+            .filter(|b| damage.data.contains(b))
+            .collect();
+
+        if !lost.is_empty() {
+            let rows: Vec<usize> = group
+                .parity
+                .clone()
+                .filter(|p| !damage.parity.contains(p))
+                .take(lost.len())
+                .collect();
+
+            // What the lost blocks add up to in each chosen parity row
+            let mut sums = Vec::with_capacity(rows.len());
+            for &p in &rows {
+                let mut sum = vec![0u8; block_size];
+                read_parity_block(&mut parity_file, p, &mut sum)
+                    .map_err(|e| format!("Failed to read parity block {}: {}", p, e))?;
+                sums.push(sum);
+            }
+            for (i, b) in group.data.clone().enumerate() {
+                if lost.contains(&b) {
+                    continue;
+                }
+                layout
+                    .read_block(b, &mut block)
+                    .map_err(|e| format!("Failed to read block {}: {}", b, e))?;
+                for (sum, &p) in sums.iter_mut().zip(&rows) {
+                    mul_add(sum, &block, coefficient(p - group.parity.start, i));
+                }
+            }
+
+            let matrix = rows
+                .iter()
+                .map(|&p| {
+                    lost.iter()
+                        .map(|&b| coefficient(p - group.parity.start, b - group.data.start))
+                        .collect()
+                })
+                .collect();
+            let inverse = invert(matrix).ok_or("Parity data can't rebuild these blocks")?;
+            for (r, &b) in lost.iter().enumerate() {
+                let mut rebuilt = vec![0u8; block_size];
+                for (sum, &factor) in sums.iter().zip(&inverse[r]) {
+                    mul_add(&mut rebuilt, sum, factor);
+                }
+                if block_hash(&rebuilt) != index.data_hashes[b] {
+                    return Err(format!("Rebuilt block {} does not match its checksum", b));
+                }
+                layout
+                    .write_block(b, &rebuilt)
+                    .map_err(|e| format!("Failed to write block {}: {}", b, e))?;
+            }
+        }
+
+        // Damaged parity blocks are recomputed from the now intact data
+        let stale: Vec<usize> = group
+            .parity
+            .clone()
+            .filter(|p| damage.parity.contains(p))
+            .collect();
+        if !stale.is_empty() {
+            let mut parity = vec![vec![0u8; block_size]; stale.len()];
+            for (i, b) in group.data.clone().enumerate() {
+                layout
+                    .read_block(b, &mut block)
+                    .map_err(|e| format!("Failed to read block {}: {}", b, e))?;
+                for (acc, &p) in parity.iter_mut().zip(&stale) {
+                    mul_add(acc, &block, coefficient(p - group.parity.start, i));
+                }
+            }
+            for (acc, &p) in parity.iter().zip(&stale) {
+                parity_file
+                    .seek(SeekFrom::Start(p as u64 * index.block_size))
+                    .and_then(|_| parity_file.write_all(acc))
+                    .map_err(|e| format!("Failed to write parity block {}: {}", p, e))?;
+            }
+        }
+    }
+    parity_file
+        .sync_all()
+        .map_err(|e| format!("Failed to write parity data: {}", e))?;
+
+    // Give repaired files back their original modification times, so the
+    // manifest and the next sync see them as unchanged
+    let repaired: Vec<usize> = damaged_files(&layout, &damage);
+    for &i in &repaired {
+        let file = &index.files[i];
+        let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(file.modified);
+        let set = layout
+            .path(i)
+            .and_then(|path| std::fs::File::options().write(true).open(path))
+            .and_then(|f| f.set_modified(modified));
+        if let Err(e) = set {
+            warn!(
+                "Failed to restore modification time of {}: {}",
+                file.path, e
+            );
+        }
+    }
+
+    let damage = inspect(folder, &index, progress)?;
+    let repaired_files = repaired
+        .into_iter()
+        .map(|i| index.files[i].path.clone())
+        .collect();
+    Ok(report(folder, &index, &damage, repaired_files))
+}
+
+fn report(
+    folder: &Path,
+    index: &ParityIndex,
+    damage: &Damage,
+    repaired_files: Vec<String>,
+) -> ParityReport {
+    let layout = Layout::new(folder, &index.files, index.block_size);
+    let groups = groups(
+        index.data_hashes.len(),
+        index.group_size,
+        index.redundancy_percent,
+    );
+    let repairable = damage.changed_files.is_empty()
+        && groups.iter().all(|g| {
+            let lost = damage.data.iter().filter(|b| g.data.contains(b)).count();
+            let intact = g.parity.len()
+                - damage
+                    .parity
+                    .iter()
+                    .filter(|p| g.parity.contains(p))
+                    .count();
+            lost <= intact
+        });
+
+    ParityReport {
+        path: folder.to_string_lossy().to_string(),
+        created_at: index.created_at.clone(),
+        redundancy_percent: index.redundancy_percent,
+        data_blocks: index.data_hashes.len() as u64,
+        parity_blocks: index.parity_hashes.len() as u64,
+        damaged_blocks: damage.data.len() as u64,
+        damaged_parity_blocks: damage.parity.len() as u64,
+        damaged_files: damaged_files(&layout, damage)
+            .into_iter()
+            .filter(|i| !damage.changed_files.contains(i))
+            .map(|i| index.files[i].path.clone())
+            .collect(),
+        changed_files: damage
+            .changed_files
+            .iter()
+            .map(|&i| index.files[i].path.clone())
+            .collect(),
+        repairable,
+        repaired_files,
+    }
+}
+
+/// Files that are missing or overlap a damaged block, in index order.
+fn damaged_files(layout: &Layout, damage: &Damage) -> Vec<usize> {
+    let mut files: Vec<usize> = damage
+        .data
+        .iter()
+        .flat_map(|&b| layout.segments(b).into_iter().map(|s| s.file))
+        .chain(damage.missing_files.iter().copied())
+        .collect();
+    files.sort_unstable();
+    files.dedup();
+    files
+}
+
+/// The data and parity blocks of one group.
+struct Group {
+    data: Range<usize>,
+    parity: Range<usize>,
+}
+
+fn groups(data_blocks: usize, group_size: usize, redundancy_percent: u8) -> Vec<Group> {
+    let mut groups = Vec::new();
+    let mut parity_start = 0;
+    for data_start in (0..data_blocks).step_by(group_size.max(1)) {
+        let data_end = (data_start + group_size).min(data_blocks);
+        let parity_count = ((data_end - data_start) * usize::from(redundancy_percent))
+            .div_ceil(100)
+            .max(1);
+        groups.push(Group {
+            data: data_start..data_end,
+            parity: parity_start..parity_start + parity_count,
+        });
+        parity_start += parity_count;
+    }
+    groups
+}
+
+/// Part of a data block that lies in one file.
+struct Segment {
+    file: usize,
+    file_offset: u64,
+    block_offset: usize,
+    len: usize,
+}
+
+/// The protected files laid end to end and cut into blocks.
+struct Layout<'a> {
+    folder: &'a Path,
+    files: &'a [ProtectedFile],
+    starts: Vec<u64>,
+    total: u64,
+    block_size: u64,
+}
+
+impl<'a> Layout<'a> {
+    fn new(folder: &'a Path, files: &'a [ProtectedFile], block_size: u64) -> Self {
+        let mut starts = Vec::with_capacity(files.len());
+        let mut total = 0;
+        for file in files {
+            starts.push(total);
+            total += file.size;
+        }
+        Layout {
+            folder,
+            files,
+            starts,
+            total,
+            block_size,
+        }
+    }
+
+    fn block_count(&self) -> usize {
+        self.total.div_ceil(self.block_size) as usize
+    }
+
+    fn segments(&self, block: usize) -> Vec<Segment> {
+        let start = block as u64 * self.block_size;
+        let end = (start + self.block_size).min(self.total);
+        let mut segments = Vec::new();
+        let mut pos = start;
+        let mut file = self.starts.partition_point(|&s| s <= pos).saturating_sub(1);
+        while pos < end && file < self.files.len() {
+            let file_offset = pos - self.starts[file];
+            let len = (self.files[file].size.saturating_sub(file_offset)).min(end - pos);
+            if len > 0 {
+                segments.push(Segment {
+                    file,
+                    file_offset,
+                    block_offset: (pos - start) as usize,
+                    len: len as usize,
+                });
+                pos += len;
+            }
+            file += 1;
+        }
+        segments
+    }
+
+    /// Reads a data block, padded with zeros past the end of the last file.
+    fn read_block(&self, block: usize, buf: &mut [u8]) -> std::io::Result<()> {
+        buf.fill(0);
+        for segment in self.segments(block) {
+            let mut file = std::fs::File::open(self.path(segment.file)?)?;
+            file.seek(SeekFrom::Start(segment.file_offset))?;
+            file.read_exact(&mut buf[segment.block_offset..segment.block_offset + segment.len])?;
+        }
+        Ok(())
+    }
+
+    fn write_block(&self, block: usize, buf: &[u8]) -> std::io::Result<()> {
+        for segment in self.segments(block) {
+            let mut file = std::fs::File::options()
+                .write(true)
+                .open(self.path(segment.file)?)?;
+            file.seek(SeekFrom::Start(segment.file_offset))?;
+            file.write_all(&buf[segment.block_offset..segment.block_offset + segment.len])?;
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn path(&self, file: usize) -> std::io::Result<PathBuf> {
+        let relative = &self.files[file].path;
+        safe_join(self.folder, relative).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is outside the backup folder", relative),
+            )
+        })
+    }
+}
+
+fn read_parity_block(
+    file: &mut std::fs::File,
+    block: usize,
+    buf: &mut [u8],
+) -> std::io::Result<()> {
+    file.seek(SeekFrom::Start(block as u64 * buf.len() as u64))?;
+    file.read_exact(buf)
+}
+
+fn read_index(folder: &Path) -> Result<ParityIndex, String> {
+    let parity_dir = folder.join(PARITY_DIR);
+    INDEX_FILES
+        .iter()
+        .filter_map(|name| std::fs::read_to_string(parity_dir.join(name)).ok())
+        .filter_map(|content| serde_json::from_str::<ParityIndex>(&content).ok())
+        .find(is_valid_index)
+        .ok_or_else(|| "This backup has no readable parity data".to_string())
+}
+
+/// Whether an index read from disk can be used as is: its sizes are ones
+/// `create` could have written, its paths stay inside the backup folder and
+/// its hash lists match its layout.
+fn is_valid_index(index: &ParityIndex) -> bool {
+    if index.version > PARITY_VERSION
+        || !(1..=BLOCK_SIZE).contains(&index.block_size)
+        || !(1..=MAX_REDUNDANCY_PERCENT).contains(&index.redundancy_percent)
+    {
+        return false;
+    }
+    let max_group_size = MAX_GROUP_BLOCKS * 100 / (100 + usize::from(index.redundancy_percent));
+    if !(1..=max_group_size).contains(&index.group_size) {
+        return false;
+    }
+    if require_plain_name(&index.parity_file).is_err()
+        || index
+            .files
+            .iter()
+            .any(|f| safe_join(Path::new(""), &f.path).is_none())
+    {
+        return false;
+    }
+    let Some(total) = index
+        .files
+        .iter()
+        .try_fold(0u64, |total, f| total.checked_add(f.size))
+    else {
+        return false;
+    };
+    let data_blocks = total.div_ceil(index.block_size);
+    if index.data_hashes.len() as u64 != data_blocks {
+        return false;
+    }
+    let parity_blocks = groups(
+        index.data_hashes.len(),
+        index.group_size,
+        index.redundancy_percent,
+    )
+    .last()
+    .map_or(0, |g| g.parity.end);
+    index.parity_hashes.len() == parity_blocks
+}
+
+fn write_index(parity_dir: &Path, index: &ParityIndex) -> Result<(), String> {
+    let content = serde_json::to_vec(index)
+        .map_err(|e| format!("Failed to serialize parity index: {}", e))?;
+
+    for name in INDEX_FILES {
+        write_atomic(&parity_dir.join(name), &content)
+            .map_err(|e| format!("Failed to write parity index: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Deletes parity files from earlier runs that the index no longer points to.
+fn remove_old_parity(parity_dir: &Path, current: &str) {
+    let Ok(entries) = std::fs::read_dir(parity_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with("parity-") && name != current {
+            if let Err(e) = std::fs::remove_file(entry.path()) {
+                warn!("Failed to remove old parity file {:?}: {}", entry.path(), e);
+            }
+        }
+    }
+}
+
+fn block_hash(block: &[u8]) -> String {
+    blake3::hash(block).to_hex()[..32].to_string()
+}
+
+fn modified_secs(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Emits `parity-progress` events and stops the run once it is cancelled.
+fn progress_emitter(
+    app: AppHandle,
+    session_name: String,
+    operation: &'static str,
+) -> impl FnMut(f64) -> bool {
+    let mut last_percent = -1.0;
+    move |percent| {
+        if percent - last_percent >= 0.1 || percent >= 100.0 {
+            last_percent = percent;
+            let _ = app.emit(
+                "parity-progress",
+                ParityProgress {
+                    session_name: session_name.clone(),
+                    operation: operation.to_string(),
+                    percent,
+                },
+            );
+        }
+        !is_cancelled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A backup folder with `blocks` blocks of data spread over two files.
+    fn backup(blocks: u64) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("jsync-parity-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(folder.join("Capture")).unwrap();
+        let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut bytes = |len: u64| -> Vec<u8> {
+            (0..len)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    seed as u8
+                })
+                .collect()
+        };
+        let first = blocks * BLOCK_SIZE - 3000;
+        std::fs::write(folder.join("Capture/a.iiq"), bytes(first)).unwrap();
+        std::fs::write(folder.join("Capture/b.cos"), bytes(1000)).unwrap();
+        folder
+    }
+
+    /// Overwrites a byte in each of `blocks`, keeping the file's modification
+    /// time the way silent corruption on disk would.
+    fn corrupt(folder: &Path, blocks: &[u64]) {
+        let path = folder.join("Capture/a.iiq");
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        let mut file = std::fs::File::options().write(true).open(&path).unwrap();
+        for &b in blocks {
+            file.seek(SeekFrom::Start(b * BLOCK_SIZE + 17)).unwrap();
+            file.write_all(&[0xff, 0x00, 0xff]).unwrap();
+        }
+        file.set_modified(modified).unwrap();
+    }
+
+    fn contents(folder: &Path) -> Vec<u8> {
+        std::fs::read(folder.join("Capture/a.iiq")).unwrap()
+    }
+
+    #[test]
+    fn groups_cover_every_block_once() {
+        let layout = groups(1000, 170, 50);
+        assert_eq!(layout.first().unwrap().data, 0..170);
+        assert_eq!(layout.last().unwrap().data.end, 1000);
+        for pair in layout.windows(2) {
+            assert_eq!(pair[0].data.end, pair[1].data.start);
+            assert_eq!(pair[0].parity.end, pair[1].parity.start);
+        }
+        assert!(layout
+            .iter()
+            .all(|g| g.data.len() + g.parity.len() <= MAX_GROUP_BLOCKS));
+    }
+
+    #[test]
+    fn repairs_damage_up_to_the_parity_count() {
+        let folder = backup(6);
+        let original = contents(&folder);
+        let info = write_parity(&folder, 50, |_| true).unwrap();
+        assert_eq!((info.data_blocks, info.parity_blocks), (6, 3));
+
+        corrupt(&folder, &[0, 2, 4]);
+        let report = repair(&folder, &mut |_| true).unwrap();
+        assert_eq!(report.repaired_files, ["Capture/a.iiq"]);
+        assert_eq!(report.damaged_blocks, 0);
+        assert_eq!(contents(&folder), original);
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn refuses_damage_past_the_parity_count() {
+        let folder = backup(6);
+        write_parity(&folder, 50, |_| true).unwrap();
+
+        corrupt(&folder, &[0, 1, 2, 3]);
+        let index = read_index(&folder).unwrap();
+        let damage = inspect(&folder, &index, &mut |_| true).unwrap();
+        let before = report(&folder, &index, &damage, Vec::new());
+        assert_eq!(before.damaged_blocks, 4);
+        assert!(!before.repairable);
+        assert!(repair(&folder, &mut |_| true).is_err());
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn rebuilds_lost_parity_blocks() {
+        let folder = backup(4);
+        write_parity(&folder, 50, |_| true).unwrap();
+        let index = read_index(&folder).unwrap();
+        let parity_path = folder.join(PARITY_DIR).join(&index.parity_file);
+        let intact = std::fs::read(&parity_path).unwrap();
+
+        let mut damaged = intact.clone();
+        damaged[BLOCK_SIZE as usize + 5] ^= 0xff;
+        std::fs::write(&parity_path, damaged).unwrap();
+        corrupt(&folder, &[3]);
+
+        let report = repair(&folder, &mut |_| true).unwrap();
+        assert_eq!(
+            (report.damaged_blocks, report.damaged_parity_blocks),
+            (0, 0)
+        );
+        assert_eq!(std::fs::read(&parity_path).unwrap(), intact);
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn rejects_indexes_that_cannot_be_repaired_from() {
+        let folder = backup(3);
+        write_parity(&folder, 50, |_| true).unwrap();
+        let valid = read_index(&folder).unwrap();
+        assert!(is_valid_index(&valid));
+
+        let edited = |edit: fn(&mut ParityIndex)| {
+            let mut index = read_index(&folder).unwrap();
+            edit(&mut index);
+            is_valid_index(&index)
+        };
+        assert!(!edited(|index| index.block_size = 0));
+        assert!(!edited(|index| index.block_size = u64::MAX));
+        assert!(!edited(|index| index.group_size = 0));
+        assert!(!edited(|index| index.group_size = usize::MAX));
+        assert!(!edited(
+            |index| index.files[0].path = "../escape".to_string()
+        ));
+        assert!(!edited(
+            |index| index.files[0].path = "/etc/hosts".to_string()
+        ));
+        assert!(!edited(
+            |index| index.parity_file = "../parity.dat".to_string()
+        ));
+        assert!(!edited(|index| index.files[0].size += BLOCK_SIZE));
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn cancelled_checks_stop() {
+        let folder = backup(3);
+        write_parity(&folder, 10, |_| true).unwrap();
+        let index = read_index(&folder).unwrap();
+        let mut calls = 0;
+        let result = inspect(&folder, &index, &mut |_| {
+            calls += 1;
+            false
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+        assert!(write_parity(&folder, 10, |_| false).is_err());
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn unchanged_blocks_keep_their_place() {
+        let file = |path: &str, size: u64, modified: u64| ProtectedFile {
+            path: path.to_string(),
+            size,
+            modified,
+        };
+        let folder = Path::new("/backup");
+        let before = [file("a", 3 * BLOCK_SIZE + 10, 1), file("b", 100, 1)];
+        let old = Layout::new(folder, &before, BLOCK_SIZE);
+
+        // A new file after the covered ones only touches the last block
+        let appended = [
+            file("a", 3 * BLOCK_SIZE + 10, 1),
+            file("b", 100, 1),
+            file("c", 50, 2),
+        ];
+        let layout = Layout::new(folder, &appended, BLOCK_SIZE);
+        assert!((0..3).all(|b| same_block(&layout, &old, b)));
+        assert!(!same_block(&layout, &old, 3));
+
+        // A modified file changes every block it is in
+        let modified = [file("a", 3 * BLOCK_SIZE + 10, 5), file("b", 100, 1)];
+        let layout = Layout::new(folder, &modified, BLOCK_SIZE);
+        assert!((0..4).all(|b| !same_block(&layout, &old, b)));
+    }
+
+    #[test]
+    fn rewriting_unchanged_backups_keeps_their_parity() {
+        let folder = backup(4);
+        write_parity(&folder, 50, |_| true).unwrap();
+        let first = read_index(&folder).unwrap();
+
+        let mut blocks = 0;
+        write_parity(&folder, 50, |_| {
+            blocks += 1;
+            true
+        })
+        .unwrap();
+        let second = read_index(&folder).unwrap();
+        assert_eq!(blocks, 1, "an unchanged group is copied, not encoded");
+        assert_eq!(first.parity_hashes, second.parity_hashes);
+        assert_ne!(first.parity_file, second.parity_file);
+
+        corrupt(&folder, &[1]);
+        let report = repair(&folder, &mut |_| true).unwrap();
+        assert_eq!(report.repaired_files, ["Capture/a.iiq"]);
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+use log::{info, warn};
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+
+use super::probe::PROBE_TIMEOUT;
+
+/// Folders the webview is allowed to read or change. Session folders are
+/// registered when Capture One reports them and destination roots when the user
+/// picks them in the native dialog or they are loaded from a session's sidecar.
+/// Anything outside these roots is refused, whatever the frontend asks for.
+static ALLOWED_ROOTS: Mutex<Vec<AllowedRoot>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RootKind {
+    Session,
+    Destination,
+}
+
+#[derive(Debug, Clone)]
+struct AllowedRoot {
+    path: PathBuf,
+    kind: RootKind,
+}
+
+/// Error returned by filesystem commands that take paths from the webview.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FsCommandError {
+    PathNotAllowed { path: String },
+    InvalidPath { path: String, reason: String },
+    Failed { message: String },
+}
+
+impl std::fmt::Display for FsCommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FsCommandError::PathNotAllowed { path } => {
+                write!(f, "Path is outside the allowed folders: {}", path)
+            }
+            FsCommandError::InvalidPath { path, reason } => {
+                write!(f, "Invalid path {}: {}", path, reason)
+            }
+            FsCommandError::Failed { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<FsCommandError> for String {
+    fn from(e: FsCommandError) -> Self {
+        e.to_string()
+    }
+}
+
+impl From<String> for FsCommandError {
+    fn from(message: String) -> Self {
+        FsCommandError::Failed { message }
+    }
+}
+
+pub fn register_session_root(path: &str) {
+    register(path, RootKind::Session);
+}
+
+pub fn register_destination_root(path: &str) {
+    register(path, RootKind::Destination);
+}
+
+fn register(path: &str, kind: RootKind) {
+    let canonical = match canonicalize_lenient(path) {
+        Ok(canonical) => canonical,
+        // The path is well formed but its volume isn't answering. Registering it
+        // as given can only cause false refusals later, never let a path escape,
+        // since requests are canonicalised before they are compared.
+        Err(FsCommandError::Failed { message }) => {
+            warn!(
+                "Registering {:?} root {} unresolved: {}",
+                kind, path, message
+            );
+            PathBuf::from(path)
+        }
+        Err(_) => {
+            warn!("Refusing to register invalid {:?} root: {}", kind, path);
+            return;
+        }
+    };
+
+    let mut roots = ALLOWED_ROOTS.lock().unwrap_or_else(|e| e.into_inner());
+    if roots.iter().any(|r| r.path == canonical && r.kind == kind) {
+        return;
+    }
+    info!("Allowing {:?} root {:?}", kind, canonical);
+    roots.push(AllowedRoot {
+        path: canonical,
+        kind,
+    });
+}
+
+/// All registered roots of `kind`, canonicalised.
+pub fn registered_roots(kind: RootKind) -> Vec<PathBuf> {
+    let roots = ALLOWED_ROOTS.lock().unwrap_or_else(|e| e.into_inner());
+    roots
+        .iter()
+        .filter(|r| r.kind == kind)
+        .map(|r| r.path.clone())
+        .collect()
+}
+
+/// Checks that `path` is one of the registered roots of `kind` itself.
+pub fn require_root(path: &str, kind: RootKind) -> Result<PathBuf, FsCommandError> {
+    let canonical = canonicalize_lenient(path)?;
+    let roots = ALLOWED_ROOTS.lock().unwrap_or_else(|e| e.into_inner());
+    if roots.iter().any(|r| r.kind == kind && r.path == canonical) {
+        Ok(canonical)
+    } else {
+        warn!("Blocked access to unregistered {:?} root: {}", kind, path);
+        Err(FsCommandError::PathNotAllowed {
+            path: path.to_string(),
+        })
+    }
+}
+
+/// Checks that `path` is a registered root or lies inside one.
+pub fn require_within(path: &str, kinds: &[RootKind]) -> Result<PathBuf, FsCommandError> {
+    let canonical = canonicalize_lenient(path)?;
+    let roots = ALLOWED_ROOTS.lock().unwrap_or_else(|e| e.into_inner());
+    if roots
+        .iter()
+        .any(|r| kinds.contains(&r.kind) && canonical.starts_with(&r.path))
+    {
+        Ok(canonical)
+    } else {
+        warn!("Blocked access outside allowed roots: {}", path);
+        Err(FsCommandError::PathNotAllowed {
+            path: path.to_string(),
+        })
+    }
+}
+
+/// Checks that `name` is a single plain file or folder name, so it can be joined
+/// onto an allowed root without escaping it.
+pub fn require_plain_name(name: &str) -> Result<(), FsCommandError> {
+    let mut components = Path::new(name).components();
+    let is_plain =
+        matches!(components.next(), Some(Component::Normal(_))) && components.next().is_none();
+    if is_plain {
+        Ok(())
+    } else {
+        Err(FsCommandError::InvalidPath {
+            path: name.to_string(),
+            reason: "expected a single folder name".to_string(),
+        })
+    }
+}
+
+/// Canonicalises `path` even when its tail doesn't exist yet, by resolving the
+/// deepest existing ancestor and appending the rest. `..` is rejected outright
+/// so a missing folder can't be used to step outside a root.
+fn canonicalize_lenient(path: &str) -> Result<PathBuf, FsCommandError> {
+    let invalid = |reason: &str| FsCommandError::InvalidPath {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    };
+
+    let original = Path::new(path);
+    if !original.is_absolute() {
+        return Err(invalid("path must be absolute"));
+    }
+    if original
+        .components()
+        .any(|c| matches!(c, Component::ParentDir))
+    {
+        return Err(invalid("path must not contain '..'"));
+    }
+
+    let mut existing = original;
+    let mut missing = Vec::new();
+    loop {
+        match canonicalize_with_timeout(existing)? {
+            Ok(mut resolved) => {
+                resolved.extend(missing.iter().rev());
+                return Ok(resolved);
+            }
+            Err(_) => {
+                missing.push(
+                    existing
+                        .file_name()
+                        .ok_or_else(|| invalid("path does not exist"))?,
+                );
+                existing = existing
+                    .parent()
+                    .ok_or_else(|| invalid("path does not exist"))?;
+            }
+        }
+    }
+}
+
+/// `std::fs::canonicalize` bounded by `PROBE_TIMEOUT`, since it stats every
+/// component and a dead network mount would block it indefinitely. The worker
+/// thread may stay stuck, but the caller is released.
+fn canonicalize_with_timeout(path: &Path) -> Result<std::io::Result<PathBuf>, FsCommandError> {
+    let (sender, receiver) = mpsc::channel();
+    let owned = path.to_path_buf();
+    std::thread::spawn(move || {
+        let _ = sender.send(std::fs::canonicalize(owned));
+    });
+    receiver
+        .recv_timeout(PROBE_TIMEOUT)
+        .map_err(|_| FsCommandError::Failed {
+            message: format!(
+                "{} did not respond within {}s",
+                path.display(),
+                PROBE_TIMEOUT.as_secs()
+            ),
+        })
+}
@@ -0,0 +1,171 @@
+use log::{info, warn};
+use serde::Serialize;
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::policy::{require_within, FsCommandError, RootKind};
+
+/// Upper bound for any filesystem call against a destination. A vanished SMB or
+/// NFS server otherwise blocks the calling thread until the kernel gives up.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Round trips slower than this still work but are reported as slow.
+const SLOW_THRESHOLD: Duration = Duration::from_millis(1500);
+
+const PROBE_PAYLOAD: &[u8] = b"jsync-probe";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeStatus {
+    Reachable,
+    ReadOnly,
+    Slow,
+    Unreachable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResult {
+    pub status: ProbeStatus,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+impl ProbeResult {
+    /// Whether a backup can be written to the probed destination.
+    pub fn is_writable(&self) -> bool {
+        matches!(self.status, ProbeStatus::Reachable | ProbeStatus::Slow)
+    }
+}
+
+/// Runs a blocking filesystem operation on the blocking pool, giving up after
+/// `timeout`. The worker thread may stay stuck on a dead mount, but the caller
+/// (and the UI waiting on it) is released.
+pub async fn run_blocking_with_timeout<T, F>(timeout: Duration, f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    match tokio::time::timeout(timeout, tokio::task::spawn_blocking(f)).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => Err(format!("Filesystem task failed: {}", e)),
+        Err(_) => Err(format!("Timed out after {}s", timeout.as_secs())),
+    }
+}
+
+#[tauri::command]
+pub async fn probe_destination(path: String) -> Result<ProbeResult, FsCommandError> {
+    require_within(&path, &[RootKind::Destination])?;
+    Ok(probe_path(&path).await)
+}
+
+/// Checks that `path` answers a stat and survives a write/read/delete round trip.
+pub async fn probe_path(path: &str) -> ProbeResult {
+    let started = Instant::now();
+    let task_path = path.to_string();
+
+    let outcome =
+        run_blocking_with_timeout(PROBE_TIMEOUT, move || round_trip(Path::new(&task_path))).await;
+    let latency = started.elapsed();
+    let latency_ms = latency.as_millis() as u64;
+
+    let result = match outcome {
+        Ok(Ok(())) if latency > SLOW_THRESHOLD => ProbeResult {
+            status: ProbeStatus::Slow,
+            latency_ms,
+            error: None,
+        },
+        Ok(Ok(())) => ProbeResult {
+            status: ProbeStatus::Reachable,
+            latency_ms,
+            error: None,
+        },
+        Ok(Err((status, e))) => ProbeResult {
+            status,
+            latency_ms,
+            error: Some(e),
+        },
+        Err(e) => ProbeResult {
+            status: ProbeStatus::Unreachable,
+            latency_ms,
+            error: Some(e),
+        },
+    };
+
+    if result.is_writable() {
+        info!(
+            "Probed {}: {:?} in {}ms",
+            path, result.status, result.latency_ms
+        );
+    } else {
+        warn!(
+            "Probed {}: {:?} in {}ms ({})",
+            path,
+            result.status,
+            result.latency_ms,
+            result.error.as_deref().unwrap_or("")
+        );
+    }
+
+    result
+}
+
+fn round_trip(path: &Path) -> Result<(), (ProbeStatus, String)> {
+    let metadata = std::fs::metadata(path).map_err(|e| {
+        (
+            ProbeStatus::Unreachable,
+            format!("Destination not accessible: {}", e),
+        )
+    })?;
+    if !metadata.is_dir() {
+        return Err((
+            ProbeStatus::Unreachable,
+            "Destination is not a folder".to_string(),
+        ));
+    }
+
+    let probe_file = path.join(format!(".jsync-probe-{}", uuid::Uuid::new_v4()));
+
+    let write = std::fs::File::create(&probe_file).and_then(|mut f| {
+        f.write_all(PROBE_PAYLOAD)?;
+        f.sync_all()
+    });
+    if let Err(e) = write {
+        let _ = std::fs::remove_file(&probe_file);
+        let status = if is_read_only_error(&e) {
+            ProbeStatus::ReadOnly
+        } else {
+            ProbeStatus::Unreachable
+        };
+        return Err((status, format!("Destination is not writable: {}", e)));
+    }
+
+    let mut contents = Vec::new();
+    let read = std::fs::File::open(&probe_file).and_then(|mut f| f.read_to_end(&mut contents));
+    let removed = std::fs::remove_file(&probe_file);
+
+    read.map_err(|e| {
+        (
+            ProbeStatus::Unreachable,
+            format!("Failed to read back probe file: {}", e),
+        )
+    })?;
+    if contents != PROBE_PAYLOAD {
+        return Err((
+            ProbeStatus::Unreachable,
+            "Probe file read back with different contents".to_string(),
+        ));
+    }
+    removed.map_err(|e| {
+        (
+            ProbeStatus::Unreachable,
+            format!("Failed to remove probe file: {}", e),
+        )
+    })?;
+
+    Ok(())
+}
+
+fn is_read_only_error(e: &std::io::Error) -> bool {
+    e.kind() == ErrorKind::PermissionDenied || e.raw_os_error() == Some(libc::EROFS)
+}
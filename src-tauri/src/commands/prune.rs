@@ -0,0 +1,289 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use super::destinations::read_disk_info;
+use super::policy::{registered_roots, require_root, FsCommandError, RootKind};
+use super::probe::{run_blocking_with_timeout, PROBE_TIMEOUT};
+use super::quarantine::quarantine_folder;
+use super::receipt::{read_receipt, BackupReceipt};
+use super::scan::{scan_cache_path, scan_sessions, ScannedSession};
+use super::session::session_config_path;
+use super::sidecar::read_session_config;
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// Retention rules for a destination, kept with it in the session config.
+/// Sessions backed up within
+/// `keep_within_days` are always kept. Older ones are pruned oldest first until
+/// `min_free_bytes` is available, or all of them when no free-space target is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    #[serde(default)]
+    pub keep_within_days: Option<u32>,
+    #[serde(default)]
+    pub min_free_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PruneCandidate {
+    pub session: ScannedSession,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrunePlan {
+    pub destination_path: String,
+    pub available_bytes: u64,
+    /// Free space once everything in `prune` has been deleted.
+    pub projected_available_bytes: u64,
+    pub prune: Vec<PruneCandidate>,
+    pub keep: Vec<PruneCandidate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PruneResult {
+    pub pruned: Vec<String>,
+    pub freed_bytes: u64,
+    pub errors: Vec<String>,
+}
+
+/// Previews a prune of a session's destination under the retention policy
+/// stored for it.
+#[tauri::command]
+pub async fn plan_prune(
+    app: AppHandle,
+    session_path: String,
+    session_name: String,
+    destination_id: u64,
+) -> Result<PrunePlan, FsCommandError> {
+    let (destination_path, policy) =
+        destination_policy(&session_path, &session_name, destination_id)?;
+    info!("Planning prune of {} with {:?}", destination_path, policy);
+    let root = require_root(&destination_path, RootKind::Destination)?;
+    build_plan(&app, &root, &destination_path, &policy).await
+}
+
+/// Moves the sessions in `session_paths`, which must come from a preview, into
+/// the destination's quarantine, where they can be restored until it is
+/// purged. The plan is rebuilt first and only sessions it still marks for
+/// pruning are moved, so a stale or edited list can't remove anything else.
+#[tauri::command]
+pub async fn apply_prune(
+    app: AppHandle,
+    session_path: String,
+    session_name: String,
+    destination_id: u64,
+    session_paths: Vec<String>,
+) -> Result<PruneResult, FsCommandError> {
+    let (destination_path, policy) =
+        destination_policy(&session_path, &session_name, destination_id)?;
+    info!(
+        "Applying prune of {} sessions on {}",
+        session_paths.len(),
+        destination_path
+    );
+    let root = require_root(&destination_path, RootKind::Destination)?;
+    let plan = build_plan(&app, &root, &destination_path, &policy).await?;
+
+    let targets: Vec<ScannedSession> = plan
+        .prune
+        .into_iter()
+        .map(|c| c.session)
+        .filter(|s| session_paths.contains(&s.path))
+        .collect();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let mut result = PruneResult {
+            pruned: Vec::new(),
+            freed_bytes: 0,
+            errors: Vec::new(),
+        };
+        for session in targets {
+            match quarantine_folder(&root, Path::new(&session.path)) {
+                Ok(entry) => {
+                    info!("Pruned session backup {} into {}", session.path, entry.id);
+                    result.freed_bytes += session.size_bytes;
+                    result.pruned.push(session.path);
+                }
+                Err(e) => {
+                    warn!("Failed to prune {}: {}", session.path, e);
+                    result
+                        .errors
+                        .push(format!("Failed to prune {}: {}", session.path, e));
+                }
+            }
+        }
+        result
+    })
+    .await
+    .map_err(|e| format!("Prune failed: {}", e))?;
+
+    Ok(result)
+}
+
+/// The path and retention policy of a destination in the session config.
+fn destination_policy(
+    session_path: &str,
+    session_name: &str,
+    destination_id: u64,
+) -> Result<(String, RetentionPolicy), FsCommandError> {
+    let config = read_session_config(&session_config_path(session_path, session_name)?)?;
+    let dest = config
+        .destinations
+        .into_iter()
+        .find(|d| d.id == destination_id)
+        .ok_or_else(|| format!("No destination {} in this session", destination_id))?;
+    let policy = dest
+        .retention
+        .ok_or_else(|| format!("No retention policy is set for {}", dest.label))?;
+    Ok((dest.path, policy))
+}
+
+async fn build_plan(
+    app: &AppHandle,
+    root: &Path,
+    destination_path: &str,
+    policy: &RetentionPolicy,
+) -> Result<PrunePlan, FsCommandError> {
+    let disk_path = destination_path.to_string();
+    let disk = run_blocking_with_timeout(PROBE_TIMEOUT, move || read_disk_info(&disk_path))
+        .await
+        .map_err(|e| format!("Destination not accessible: {} ({})", destination_path, e))??;
+
+    let root = root.to_path_buf();
+    let cache_path = scan_cache_path(app);
+    let policy = policy.clone();
+    let mut plan = PrunePlan {
+        destination_path: destination_path.to_string(),
+        available_bytes: disk.available_bytes,
+        projected_available_bytes: disk.available_bytes,
+        prune: Vec::new(),
+        keep: Vec::new(),
+    };
+
+    // Scanning and reading other destinations' receipts all touch the disk
+    let plan = tokio::task::spawn_blocking(move || {
+        let (sessions, _) = scan_sessions(&root, cache_path.as_deref(), false);
+        fill_plan(&mut plan, &root, sessions, &policy);
+        plan
+    })
+    .await
+    .map_err(|e| format!("Prune planning failed: {}", e))?;
+
+    info!(
+        "Prune plan for {}: {} to prune, {} to keep",
+        destination_path,
+        plan.prune.len(),
+        plan.keep.len()
+    );
+    Ok(plan)
+}
+
+/// Sorts the scanned sessions into `plan.prune` and `plan.keep`.
+fn fill_plan(
+    plan: &mut PrunePlan,
+    root: &Path,
+    sessions: Vec<ScannedSession>,
+    policy: &RetentionPolicy,
+) {
+    let other_roots: Vec<PathBuf> = registered_roots(RootKind::Destination)
+        .into_iter()
+        .filter(|r| r != root)
+        .collect();
+    let cutoff = policy
+        .keep_within_days
+        .map(|days| chrono::Utc::now().timestamp() - i64::from(days) * DAY_SECS);
+
+    // Oldest first, so the free-space rule removes the oldest backups
+    let mut sessions: Vec<(ScannedSession, Option<i64>)> = sessions
+        .into_iter()
+        .map(|s| {
+            let backed_up = s.last_backup.as_deref().and_then(parse_timestamp);
+            (s, backed_up)
+        })
+        .collect();
+    sessions.sort_by_key(|(_, backed_up)| backed_up.unwrap_or(i64::MAX));
+
+    for (session, backed_up) in sessions {
+        let candidate = |reason: &str| PruneCandidate {
+            session: session.clone(),
+            reason: reason.to_string(),
+        };
+
+        let receipt = if session.has_receipt {
+            read_receipt(Path::new(&session.path))
+        } else {
+            None
+        };
+        let Some(receipt) = receipt else {
+            plan.keep.push(candidate("no backup receipt"));
+            continue;
+        };
+        let Some(backed_up) = backed_up else {
+            plan.keep.push(candidate("backup time unknown"));
+            continue;
+        };
+        if cutoff.is_some_and(|cutoff| backed_up >= cutoff) {
+            plan.keep.push(candidate("within the retention window"));
+            continue;
+        }
+        if let Some(min_free) = policy.min_free_bytes {
+            if plan.projected_available_bytes >= min_free {
+                plan.keep.push(candidate("free space target already met"));
+                continue;
+            }
+        } else if cutoff.is_none() {
+            plan.keep.push(candidate("no retention rule set"));
+            continue;
+        }
+        if !verified_elsewhere(&receipt, root, Path::new(&session.path), &other_roots) {
+            plan.keep
+                .push(candidate("not verified on another destination"));
+            continue;
+        }
+
+        plan.projected_available_bytes += session.size_bytes;
+        let reason = if cutoff.is_some() {
+            "older than the retention window"
+        } else {
+            "oldest backup, needed for free space"
+        };
+        plan.prune.push(candidate(reason));
+    }
+}
+
+/// Whether another destination holds a verified copy of the same session that
+/// is at least as recent as this one. The copy is looked for at the same
+/// relative path and directly under the other destination's root.
+fn verified_elsewhere(
+    receipt: &BackupReceipt,
+    root: &Path,
+    session_folder: &Path,
+    other_roots: &[PathBuf],
+) -> bool {
+    let relative = session_folder.strip_prefix(root).ok();
+
+    other_roots.iter().any(|other| {
+        let candidates = relative
+            .map(|rel| other.join(rel))
+            .into_iter()
+            .chain(std::iter::once(other.join(&receipt.session_name)));
+
+        candidates
+            .filter_map(|folder| read_receipt(&folder))
+            .any(|copy| {
+                copy.verified
+                    && copy.session_name == receipt.session_name
+                    && copy.source_path == receipt.source_path
+                    && copy.finished_at >= receipt.finished_at
+            })
+    })
+}
+
+fn parse_timestamp(value: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|t| t.timestamp())
+}
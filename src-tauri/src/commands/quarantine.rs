@@ -0,0 +1,273 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::policy::{require_plain_name, require_root, RootKind};
+use super::repository::safe_join;
+use super::sidecar::now_secs;
+
+/// Folder at the root of a destination that deleted backups are moved into.
+/// Living on the same volume means quarantining is a rename, not a copy.
+pub const QUARANTINE_DIR: &str = ".jsync-trash";
+
+const ENTRY_FILE: &str = "entry.json";
+
+/// How long a deleted backup can be restored before it is purged.
+const QUARANTINE_RETENTION_SECS: u64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineEntry {
+    pub id: String,
+    pub session_name: String,
+    pub original_path: String,
+    pub deleted_at: u64,
+    pub expires_at: u64,
+}
+
+/// Resolves `<destination>/<session_name>` and makes sure it stays inside the
+/// destination. `session_name` must be a single plain folder name, so `..`,
+/// absolute paths and nested paths are rejected before touching the disk.
+pub fn session_backup_path(destination_path: &str, session_name: &str) -> Result<PathBuf, String> {
+    require_plain_name(session_name)?;
+    if session_name == QUARANTINE_DIR {
+        return Err(format!("Invalid session name: {:?}", session_name));
+    }
+
+    let root = std::fs::canonicalize(destination_path)
+        .map_err(|e| format!("Destination not accessible: {}", e))?;
+    let backup_path = root.join(session_name);
+
+    // Resolve symlinks too: a session folder that links elsewhere must not be followed
+    if let Ok(resolved) = std::fs::canonicalize(&backup_path) {
+        if resolved.parent() != Some(root.as_path()) {
+            return Err(format!(
+                "Backup folder {:?} resolves outside the destination",
+                backup_path
+            ));
+        }
+    }
+
+    Ok(backup_path)
+}
+
+/// Moves a session backup into the destination's quarantine folder.
+pub fn quarantine_backup(
+    destination_path: &str,
+    session_name: &str,
+) -> Result<QuarantineEntry, String> {
+    let backup_path = session_backup_path(destination_path, session_name)?;
+    let root = backup_path
+        .parent()
+        .ok_or("Backup folder has no parent")?
+        .to_path_buf();
+    quarantine_folder(&root, &backup_path)
+}
+
+/// Moves a session backup folder anywhere below the destination `root` into
+/// its quarantine folder, where it is kept under its own folder name.
+pub fn quarantine_folder(root: &Path, backup_path: &Path) -> Result<QuarantineEntry, String> {
+    let session_name = backup_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| format!("Invalid backup folder: {:?}", backup_path))?;
+    let resolved = std::fs::canonicalize(backup_path)
+        .map_err(|_| format!("Backup folder does not exist: {:?}", backup_path))?;
+    if !resolved.is_dir()
+        || resolved == root
+        || !resolved.starts_with(root)
+        || resolved.starts_with(root.join(QUARANTINE_DIR))
+    {
+        return Err(format!(
+            "{:?} is not a backup folder on this destination",
+            backup_path
+        ));
+    }
+
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let entry_dir = root.join(QUARANTINE_DIR).join(&id);
+    std::fs::create_dir_all(&entry_dir)
+        .map_err(|e| format!("Failed to create quarantine folder: {}", e))?;
+
+    let deleted_at = now_secs();
+    let entry = QuarantineEntry {
+        id,
+        session_name: session_name.to_string(),
+        original_path: resolved.to_string_lossy().to_string(),
+        deleted_at,
+        expires_at: deleted_at + QUARANTINE_RETENTION_SECS,
+    };
+    write_entry(&entry_dir, &entry)?;
+
+    if let Err(e) = std::fs::rename(&resolved, entry_dir.join(session_name)) {
+        let _ = std::fs::remove_dir_all(&entry_dir);
+        return Err(format!("Failed to move backup to quarantine: {}", e));
+    }
+
+    info!(
+        "Quarantined {:?} as {} until {}",
+        resolved, entry.id, entry.expires_at
+    );
+    Ok(entry)
+}
+
+#[tauri::command]
+pub async fn list_quarantine(destination_path: String) -> Result<Vec<QuarantineEntry>, String> {
+    let quarantine = quarantine_root(&destination_path)?;
+    Ok(read_entries(&quarantine)
+        .into_iter()
+        .map(|(_, entry)| entry)
+        .collect())
+}
+
+#[tauri::command]
+pub async fn restore_quarantined(
+    destination_path: String,
+    entry_id: String,
+) -> Result<String, String> {
+    let quarantine = quarantine_root(&destination_path)?;
+    let (entry_dir, entry) = find_entry(&quarantine, &entry_id)?;
+
+    let target = restore_target(&destination_path, &quarantine, &entry)?;
+    if target.exists() {
+        return Err(format!(
+            "A backup already exists at {:?}; delete or move it before restoring",
+            target
+        ));
+    }
+
+    std::fs::rename(entry_dir.join(&entry.session_name), &target)
+        .map_err(|e| format!("Failed to restore backup: {}", e))?;
+    std::fs::remove_dir_all(&entry_dir)
+        .map_err(|e| format!("Restored, but failed to clean up quarantine entry: {}", e))?;
+
+    info!("Restored quarantined backup {} to {:?}", entry_id, target);
+    Ok(target.to_string_lossy().to_string())
+}
+
+/// Permanently deletes quarantined backups: the given entry, or every expired
+/// entry when `entry_id` is `None`. Returns how many entries were removed.
+#[tauri::command]
+pub async fn purge_quarantine(
+    destination_path: String,
+    entry_id: Option<String>,
+) -> Result<u32, String> {
+    let quarantine = quarantine_root(&destination_path)?;
+
+    if let Some(entry_id) = entry_id {
+        let (entry_dir, _) = find_entry(&quarantine, &entry_id)?;
+        std::fs::remove_dir_all(&entry_dir)
+            .map_err(|e| format!("Failed to purge quarantine entry: {}", e))?;
+        info!("Purged quarantine entry {}", entry_id);
+        return Ok(1);
+    }
+
+    Ok(purge_expired(&quarantine))
+}
+
+/// Removes expired entries, logging rather than failing on individual errors.
+pub fn purge_expired(quarantine: &Path) -> u32 {
+    let now = now_secs();
+    let mut purged = 0;
+
+    for (entry_dir, entry) in read_entries(quarantine) {
+        if entry.expires_at > now {
+            continue;
+        }
+        match std::fs::remove_dir_all(&entry_dir) {
+            Ok(()) => {
+                info!("Purged expired quarantine entry {}", entry.id);
+                purged += 1;
+            }
+            Err(e) => warn!("Failed to purge quarantine entry {}: {}", entry.id, e),
+        }
+    }
+
+    purged
+}
+
+/// Where a quarantined backup goes back to: the folder it was taken from if
+/// that is still inside the destination, otherwise `<destination>/<session_name>`.
+fn restore_target(
+    destination_path: &str,
+    quarantine: &Path,
+    entry: &QuarantineEntry,
+) -> Result<PathBuf, String> {
+    let original = Path::new(&entry.original_path);
+    let inside = quarantine.parent().and_then(|root| {
+        let relative = original.strip_prefix(root).ok()?.to_str()?;
+        let target = safe_join(root, relative)?;
+        let parent = std::fs::canonicalize(target.parent()?).ok()?;
+        let in_place = parent.starts_with(root)
+            && !parent.starts_with(quarantine)
+            && target.file_name()?.to_str() == Some(entry.session_name.as_str());
+        in_place.then_some(target)
+    });
+    match inside {
+        Some(target) => Ok(target),
+        None => session_backup_path(destination_path, &entry.session_name),
+    }
+}
+
+fn quarantine_root(destination_path: &str) -> Result<PathBuf, String> {
+    require_root(destination_path, RootKind::Destination)?;
+    let root = std::fs::canonicalize(destination_path)
+        .map_err(|e| format!("Destination not accessible: {}", e))?;
+    Ok(root.join(QUARANTINE_DIR))
+}
+
+fn find_entry(quarantine: &Path, entry_id: &str) -> Result<(PathBuf, QuarantineEntry), String> {
+    if !is_entry_id(entry_id) {
+        return Err(format!("Invalid quarantine entry: {:?}", entry_id));
+    }
+
+    let entry_dir = quarantine.join(entry_id);
+    let entry = read_entry(&entry_dir)
+        .ok_or_else(|| format!("Quarantine entry not found: {}", entry_id))?;
+    Ok((entry_dir, entry))
+}
+
+/// Entries with the folder each was read from. The folder is what gets
+/// restored or deleted, never a path built from the entry file's contents.
+fn read_entries(quarantine: &Path) -> Vec<(PathBuf, QuarantineEntry)> {
+    let Ok(dirs) = std::fs::read_dir(quarantine) else {
+        return Vec::new();
+    };
+
+    let mut entries: Vec<(PathBuf, QuarantineEntry)> = dirs
+        .flatten()
+        .filter_map(|d| {
+            let entry_dir = d.path();
+            read_entry(&entry_dir).map(|entry| (entry_dir, entry))
+        })
+        .collect();
+    entries.sort_by_key(|(_, e)| std::cmp::Reverse(e.deleted_at));
+    entries
+}
+
+/// Reads an entry, ignoring it unless its ID is one of ours and names the
+/// folder it is in.
+fn read_entry(entry_dir: &Path) -> Option<QuarantineEntry> {
+    let content = std::fs::read_to_string(entry_dir.join(ENTRY_FILE)).ok()?;
+    let entry: QuarantineEntry = serde_json::from_str(&content).ok()?;
+    let folder = entry_dir.file_name()?.to_str()?;
+    if !is_entry_id(&entry.id) || entry.id != folder {
+        warn!(
+            "Ignoring quarantine entry with a mismatched ID in {:?}",
+            entry_dir
+        );
+        return None;
+    }
+    Some(entry)
+}
+
+/// Entry IDs are generated lowercase hex strings; anything else can't be one of ours.
+fn is_entry_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+}
+
+fn write_entry(entry_dir: &Path, entry: &QuarantineEntry) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(entry)
+        .map_err(|e| format!("Failed to serialize quarantine entry: {}", e))?;
+    std::fs::write(entry_dir.join(ENTRY_FILE), content)
+        .map_err(|e| format!("Failed to write quarantine entry: {}", e))
+}
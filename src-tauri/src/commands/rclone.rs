@@ -0,0 +1,196 @@
+use log::info;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager};
+
+/// rclone's config file in the app's data folder. JSync never reads the
+/// user's own rclone config, so a personal remote can't change its backups.
+const RCLONE_CONFIG_FILE: &str = "rclone.conf";
+
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// The rclone binary bundled with the app. In packaged apps (macOS) the
+/// externalBin is placed in the same directory as the executable
+/// (Contents/MacOS); in dev it's also next to the executable in target/debug.
+fn rclone_path() -> Result<PathBuf, String> {
+    Ok(std::env::current_exe()
+        .map_err(|e| format!("Failed to get current executable path: {}", e))?
+        .parent()
+        .ok_or("Failed to get parent directory of executable")?
+        .join("rclone"))
+}
+
+/// Locates the app's rclone config and creates it if needed. Called once at
+/// startup, before anything runs rclone. The location is kept even if the file
+/// can't be created yet, so `rclone_config_path` can try again later.
+pub fn init_rclone_config(app: &AppHandle) -> Result<(), String> {
+    let path = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("App data folder not available: {}", e))?
+        .join(RCLONE_CONFIG_FILE);
+    ensure_config_file(CONFIG_PATH.get_or_init(|| path))
+}
+
+/// The app's rclone config, created first if it is missing, e.g. because it
+/// couldn't be created at startup or was deleted since.
+pub fn rclone_config_path() -> Result<&'static Path, String> {
+    let path = CONFIG_PATH
+        .get()
+        .ok_or_else(|| "The rclone config has not been set up".to_string())?;
+    ensure_config_file(path)?;
+    Ok(path)
+}
+
+/// Creates an empty, private config file unless one is there already. An
+/// existing file is never replaced, so remotes written meanwhile survive.
+fn ensure_config_file(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if path.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+    {
+        Ok(_) => {
+            info!("Created rclone config at {:?}", path);
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) => Err(format!("Failed to create rclone config {:?}: {}", path, e)),
+    }
+}
+
+/// An rclone command that reads only the app's config file. Inherited
+/// `RCLONE_*` variables are removed, since rclone treats them as flags and
+/// config values; callers add their own afterwards.
+pub fn rclone_command() -> Result<std::process::Command, String> {
+    let mut command = std::process::Command::new(rclone_path()?);
+    command.arg("--config").arg(rclone_config_path()?);
+    for (name, _) in std::env::vars_os() {
+        if name.to_string_lossy().starts_with("RCLONE_") {
+            command.env_remove(name);
+        }
+    }
+    Ok(command)
+}
+
+/// Runs rclone to completion with `env` added to its environment. Only a
+/// failure to start it is an error; callers decide what its exit status means.
+pub fn rclone_output(
+    args: &[String],
+    env: &[(String, String)],
+) -> Result<std::process::Output, String> {
+    rclone_command()?
+        .args(args)
+        .envs(env.iter().cloned())
+        .stdin(std::process::Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run rclone: {}", e))
+}
+
+/// The last line rclone wrote to stderr, for error messages.
+pub fn last_error_line(output: &std::process::Output) -> String {
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or("no output")
+        .to_string()
+}
+
+/// Somewhere rclone reads and writes, such as an on-the-fly remote configured
+/// entirely through the environment, together with that environment.
+pub struct RcloneTarget {
+    root: String,
+    env: Vec<(String, String)>,
+}
+
+impl RcloneTarget {
+    pub fn new(root: String, env: Vec<(String, String)>) -> RcloneTarget {
+        RcloneTarget { root, env }
+    }
+
+    /// rclone path of `relative` inside the target.
+    pub fn path(&self, relative: &str) -> String {
+        if relative.is_empty() {
+            self.root.clone()
+        } else if self.root.ends_with(':') {
+            format!("{}{}", self.root, relative)
+        } else {
+            format!("{}/{}", self.root.trim_end_matches('/'), relative)
+        }
+    }
+
+    pub fn env(&self) -> &[(String, String)] {
+        &self.env
+    }
+
+    /// Every file under `folder` as (relative path, size, modification time).
+    pub fn list_files(&self, folder: &str) -> Result<Vec<(String, u64, u64)>, String> {
+        let args = [
+            "lsjson".to_string(),
+            "--recursive".to_string(),
+            "--files-only".to_string(),
+            self.path(folder),
+        ];
+        let output = rclone_output(&args, &self.env)?;
+        // Exit code 3 means the folder doesn't exist: nothing is backed up yet
+        if output.status.code() == Some(3) {
+            return Ok(Vec::new());
+        }
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to list backup: {}",
+                last_error_line(&output)
+            ));
+        }
+        let entries: Vec<RcloneListEntry> = serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("Failed to parse rclone listing: {}", e))?;
+        Ok(entries
+            .into_iter()
+            .map(|e| {
+                let modified = chrono::DateTime::parse_from_rfc3339(&e.mod_time)
+                    .map(|t| t.timestamp().max(0) as u64)
+                    .unwrap_or(0);
+                (e.path, e.size.max(0) as u64, modified)
+            })
+            .collect())
+    }
+
+    /// Copies the file at `relative` to the local `target`.
+    pub fn copy_file(&self, relative: &str, target: &Path) -> Result<(), String> {
+        let args = [
+            "copyto".to_string(),
+            self.path(relative),
+            target.to_string_lossy().to_string(),
+        ];
+        let output = rclone_output(&args, &self.env)?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to restore {}: {}",
+                relative,
+                last_error_line(&output)
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RcloneListEntry {
+    path: String,
+    size: i64,
+    mod_time: String,
+}
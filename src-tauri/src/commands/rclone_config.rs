@@ -0,0 +1,235 @@
+use log::info;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+use super::probe::{ProbeResult, ProbeStatus};
+use super::rclone::{last_error_line, rclone_config_path, rclone_output};
+use super::remote::{timed_probe, validate_remote, RemoteBackend, RemoteDestination, PROBE_ARGS};
+use super::secrets::{delete_secret, read_secret, reveal, store_secret, write_private_file};
+
+/// Serialises read-modify-write cycles on the rclone config file.
+static CONFIG_LOCK: Mutex<()> = Mutex::new(());
+
+/// Sections of the rclone config, by remote name, each a map of options.
+type ConfigSections = BTreeMap<String, BTreeMap<String, String>>;
+
+/// A remote in the app's rclone config. rclone only accepts some credentials
+/// obscured, so none are written to the config; they are kept obscured in the
+/// app's secret store and handed to rclone through the environment instead.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigRemote {
+    pub name: String,
+    pub backend: RemoteBackend,
+    pub options: BTreeMap<String, String>,
+    pub has_credential: bool,
+}
+
+#[tauri::command]
+pub fn list_rclone_remotes(app: AppHandle) -> Result<Vec<ConfigRemote>, String> {
+    let sections = {
+        let _guard = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        load_config()?
+    };
+
+    let mut remotes = Vec::new();
+    for (name, mut options) in sections {
+        let backend = options
+            .remove("type")
+            .and_then(|t| RemoteBackend::from_name(&t));
+        let Some(backend) = backend else {
+            continue;
+        };
+        remotes.push(ConfigRemote {
+            has_credential: read_secret(&app, &secret_key(&name))?.is_some(),
+            name,
+            backend,
+            options,
+        });
+    }
+    Ok(remotes)
+}
+
+/// Adds a remote to the app's rclone config. `credential`, if given, goes to
+/// the app's secret store.
+#[tauri::command]
+pub fn create_rclone_remote(
+    app: AppHandle,
+    name: String,
+    backend: RemoteBackend,
+    options: BTreeMap<String, String>,
+    credential: Option<String>,
+) -> Result<ConfigRemote, String> {
+    require_remote_name(&name)?;
+    validate_remote(&RemoteDestination {
+        backend,
+        options: options.clone(),
+        root: String::new(),
+        credential_id: None,
+    })?;
+
+    let _guard = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut sections = load_config()?;
+    if sections.contains_key(&name) {
+        return Err(format!("A remote named {:?} already exists", name));
+    }
+
+    // A credential left behind by an earlier remote of this name must not be reused
+    let has_credential = match credential.filter(|c| !c.is_empty()) {
+        Some(credential) => {
+            store_secret(&app, &secret_key(&name), &credential)?;
+            true
+        }
+        None => {
+            delete_secret(&app, &secret_key(&name))?;
+            false
+        }
+    };
+
+    let mut section = options.clone();
+    section.insert("type".to_string(), backend.name().to_string());
+    sections.insert(name.clone(), section);
+    save_config(&sections)?;
+    info!("Created rclone remote {} ({})", name, backend.name());
+
+    Ok(ConfigRemote {
+        name,
+        backend,
+        options,
+        has_credential,
+    })
+}
+
+/// Checks that a remote in the app's config answers by listing its top level.
+/// Nothing is written, so a reachable remote may still refuse writes.
+#[tauri::command]
+pub async fn test_rclone_remote(app: AppHandle, name: String) -> Result<ProbeResult, String> {
+    require_remote_name(&name)?;
+    let env = config_remote_env(&app, &name)?;
+
+    let target = format!("{}:", name);
+    Ok(timed_probe(&name, move || {
+        let mut args = vec![
+            "lsf".to_string(),
+            "--max-depth".to_string(),
+            "1".to_string(),
+            target,
+        ];
+        args.extend(PROBE_ARGS.iter().map(|a| a.to_string()));
+        let output = rclone_output(&args, &env).map_err(|e| (ProbeStatus::Unreachable, e))?;
+        if !output.status.success() {
+            return Err((
+                ProbeStatus::Unreachable,
+                format!("Remote not accessible: {}", last_error_line(&output)),
+            ));
+        }
+        Ok(())
+    })
+    .await)
+}
+
+/// Removes a remote from the app's rclone config along with its credential.
+#[tauri::command]
+pub fn delete_rclone_remote(app: AppHandle, name: String) -> Result<(), String> {
+    require_remote_name(&name)?;
+    let _guard = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut sections = load_config()?;
+    if sections.remove(&name).is_none() {
+        return Err(format!("No remote named {:?}", name));
+    }
+    save_config(&sections)?;
+    delete_secret(&app, &secret_key(&name))?;
+    info!("Deleted rclone remote {}", name);
+    Ok(())
+}
+
+/// Environment that supplies a config remote's credential to rclone, in the
+/// `RCLONE_CONFIG_<REMOTE>_<OPTION>` form rclone reads config values from.
+fn config_remote_env(app: &AppHandle, name: &str) -> Result<Vec<(String, String)>, String> {
+    let backend = {
+        let _guard = CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        load_config()?
+            .get(name)
+            .and_then(|options| options.get("type"))
+            .and_then(|t| RemoteBackend::from_name(t))
+            .ok_or_else(|| format!("No remote named {:?}", name))?
+    };
+
+    let Some(obscured) = read_secret(app, &secret_key(name))? else {
+        return Ok(Vec::new());
+    };
+    let (option, rclone_obscures) = backend.credential_option();
+    let value = if rclone_obscures {
+        obscured
+    } else {
+        reveal(&obscured)?
+    };
+    let env_name = format!("RCLONE_CONFIG_{}_{}", name, option)
+        .to_uppercase()
+        .replace('-', "_");
+    Ok(vec![(env_name, value)])
+}
+
+/// Remote names become part of rclone paths and environment variable names,
+/// so only letters, digits, `_` and `-` are allowed.
+fn require_remote_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(format!(
+            "Invalid remote name {:?}; use letters, digits, '_' and '-'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+fn load_config() -> Result<ConfigSections, String> {
+    let path = rclone_config_path()?;
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    };
+
+    let mut sections = ConfigSections::new();
+    let mut current: Option<String> = None;
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.entry(name.to_string()).or_default();
+            current = Some(name.to_string());
+        } else if let (Some(section), Some((key, value))) = (&current, line.split_once('=')) {
+            sections
+                .entry(section.clone())
+                .or_default()
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    Ok(sections)
+}
+
+fn save_config(sections: &ConfigSections) -> Result<(), String> {
+    let mut content = String::new();
+    for (name, options) in sections {
+        content.push_str(&format!("[{}]\n", name));
+        // rclone writes the backend type first; keep to that for anyone reading the file
+        let (kind, rest): (Vec<_>, Vec<_>) = options.iter().partition(|(key, _)| *key == "type");
+        for (key, value) in kind.into_iter().chain(rest) {
+            content.push_str(&format!("{} = {}\n", key, value));
+        }
+        content.push('\n');
+    }
+    write_private_file(rclone_config_path()?, content.as_bytes())
+}
+
+fn secret_key(name: &str) -> String {
+    format!("rclone-remote/{}", name)
+}
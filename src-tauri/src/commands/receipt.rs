@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::manifest::is_backup_metadata;
+use super::sidecar::write_atomic;
+
+/// Written into each session backup folder after a successful sync, so the
+/// backup describes itself to whoever plugs the drive in next.
+pub const RECEIPT_FILE: &str = ".jsync-receipt.json";
+
+/// rclone filter rule that keeps the receipt out of the sync. Excluded files
+/// are left alone at the destination, so the delete pass never removes it.
+pub const RECEIPT_FILTER: &str = "- /.jsync-receipt.json";
+
+const RECEIPT_VERSION: u32 = 1;
+
+/// How many audits a receipt remembers; older ones are dropped.
+const MAX_AUDIT_HISTORY: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupReceipt {
+    pub version: u32,
+    pub session_name: String,
+    pub source_path: String,
+    pub source_host: String,
+    pub app_version: String,
+    pub started_at: String,
+    pub finished_at: String,
+    /// Name of the snapshot this run wrote, for destinations in snapshot mode.
+    #[serde(default)]
+    pub snapshot: Option<String>,
+    /// rclone filter rules used; empty means the whole session was synced.
+    pub filters: Vec<String>,
+    /// Whether a full audit has confirmed the copy against its manifest since
+    /// this backup was written.
+    pub verified: bool,
+    pub image_count: Option<u32>,
+    pub files_transferred: u32,
+    pub bytes_transferred: u64,
+    pub total_files: u64,
+    pub total_bytes: u64,
+    /// Audits of this backup folder, oldest first. Kept across backups.
+    #[serde(default)]
+    pub audits: Vec<AuditRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub audited_at: String,
+    pub host: String,
+    /// Share of files that were re-hashed; 100 for a full audit.
+    pub sample_percent: u8,
+    pub files_checked: u64,
+    pub corrupted: u64,
+    pub missing: u64,
+    pub unexpected: u64,
+    pub passed: bool,
+}
+
+impl BackupReceipt {
+    pub fn new(
+        session_name: &str,
+        source_path: &str,
+        started_at: String,
+        finished_at: String,
+    ) -> Self {
+        BackupReceipt {
+            version: RECEIPT_VERSION,
+            session_name: session_name.to_string(),
+            source_path: source_path.to_string(),
+            source_host: super::sidecar::host_name(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at,
+            finished_at,
+            snapshot: None,
+            filters: Vec::new(),
+            verified: false,
+            image_count: None,
+            files_transferred: 0,
+            bytes_transferred: 0,
+            total_files: 0,
+            total_bytes: 0,
+            audits: Vec::new(),
+        }
+    }
+}
+
+pub fn read_receipt(backup_folder: &Path) -> Option<BackupReceipt> {
+    let content = std::fs::read_to_string(backup_folder.join(RECEIPT_FILE)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Replaces the receipt via a temp file so a half-written one is never left behind.
+pub fn write_receipt(backup_folder: &Path, receipt: &BackupReceipt) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(receipt)
+        .map_err(|e| format!("Failed to serialize backup receipt: {}", e))?;
+
+    write_atomic(&backup_folder.join(RECEIPT_FILE), content.as_bytes())
+        .map_err(|e| format!("Failed to write backup receipt: {}", e))
+}
+
+/// Appends an audit to the receipt in `backup_folder`. A passing full audit
+/// marks the backup verified; any failure clears it.
+pub fn record_audit(backup_folder: &Path, audit: AuditRecord) -> Result<(), String> {
+    let mut receipt = read_receipt(backup_folder)
+        .ok_or_else(|| format!("No backup receipt in {:?}", backup_folder))?;
+
+    if !audit.passed {
+        receipt.verified = false;
+    } else if audit.sample_percent >= 100 {
+        receipt.verified = true;
+    }
+    receipt.audits.push(audit);
+    let excess = receipt.audits.len().saturating_sub(MAX_AUDIT_HISTORY);
+    receipt.audits.drain(..excess);
+
+    write_receipt(backup_folder, &receipt)
+}
+
+/// Counts the files and bytes in a backup folder, leaving out jsync's own files.
+pub fn folder_totals(dir: &Path) -> (u64, u64) {
+    let mut files = 0;
+    let mut bytes = 0;
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if is_backup_metadata(&entry.file_name()) {
+                continue;
+            }
+            if file_type.is_dir() {
+                let (sub_files, sub_bytes) = folder_totals(&entry.path());
+                files += sub_files;
+                bytes += sub_bytes;
+            } else if file_type.is_file() {
+                files += 1;
+                bytes += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            }
+        }
+    }
+    (files, bytes)
+}
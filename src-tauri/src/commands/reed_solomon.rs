@@ -0,0 +1,267 @@
+/// A group can hold at most this many data and parity blocks together.
+pub const MAX_GROUP_BLOCKS: usize = 256;
+
+/// The field's reducing polynomial, x^8 + x^4 + x^3 + x^2 + 1.
+const POLYNOMIAL: u16 = 0x11d;
+
+const EXP: [u8; 512] = exp_table();
+const LOG: [u8; 256] = log_table();
+
+const fn exp_table() -> [u8; 512] {
+    let mut table = [0u8; 512];
+    let mut value: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        table[i] = value as u8;
+        table[i + 255] = value as u8;
+        value <<= 1;
+        if value & 0x100 != 0 {
+            value ^= POLYNOMIAL;
+        }
+        i += 1;
+    }
+    table
+}
+
+const fn log_table() -> [u8; 256] {
+    let exp = exp_table();
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 255 {
+        table[exp[i] as usize] = i as u8;
+        i += 1;
+    }
+    table
+}
+
+pub fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    EXP[LOG[a as usize] as usize + LOG[b as usize] as usize]
+}
+
+fn inv(a: u8) -> u8 {
+    debug_assert!(a != 0);
+    EXP[255 - LOG[a as usize] as usize]
+}
+
+/// Matrix entry for parity block `parity` and data block `data` of a group:
+/// parity block `j` is the sum of `coefficient(j, i) * data[i]` over the
+/// group's data blocks. This is a Cauchy matrix, and every square part of one
+/// is invertible, so any `e` lost data blocks can be rebuilt from any `e`
+/// intact parity blocks. Parity rows use the field elements from 255 down and
+/// data columns those from 0 up, so the two never meet while a group stays
+/// within `MAX_GROUP_BLOCKS`.
+pub fn coefficient(parity: usize, data: usize) -> u8 {
+    inv((255 - parity) as u8 ^ data as u8)
+}
+
+/// Adds `factor * block` to `acc`. Addition in the field is XOR.
+pub fn mul_add(acc: &mut [u8], block: &[u8], factor: u8) {
+    if factor == 0 {
+        return;
+    }
+    let mut table = [0u8; 256];
+    for (value, product) in table.iter_mut().enumerate() {
+        *product = mul(value as u8, factor);
+    }
+    for (a, b) in acc.iter_mut().zip(block) {
+        *a ^= table[*b as usize];
+    }
+}
+
+/// Inverts a square matrix with Gauss-Jordan elimination. Returns `None` if
+/// it is singular.
+pub fn invert(mut matrix: Vec<Vec<u8>>) -> Option<Vec<Vec<u8>>> {
+    let size = matrix.len();
+    let mut inverse: Vec<Vec<u8>> = (0..size)
+        .map(|row| (0..size).map(|col| u8::from(row == col)).collect())
+        .collect();
+
+    for col in 0..size {
+        let pivot = (col..size).find(|&row| matrix[row][col] != 0)?;
+        matrix.swap(col, pivot);
+        inverse.swap(col, pivot);
+
+        let scale = inv(matrix[col][col]);
+        for value in matrix[col].iter_mut().chain(inverse[col].iter_mut()) {
+            *value = mul(*value, scale);
+        }
+
+        for row in 0..size {
+            let factor = matrix[row][col];
+            if row == col || factor == 0 {
+                continue;
+            }
+            for k in 0..size {
+                matrix[row][k] ^= mul(factor, matrix[col][k]);
+                inverse[row][k] ^= mul(factor, inverse[col][k]);
+            }
+        }
+    }
+    Some(inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multiply(a: &[Vec<u8>], b: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        (0..a.len())
+            .map(|row| {
+                (0..b[0].len())
+                    .map(|col| (0..b.len()).fold(0, |sum, k| sum ^ mul(a[row][k], b[k][col])))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn identity(size: usize) -> Vec<Vec<u8>> {
+        (0..size)
+            .map(|row| (0..size).map(|col| u8::from(row == col)).collect())
+            .collect()
+    }
+
+    /// Carry-less multiplication reduced by the polynomial, bit by bit.
+    fn slow_mul(mut a: u8, mut b: u8) -> u8 {
+        let mut product = 0;
+        while b != 0 {
+            if b & 1 != 0 {
+                product ^= a;
+            }
+            let carry = a & 0x80 != 0;
+            a <<= 1;
+            if carry {
+                a ^= (POLYNOMIAL & 0xff) as u8;
+            }
+            b >>= 1;
+        }
+        product
+    }
+
+    #[test]
+    fn multiplication_matches_the_field_definition() {
+        for a in 0..=255u8 {
+            for b in 0..=255u8 {
+                assert_eq!(mul(a, b), slow_mul(a, b), "{} * {}", a, b);
+                assert_eq!(mul(a, b), mul(b, a));
+            }
+            assert_eq!(mul(a, 1), a);
+            assert_eq!(mul(a, 0), 0);
+        }
+        assert_eq!(mul(0x80, 2), 0x1d);
+    }
+
+    #[test]
+    fn multiplication_distributes_over_addition() {
+        for a in (0..=255u8).step_by(7) {
+            for b in (0..=255u8).step_by(11) {
+                for c in (0..=255u8).step_by(13) {
+                    assert_eq!(mul(a, b ^ c), mul(a, b) ^ mul(a, c));
+                    assert_eq!(mul(mul(a, b), c), mul(a, mul(b, c)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_nonzero_element_has_an_inverse() {
+        for a in 1..=255u8 {
+            assert_eq!(mul(a, inv(a)), 1, "inverse of {}", a);
+        }
+    }
+
+    #[test]
+    fn mul_add_accumulates_scaled_blocks() {
+        let block: Vec<u8> = (0..=255).collect();
+        let mut acc = vec![0x5a; 256];
+        mul_add(&mut acc, &block, 0x37);
+        for (i, value) in acc.iter().enumerate() {
+            assert_eq!(*value, 0x5a ^ mul(i as u8, 0x37));
+        }
+        let before = acc.clone();
+        mul_add(&mut acc, &block, 0);
+        assert_eq!(acc, before);
+    }
+
+    #[test]
+    fn cauchy_submatrices_invert() {
+        for size in [1, 2, 5, 16] {
+            let rows: Vec<usize> = (0..size).map(|r| r * 3).collect();
+            let cols: Vec<usize> = (0..size).map(|c| 100 + c * 7).collect();
+            let matrix: Vec<Vec<u8>> = rows
+                .iter()
+                .map(|&r| cols.iter().map(|&c| coefficient(r, c)).collect())
+                .collect();
+            let inverse = invert(matrix.clone()).expect("Cauchy matrices are invertible");
+            assert_eq!(multiply(&matrix, &inverse), identity(size));
+        }
+    }
+
+    #[test]
+    fn singular_matrices_are_rejected() {
+        assert!(invert(vec![vec![1, 2], vec![1, 2]]).is_none());
+        assert!(invert(vec![vec![0, 0], vec![3, 4]]).is_none());
+    }
+
+    /// Encodes `data` into `parity_count` parity blocks, erases the data blocks
+    /// in `lost` and rebuilds them from the first intact parity blocks.
+    fn round_trip(data: &[Vec<u8>], parity_count: usize, lost: &[usize]) -> Option<Vec<Vec<u8>>> {
+        let parity: Vec<Vec<u8>> = (0..parity_count)
+            .map(|j| {
+                let mut acc = vec![0u8; data[0].len()];
+                for (i, block) in data.iter().enumerate() {
+                    mul_add(&mut acc, block, coefficient(j, i));
+                }
+                acc
+            })
+            .collect();
+        if lost.len() > parity_count {
+            return None;
+        }
+
+        let rows: Vec<usize> = (0..lost.len()).collect();
+        let mut sums: Vec<Vec<u8>> = rows.iter().map(|&j| parity[j].clone()).collect();
+        for (i, block) in data.iter().enumerate().filter(|(i, _)| !lost.contains(i)) {
+            for (sum, &j) in sums.iter_mut().zip(&rows) {
+                mul_add(sum, block, coefficient(j, i));
+            }
+        }
+        let matrix = rows
+            .iter()
+            .map(|&j| lost.iter().map(|&i| coefficient(j, i)).collect())
+            .collect();
+        let inverse = invert(matrix)?;
+        Some(
+            inverse
+                .iter()
+                .map(|factors| {
+                    let mut rebuilt = vec![0u8; data[0].len()];
+                    for (sum, &factor) in sums.iter().zip(factors) {
+                        mul_add(&mut rebuilt, sum, factor);
+                    }
+                    rebuilt
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn lost_blocks_rebuild_up_to_the_parity_count() {
+        let data: Vec<Vec<u8>> = (0..12u8)
+            .map(|i| {
+                (0..64u8)
+                    .map(|b| b.wrapping_mul(31) ^ i.wrapping_mul(97))
+                    .collect()
+            })
+            .collect();
+        for lost in [vec![0], vec![3, 7], vec![0, 5, 11], vec![1, 2, 9, 10]] {
+            let rebuilt = round_trip(&data, 4, &lost).expect("within the parity count");
+            for (block, &i) in rebuilt.iter().zip(&lost) {
+                assert_eq!(*block, data[i], "block {} of {:?}", i, lost);
+            }
+        }
+        assert!(round_trip(&data, 4, &[0, 1, 2, 3, 4]).is_none());
+    }
+}
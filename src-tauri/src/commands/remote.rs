@@ -0,0 +1,406 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+use super::destinations::{new_destination_id, Destination};
+use super::probe::{run_blocking_with_timeout, ProbeResult, ProbeStatus};
+use super::rclone::{last_error_line, rclone_output, RcloneTarget};
+use super::secrets::{read_secret, reveal, store_secret};
+
+/// A remote answers over the network, so it gets longer than a local probe.
+const REMOTE_PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Round trips slower than this still work but are reported as slow.
+const REMOTE_SLOW_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// Keeps a dead server from stalling a probe through rclone's own retries.
+pub const PROBE_ARGS: [&str; 8] = [
+    "--contimeout",
+    "10s",
+    "--timeout",
+    "20s",
+    "--retries",
+    "1",
+    "--low-level-retries",
+    "1",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteBackend {
+    Sftp,
+    S3,
+    Webdav,
+}
+
+impl RemoteBackend {
+    /// rclone's name for the backend.
+    pub fn name(self) -> &'static str {
+        match self {
+            RemoteBackend::Sftp => "sftp",
+            RemoteBackend::S3 => "s3",
+            RemoteBackend::Webdav => "webdav",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RemoteBackend> {
+        match name {
+            "sftp" => Some(RemoteBackend::Sftp),
+            "s3" => Some(RemoteBackend::S3),
+            "webdav" => Some(RemoteBackend::Webdav),
+            _ => None,
+        }
+    }
+
+    /// Connection settings that may be set. Anything else is refused, since
+    /// some backend options (such as SFTP's `ssh`) run commands.
+    fn allowed_options(self) -> &'static [&'static str] {
+        match self {
+            RemoteBackend::Sftp => &["host", "user", "port", "key_file", "known_hosts_file"],
+            RemoteBackend::S3 => &[
+                "provider",
+                "endpoint",
+                "region",
+                "access_key_id",
+                "force_path_style",
+            ],
+            RemoteBackend::Webdav => &["url", "vendor", "user"],
+        }
+    }
+
+    /// Options that must all be set. Without a known_hosts file rclone accepts
+    /// any host key, so an SFTP server could be impersonated.
+    fn mandatory_options(self) -> &'static [&'static str] {
+        match self {
+            RemoteBackend::Sftp => &["known_hosts_file"],
+            RemoteBackend::S3 | RemoteBackend::Webdav => &[],
+        }
+    }
+
+    /// At least one of these must be set.
+    fn required_options(self) -> &'static [&'static str] {
+        match self {
+            RemoteBackend::Sftp => &["host"],
+            RemoteBackend::S3 => &["endpoint", "region"],
+            RemoteBackend::Webdav => &["url"],
+        }
+    }
+
+    /// The option the credential is passed in, and whether rclone expects it
+    /// obscured.
+    pub fn credential_option(self) -> (&'static str, bool) {
+        match self {
+            RemoteBackend::Sftp | RemoteBackend::Webdav => ("pass", true),
+            RemoteBackend::S3 => ("secret_access_key", false),
+        }
+    }
+
+    fn destination_type(self) -> &'static str {
+        match self {
+            RemoteBackend::S3 => "cloud",
+            RemoteBackend::Sftp | RemoteBackend::Webdav => "network",
+        }
+    }
+}
+
+/// A destination rclone reaches over the network rather than through a
+/// mounted path. The credential (password or secret key) is kept in the app's
+/// secret store under `credential_id`, never in the session's `.jsync` file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteDestination {
+    pub backend: RemoteBackend,
+    /// rclone options for the backend, e.g. `host`, `user` and
+    /// `known_hosts_file` for SFTP or
+    /// `provider`, `endpoint` and `access_key_id` for S3.
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+    /// Folder on the server, or `bucket/prefix` for S3, that holds the sessions.
+    #[serde(default)]
+    pub root: String,
+    #[serde(default)]
+    pub credential_id: Option<String>,
+}
+
+impl RemoteDestination {
+    /// URL-like name shown for the destination and stored as its path.
+    pub fn display_path(&self) -> String {
+        let server = match self.backend {
+            RemoteBackend::Sftp => {
+                let host = self.option("host");
+                match self.options.get("user") {
+                    Some(user) => format!("{}@{}", user, host),
+                    None => host.to_string(),
+                }
+            }
+            RemoteBackend::S3 => match self.option("endpoint") {
+                "" => format!("{}.amazonaws.com", self.option("region")),
+                endpoint => endpoint.to_string(),
+            },
+            RemoteBackend::Webdav => self.option("url").to_string(),
+        };
+        let server = server
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/');
+        format!(
+            "{}://{}/{}",
+            self.backend.name(),
+            server,
+            self.root.trim_matches('/')
+        )
+    }
+
+    fn option(&self, name: &str) -> &str {
+        self.options.get(name).map(String::as_str).unwrap_or("")
+    }
+}
+
+/// Checks a remote's settings before they are used or saved.
+pub fn validate_remote(remote: &RemoteDestination) -> Result<(), String> {
+    let allowed = remote.backend.allowed_options();
+    for (name, value) in &remote.options {
+        if !allowed.contains(&name.as_str()) {
+            return Err(format!(
+                "Option {:?} is not supported for {} destinations",
+                name,
+                remote.backend.name()
+            ));
+        }
+        if value.chars().any(char::is_control) {
+            return Err(format!("Option {:?} contains control characters", name));
+        }
+    }
+    for name in remote.backend.mandatory_options() {
+        if remote.option(name).trim().is_empty() {
+            return Err(format!(
+                "{} destinations need {} to be set",
+                remote.backend.name(),
+                name
+            ));
+        }
+    }
+    let required = remote.backend.required_options();
+    if required
+        .iter()
+        .all(|name| remote.option(name).trim().is_empty())
+    {
+        return Err(format!(
+            "{} destinations need one of: {}",
+            remote.backend.name(),
+            required.join(", ")
+        ));
+    }
+    if remote.root.chars().any(char::is_control) || remote.root.split('/').any(|c| c == "..") {
+        return Err(format!("Invalid remote folder: {:?}", remote.root));
+    }
+    Ok(())
+}
+
+/// An on-the-fly rclone remote for `remote`, configured through the
+/// environment so no setting or credential appears on a command line.
+pub fn remote_target(app: &AppHandle, remote: &RemoteDestination) -> Result<RcloneTarget, String> {
+    let credential = match &remote.credential_id {
+        Some(credential_id) => Some(read_secret(app, &secret_key(credential_id))?.ok_or_else(|| {
+            "No credentials are stored for this remote destination; enter them again in the destination's settings"
+                .to_string()
+        })?),
+        None => None,
+    };
+    target_with_credential(remote, credential.as_deref())
+}
+
+/// `remote_target` with the (obscured) credential given directly rather than
+/// read from the secret store.
+fn target_with_credential(
+    remote: &RemoteDestination,
+    credential: Option<&str>,
+) -> Result<RcloneTarget, String> {
+    validate_remote(remote)?;
+    let backend = remote.backend.name();
+    let env_name = |option: &str| {
+        format!(
+            "RCLONE_{}_{}",
+            backend.to_uppercase(),
+            option.to_uppercase()
+        )
+    };
+
+    let mut env: Vec<(String, String)> = remote
+        .options
+        .iter()
+        .map(|(name, value)| (env_name(name), value.clone()))
+        .collect();
+    if let Some(obscured) = credential {
+        let (option, rclone_obscures) = remote.backend.credential_option();
+        let value = if rclone_obscures {
+            obscured.to_string()
+        } else {
+            reveal(obscured)?
+        };
+        env.push((env_name(option), value));
+    }
+
+    Ok(RcloneTarget::new(
+        format!(":{}:{}", backend, remote.root.trim_matches('/')),
+        env,
+    ))
+}
+
+/// Adds a remote destination after checking that it can be reached and
+/// written to. `credential`, if given, is probed with as is and only goes to
+/// the app's secret store once the check has passed.
+#[tauri::command]
+pub async fn add_remote_destination(
+    app: AppHandle,
+    mut remote: RemoteDestination,
+    credential: Option<String>,
+    existing_ids: Option<Vec<u64>>,
+) -> Result<Destination, String> {
+    validate_remote(&remote)?;
+    info!("Adding remote destination {}", remote.display_path());
+
+    let credential = credential.filter(|c| !c.is_empty());
+    let target = match &credential {
+        Some(credential) => target_with_credential(&remote, Some(credential)),
+        None => remote_target(&app, &remote),
+    };
+    let probe = probe_target(&remote.display_path(), target).await;
+    if !probe.is_writable() {
+        return Err(probe
+            .error
+            .unwrap_or_else(|| format!("{} is not writable", remote.display_path())));
+    }
+
+    if let Some(credential) = credential {
+        let credential_id = remote
+            .credential_id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        store_secret(&app, &secret_key(&credential_id), &credential)?;
+        remote.credential_id = Some(credential_id);
+    }
+
+    let path = remote.display_path();
+    Ok(Destination {
+        id: new_destination_id(&existing_ids.unwrap_or_default()),
+        label: path.clone(),
+        path,
+        destination_type: remote.backend.destination_type().to_string(),
+        enabled: true,
+        volume_id: None,
+        fs_type: None,
+        removable: false,
+        volume_name: None,
+        remote: Some(remote),
+    })
+}
+
+#[tauri::command]
+pub async fn probe_remote_destination(
+    app: AppHandle,
+    remote: RemoteDestination,
+) -> Result<ProbeResult, String> {
+    validate_remote(&remote)?;
+    Ok(probe_remote(&app, &remote).await)
+}
+
+/// Checks that the remote folder can be listed and survives a
+/// create/delete round trip, creating the folder if needed.
+pub async fn probe_remote(app: &AppHandle, remote: &RemoteDestination) -> ProbeResult {
+    probe_target(&remote.display_path(), remote_target(app, remote)).await
+}
+
+async fn probe_target(name: &str, target: Result<RcloneTarget, String>) -> ProbeResult {
+    timed_probe(name, move || {
+        round_trip(&target.map_err(|e| (ProbeStatus::Unreachable, e))?)
+    })
+    .await
+}
+
+/// Runs `check` against a remote within the remote probe timeout and reports
+/// how it went, and how long it took, as a probe result.
+pub async fn timed_probe<F>(name: &str, check: F) -> ProbeResult
+where
+    F: FnOnce() -> Result<(), (ProbeStatus, String)> + Send + 'static,
+{
+    let started = Instant::now();
+    let outcome = run_blocking_with_timeout(REMOTE_PROBE_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|e| Err((ProbeStatus::Unreachable, e)));
+    let latency = started.elapsed();
+    let latency_ms = latency.as_millis() as u64;
+
+    let result = match outcome {
+        Ok(()) if latency > REMOTE_SLOW_THRESHOLD => ProbeResult {
+            status: ProbeStatus::Slow,
+            latency_ms,
+            error: None,
+        },
+        Ok(()) => ProbeResult {
+            status: ProbeStatus::Reachable,
+            latency_ms,
+            error: None,
+        },
+        Err((status, e)) => ProbeResult {
+            status,
+            latency_ms,
+            error: Some(e),
+        },
+    };
+
+    if result.is_writable() {
+        info!(
+            "Probed {}: {:?} in {}ms",
+            name, result.status, result.latency_ms
+        );
+    } else {
+        warn!(
+            "Probed {}: {:?} in {}ms ({})",
+            name,
+            result.status,
+            result.latency_ms,
+            result.error.as_deref().unwrap_or("")
+        );
+    }
+    result
+}
+
+fn round_trip(target: &RcloneTarget) -> Result<(), (ProbeStatus, String)> {
+    let run = |command: &str, path: String| {
+        let mut args = vec![command.to_string(), path];
+        args.extend(PROBE_ARGS.iter().map(|a| a.to_string()));
+        rclone_output(&args, target.env()).map_err(|e| (ProbeStatus::Unreachable, e))
+    };
+
+    let output = run("mkdir", target.path(""))?;
+    if !output.status.success() {
+        return Err((
+            ProbeStatus::Unreachable,
+            format!("Destination not accessible: {}", last_error_line(&output)),
+        ));
+    }
+
+    let probe_file = target.path(&format!(".jsync-probe-{}", uuid::Uuid::new_v4()));
+    let output = run("touch", probe_file.clone())?;
+    if !output.status.success() {
+        return Err((
+            ProbeStatus::ReadOnly,
+            format!("Destination is not writable: {}", last_error_line(&output)),
+        ));
+    }
+
+    let output = run("deletefile", probe_file)?;
+    if !output.status.success() {
+        warn!(
+            "Failed to remove probe file on remote: {}",
+            last_error_line(&output)
+        );
+    }
+    Ok(())
+}
+
+fn secret_key(credential_id: &str) -> String {
+    format!("remote/{}", credential_id)
+}
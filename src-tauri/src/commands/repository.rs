@@ -0,0 +1,694 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use tauri::AppHandle;
+
+use super::backup::{format_timestamp, progress_reporter, reset_cancellation, timestamp};
+use super::chunker::Chunker;
+use super::policy::{require_root, require_within, FsCommandError, RootKind};
+use super::session::collect_session_files;
+use super::sidecar::write_atomic;
+use super::snapshots::PARTIAL_SUFFIX;
+
+/// Folder at the root of a destination in repository mode. Every session backed
+/// up to the destination shares it, so identical data is stored once.
+pub const REPOSITORY_DIR: &str = ".jsync-repo";
+
+const REPOSITORY_VERSION: u32 = 1;
+const CONFIG_FILE: &str = "config.json";
+const CHUNKS_DIR: &str = "chunks";
+const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// Caps how many problems a check lists; the totals still count all of them.
+const MAX_REPORTED_PROBLEMS: usize = 50;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RepositoryConfig {
+    version: u32,
+    id: String,
+    hash: String,
+    created_at: String,
+}
+
+/// One backup run of a session: every file with the chunks that make it up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoSnapshot {
+    pub id: String,
+    pub session_name: String,
+    pub source_path: String,
+    pub source_host: String,
+    pub created_at: String,
+    pub image_count: Option<u32>,
+    pub total_bytes: u64,
+    pub files: Vec<RepoFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoFile {
+    /// Relative to the session folder, with `/` separators.
+    pub path: String,
+    pub size: u64,
+    pub modified: u64,
+    /// BLAKE3 hashes of the file's chunks, in order.
+    pub chunks: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepoSnapshotSummary {
+    pub id: String,
+    pub session_name: String,
+    pub source_path: String,
+    pub source_host: String,
+    pub created_at: String,
+    pub image_count: Option<u32>,
+    pub file_count: usize,
+    pub total_bytes: u64,
+}
+
+pub struct SnapshotProgress {
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+    pub files_done: u32,
+    pub total_files: u32,
+}
+
+#[derive(Debug)]
+pub struct SnapshotStats {
+    pub snapshot_id: String,
+    pub files: u32,
+    /// Bytes of chunks the repository didn't have yet.
+    pub new_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreStats {
+    pub path: String,
+    pub files: u32,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepositoryCheck {
+    pub snapshots: u32,
+    pub referenced_chunks: u64,
+    pub unreferenced_chunks: u64,
+    pub missing_count: u64,
+    pub corrupt_count: u64,
+    /// Whether chunk contents were re-hashed, not just checked for presence.
+    pub read_data: bool,
+    pub missing: Vec<String>,
+    pub corrupt: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+/// Why a chunk couldn't be read back.
+#[derive(Debug)]
+enum ChunkError {
+    /// Not a hash this repository could have stored a chunk under.
+    InvalidHash(String),
+    Unreadable {
+        hash: String,
+        error: std::io::Error,
+    },
+    /// The chunk's contents no longer match its hash.
+    Corrupt(String),
+}
+
+impl std::fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkError::InvalidHash(hash) => write!(f, "Chunk hash is invalid: {}", hash),
+            ChunkError::Unreadable { hash, error } => {
+                write!(f, "Failed to read chunk {}: {}", hash, error)
+            }
+            ChunkError::Corrupt(hash) => write!(f, "Chunk {} does not match its hash", hash),
+        }
+    }
+}
+
+impl From<ChunkError> for String {
+    fn from(e: ChunkError) -> Self {
+        e.to_string()
+    }
+}
+
+impl From<RepoSnapshot> for RepoSnapshotSummary {
+    fn from(snapshot: RepoSnapshot) -> Self {
+        RepoSnapshotSummary {
+            file_count: snapshot.files.len(),
+            id: snapshot.id,
+            session_name: snapshot.session_name,
+            source_path: snapshot.source_path,
+            source_host: snapshot.source_host,
+            created_at: snapshot.created_at,
+            image_count: snapshot.image_count,
+            total_bytes: snapshot.total_bytes,
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn list_repository_snapshots(
+    destination_path: String,
+    session_name: Option<String>,
+) -> Result<Vec<RepoSnapshotSummary>, FsCommandError> {
+    let root = require_root(&destination_path, RootKind::Destination)?;
+
+    let summaries = tokio::task::spawn_blocking(move || {
+        let repo = open_repository(&root)?;
+        let mut snapshots: Vec<RepoSnapshotSummary> = read_snapshots(&repo)?
+            .into_iter()
+            .filter(|s| {
+                session_name
+                    .as_ref()
+                    .is_none_or(|name| &s.session_name == name)
+            })
+            .map(RepoSnapshotSummary::from)
+            .collect();
+        snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok::<_, String>(snapshots)
+    })
+    .await
+    .map_err(|e| format!("Failed to list snapshots: {}", e))??;
+
+    Ok(summaries)
+}
+
+/// Restores a snapshot into `<target_path>/<session name>`, which must not exist
+/// yet. Every chunk is checked against its hash as it is read back. Progress
+/// goes out as `backup-progress` events under `progress_id`, and the restore
+/// stops when the backup is cancelled.
+#[tauri::command]
+pub async fn restore_repository_snapshot(
+    app: AppHandle,
+    destination_path: String,
+    snapshot_id: String,
+    target_path: String,
+    progress_id: u64,
+) -> Result<RestoreStats, FsCommandError> {
+    info!(
+        "Restoring repository snapshot {} from {} to {}",
+        snapshot_id, destination_path, target_path
+    );
+    let root = require_root(&destination_path, RootKind::Destination)?;
+    let target = require_within(&target_path, &[RootKind::Destination])?;
+
+    reset_cancellation();
+    let progress = progress_reporter(app, progress_id);
+    let stats = tokio::task::spawn_blocking(move || {
+        let repo = open_repository(&root)?;
+        restore_snapshot(&repo, &snapshot_id, &target, progress)
+    })
+    .await
+    .map_err(|e| format!("Restore failed: {}", e))??;
+
+    Ok(stats)
+}
+
+/// Checks that every chunk the snapshots reference is present and, with
+/// `read_data`, that its contents still match its hash.
+#[tauri::command]
+pub async fn check_repository(
+    destination_path: String,
+    read_data: Option<bool>,
+) -> Result<RepositoryCheck, FsCommandError> {
+    info!("Checking repository at {}", destination_path);
+    let root = require_root(&destination_path, RootKind::Destination)?;
+    let read_data = read_data.unwrap_or(false);
+
+    let report = tokio::task::spawn_blocking(move || {
+        let repo = open_repository(&root)?;
+        check(&repo, read_data)
+    })
+    .await
+    .map_err(|e| format!("Repository check failed: {}", e))??;
+
+    info!(
+        "Repository check: {} snapshots, {} missing and {} corrupt chunks",
+        report.snapshots, report.missing_count, report.corrupt_count
+    );
+    Ok(report)
+}
+
+/// Opens the repository at the root of a destination, creating it on first use.
+pub fn open_or_init_repository(destination_root: &Path) -> Result<PathBuf, String> {
+    let repo = destination_root.join(REPOSITORY_DIR);
+    if repo.join(CONFIG_FILE).is_file() {
+        return open_repository(destination_root);
+    }
+
+    std::fs::create_dir_all(repo.join(CHUNKS_DIR))
+        .and_then(|_| std::fs::create_dir_all(repo.join(SNAPSHOTS_DIR)))
+        .map_err(|e| format!("Failed to create repository: {}", e))?;
+    let config = RepositoryConfig {
+        version: REPOSITORY_VERSION,
+        id: uuid::Uuid::new_v4().simple().to_string(),
+        hash: "blake3".to_string(),
+        created_at: timestamp(),
+    };
+    write_json_atomic(&repo.join(CONFIG_FILE), &config)?;
+    info!("Created repository {} at {:?}", config.id, repo);
+    Ok(repo)
+}
+
+fn open_repository(destination_root: &Path) -> Result<PathBuf, String> {
+    let repo = destination_root.join(REPOSITORY_DIR);
+    let content = std::fs::read_to_string(repo.join(CONFIG_FILE))
+        .map_err(|e| format!("No repository at {:?}: {}", destination_root, e))?;
+    let config: RepositoryConfig = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse repository config: {}", e))?;
+    if config.version > REPOSITORY_VERSION {
+        return Err(format!(
+            "Repository format v{} is newer than this app supports (v{})",
+            config.version, REPOSITORY_VERSION
+        ));
+    }
+    Ok(repo)
+}
+
+/// Backs up the files under `roots` into the repository. Files whose size and
+/// modification time match the session's previous snapshot reuse its chunk
+/// list without being read. `progress` is called after every chunk and
+/// stops the run when it returns false.
+pub fn create_snapshot(
+    repo: &Path,
+    source: &Path,
+    roots: &[PathBuf],
+    session_name: &str,
+    image_count: Option<u32>,
+    mut progress: impl FnMut(&SnapshotProgress) -> bool,
+) -> Result<SnapshotStats, String> {
+    let mut files = Vec::new();
+    for root in roots {
+        collect_session_files(root, &mut files);
+    }
+    files.sort();
+    files.dedup();
+
+    let mut state = SnapshotProgress {
+        bytes_done: 0,
+        total_bytes: files.iter().map(|(_, size, _)| size).sum(),
+        total_files: files.len() as u32,
+        files_done: 0,
+    };
+
+    let source_path = source.to_string_lossy().to_string();
+    let previous: HashMap<String, RepoFile> = latest_snapshot(repo, session_name, &source_path)
+        .map(|s| s.files.into_iter().map(|f| (f.path.clone(), f)).collect())
+        .unwrap_or_default();
+
+    let mut entries = Vec::with_capacity(files.len());
+    let mut new_bytes = 0;
+    for (path, size, modified) in files {
+        let Some(relative) = relative_path(source, &path) else {
+            continue;
+        };
+
+        let unchanged = previous
+            .get(&relative)
+            .filter(|p| p.size == size && p.modified == modified);
+        let chunks = match unchanged {
+            Some(previous) => {
+                state.bytes_done += size;
+                previous.chunks.clone()
+            }
+            None => store_file(repo, &path, &mut state, &mut new_bytes, &mut progress)?,
+        };
+
+        entries.push(RepoFile {
+            path: relative,
+            size,
+            modified,
+            chunks,
+        });
+        state.files_done += 1;
+        if !progress(&state) {
+            return Err("Backup cancelled".to_string());
+        }
+    }
+
+    let created_at = chrono::Utc::now();
+    let snapshot = RepoSnapshot {
+        id: format!(
+            "{}-{}",
+            created_at.format("%Y%m%dT%H%M%SZ"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        ),
+        session_name: session_name.to_string(),
+        source_path,
+        source_host: super::sidecar::host_name(),
+        created_at: format_timestamp(created_at),
+        image_count,
+        total_bytes: state.total_bytes,
+        files: entries,
+    };
+    write_json_atomic(
+        &repo
+            .join(SNAPSHOTS_DIR)
+            .join(format!("{}.json", snapshot.id)),
+        &snapshot,
+    )?;
+
+    info!(
+        "Repository snapshot {} stored {} files, {} new bytes",
+        snapshot.id,
+        snapshot.files.len(),
+        new_bytes
+    );
+    Ok(SnapshotStats {
+        snapshot_id: snapshot.id,
+        files: state.files_done,
+        new_bytes,
+    })
+}
+
+fn store_file(
+    repo: &Path,
+    path: &Path,
+    state: &mut SnapshotProgress,
+    new_bytes: &mut u64,
+    progress: &mut impl FnMut(&SnapshotProgress) -> bool,
+) -> Result<Vec<String>, String> {
+    let file =
+        std::fs::File::open(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let mut chunker = Chunker::new(std::io::BufReader::new(file));
+    let mut chunks = Vec::new();
+
+    while let Some(chunk) = chunker
+        .next_chunk()
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?
+    {
+        let hash = blake3::hash(&chunk).to_hex().to_string();
+        let chunk_path = chunk_path(repo, &hash)?;
+        if !chunk_path.exists() {
+            write_chunk(&chunk_path, &chunk)?;
+            *new_bytes += chunk.len() as u64;
+        }
+        state.bytes_done += chunk.len() as u64;
+        chunks.push(hash);
+
+        if !progress(state) {
+            return Err("Backup cancelled".to_string());
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// Restores into a `.partial` folder that only gets the session's name once
+/// every file is back, so a failed or cancelled restore leaves nothing that
+/// looks complete.
+fn restore_snapshot(
+    repo: &Path,
+    snapshot_id: &str,
+    target: &Path,
+    mut progress: impl FnMut(&SnapshotProgress) -> bool,
+) -> Result<RestoreStats, String> {
+    let snapshot = read_snapshot(repo, snapshot_id)?;
+    super::policy::require_plain_name(&snapshot.session_name)?;
+    let session_target = target.join(&snapshot.session_name);
+    if session_target.exists() {
+        return Err(format!(
+            "{:?} already exists; choose another folder to restore into",
+            session_target
+        ));
+    }
+    let partial = target.join(format!("{}{}", snapshot.session_name, PARTIAL_SUFFIX));
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)
+            .map_err(|e| format!("Failed to clear {:?}: {}", partial, e))?;
+    }
+
+    let mut state = SnapshotProgress {
+        bytes_done: 0,
+        total_bytes: snapshot.files.iter().map(|f| f.size).sum(),
+        files_done: 0,
+        total_files: snapshot.files.len() as u32,
+    };
+    let restored = (|| {
+        for file in &snapshot.files {
+            let Some(file_target) = safe_join(&partial, &file.path) else {
+                return Err(format!(
+                    "Snapshot contains an invalid path: {:?}",
+                    file.path
+                ));
+            };
+            if let Some(parent) = file_target.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+            }
+            restore_file(repo, file, &file_target, |bytes| {
+                state.bytes_done += bytes;
+                progress(&state)
+            })?;
+            state.files_done += 1;
+        }
+        std::fs::create_dir_all(&partial)
+            .and_then(|_| std::fs::rename(&partial, &session_target))
+            .map_err(|e| format!("Failed to finish {:?}: {}", session_target, e))
+    })();
+    if let Err(e) = restored {
+        let _ = std::fs::remove_dir_all(&partial);
+        return Err(e);
+    }
+
+    info!(
+        "Restored snapshot {} to {:?} ({} files)",
+        snapshot_id, session_target, state.files_done
+    );
+    Ok(RestoreStats {
+        path: session_target.to_string_lossy().to_string(),
+        files: state.files_done,
+        bytes: state.bytes_done,
+    })
+}
+
+/// Writes one file of a snapshot to `target`, checking every chunk against its
+/// hash on the way. `progress` gets the bytes of each chunk written and stops
+/// the restore when it returns false. Returns the number of bytes written.
+pub fn restore_file(
+    repo: &Path,
+    file: &RepoFile,
+    target: &Path,
+    mut progress: impl FnMut(u64) -> bool,
+) -> Result<u64, String> {
+    let mut out = std::fs::File::create(target)
+        .map_err(|e| format!("Failed to create {:?}: {}", target, e))?;
+    let mut bytes = 0;
+    for hash in &file.chunks {
+        let data = read_chunk(repo, hash)?;
+        out.write_all(&data)
+            .map_err(|e| format!("Failed to write {:?}: {}", target, e))?;
+        bytes += data.len() as u64;
+        if !progress(data.len() as u64) {
+            return Err("Backup cancelled".to_string());
+        }
+    }
+    let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(file.modified);
+    if let Err(e) = out.set_modified(modified) {
+        warn!("Failed to set modification time of {:?}: {}", target, e);
+    }
+    Ok(bytes)
+}
+
+/// The repository on a destination and its newest snapshot of a session, if
+/// there is one.
+pub fn latest_session_snapshot(
+    destination_root: &Path,
+    session_name: &str,
+    source_path: &str,
+) -> Option<(PathBuf, RepoSnapshot)> {
+    let repo = open_repository(destination_root).ok()?;
+    let snapshot = latest_snapshot(&repo, session_name, source_path)?;
+    Some((repo, snapshot))
+}
+
+fn check(repo: &Path, read_data: bool) -> Result<RepositoryCheck, String> {
+    let snapshots = read_snapshots(repo)?;
+    let referenced: HashSet<&str> = snapshots
+        .iter()
+        .flat_map(|s| s.files.iter())
+        .flat_map(|f| f.chunks.iter().map(String::as_str))
+        .collect();
+
+    let mut report = RepositoryCheck {
+        snapshots: snapshots.len() as u32,
+        referenced_chunks: referenced.len() as u64,
+        unreferenced_chunks: 0,
+        missing_count: 0,
+        corrupt_count: 0,
+        read_data,
+        missing: Vec::new(),
+        corrupt: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    for hash in &referenced {
+        let path = match chunk_path(repo, hash) {
+            Ok(path) => path,
+            Err(_) => {
+                report.corrupt_count += 1;
+                if report.corrupt.len() < MAX_REPORTED_PROBLEMS {
+                    report.corrupt.push(hash.to_string());
+                }
+                continue;
+            }
+        };
+        if !path.is_file() {
+            report.missing_count += 1;
+            if report.missing.len() < MAX_REPORTED_PROBLEMS {
+                report.missing.push(hash.to_string());
+            }
+            continue;
+        }
+        if !read_data {
+            continue;
+        }
+        match read_chunk(repo, hash) {
+            Ok(_) => {}
+            Err(ChunkError::InvalidHash(_) | ChunkError::Corrupt(_)) => {
+                report.corrupt_count += 1;
+                if report.corrupt.len() < MAX_REPORTED_PROBLEMS {
+                    report.corrupt.push(hash.to_string());
+                }
+            }
+            Err(e) => report.errors.push(e.to_string()),
+        }
+    }
+
+    if let Ok(prefixes) = std::fs::read_dir(repo.join(CHUNKS_DIR)) {
+        for prefix in prefixes.flatten() {
+            if let Ok(chunks) = std::fs::read_dir(prefix.path()) {
+                report.unreferenced_chunks += chunks
+                    .flatten()
+                    .filter(|c| !referenced.contains(c.file_name().to_string_lossy().as_ref()))
+                    .count() as u64;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn latest_snapshot(repo: &Path, session_name: &str, source_path: &str) -> Option<RepoSnapshot> {
+    read_snapshots(repo)
+        .ok()?
+        .into_iter()
+        .filter(|s| s.session_name == session_name && s.source_path == source_path)
+        .max_by(|a, b| a.created_at.cmp(&b.created_at))
+}
+
+fn read_snapshots(repo: &Path) -> Result<Vec<RepoSnapshot>, String> {
+    let entries = std::fs::read_dir(repo.join(SNAPSHOTS_DIR))
+        .map_err(|e| format!("Failed to read repository snapshots: {}", e))?;
+
+    let mut snapshots = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|c| parse_snapshot(&c))
+        {
+            Ok(snapshot) => snapshots.push(snapshot),
+            Err(e) => warn!("Skipping unreadable snapshot {:?}: {}", path, e),
+        }
+    }
+    Ok(snapshots)
+}
+
+fn read_snapshot(repo: &Path, snapshot_id: &str) -> Result<RepoSnapshot, String> {
+    // Snapshot IDs are a timestamp and hex; anything else can't be one of ours
+    if snapshot_id.is_empty()
+        || !snapshot_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(format!("Invalid snapshot ID: {:?}", snapshot_id));
+    }
+    let path = repo
+        .join(SNAPSHOTS_DIR)
+        .join(format!("{}.json", snapshot_id));
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Snapshot {} not found: {}", snapshot_id, e))?;
+    parse_snapshot(&content).map_err(|e| format!("Failed to parse snapshot: {}", e))
+}
+
+/// Parses a snapshot, refusing one that lists a chunk under anything but a
+/// hash this repository could have stored it as.
+fn parse_snapshot(content: &str) -> Result<RepoSnapshot, String> {
+    let snapshot: RepoSnapshot = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    let invalid = snapshot
+        .files
+        .iter()
+        .flat_map(|f| f.chunks.iter())
+        .find(|hash| !is_chunk_hash(hash));
+    match invalid {
+        Some(hash) => Err(ChunkError::InvalidHash(hash.clone()).to_string()),
+        None => Ok(snapshot),
+    }
+}
+
+/// A lowercase hex BLAKE3 hash, as chunks are stored under.
+fn is_chunk_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn chunk_path(repo: &Path, hash: &str) -> Result<PathBuf, ChunkError> {
+    if !is_chunk_hash(hash) {
+        return Err(ChunkError::InvalidHash(hash.to_string()));
+    }
+    Ok(repo.join(CHUNKS_DIR).join(&hash[..2]).join(hash))
+}
+
+fn read_chunk(repo: &Path, hash: &str) -> Result<Vec<u8>, ChunkError> {
+    let data = std::fs::read(chunk_path(repo, hash)?).map_err(|error| ChunkError::Unreadable {
+        hash: hash.to_string(),
+        error,
+    })?;
+    if blake3::hash(&data).to_hex().as_str() != hash {
+        return Err(ChunkError::Corrupt(hash.to_string()));
+    }
+    Ok(data)
+}
+
+/// Chunks are written under a temporary name and renamed, so a chunk file that
+/// exists is always complete.
+fn write_chunk(path: &Path, data: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create chunk folder: {}", e))?;
+    }
+    write_atomic(path, data).map_err(|e| format!("Failed to write chunk: {}", e))
+}
+
+fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let content = serde_json::to_vec(value).map_err(|e| format!("Failed to serialize: {}", e))?;
+    write_atomic(path, &content).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+fn relative_path(source: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(source).ok()?;
+    let parts: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    Some(parts.join("/"))
+}
+
+/// Joins a stored relative path onto `base`, refusing anything that could
+/// escape it.
+pub fn safe_join(base: &Path, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative);
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    Some(base.join(relative))
+}
@@ -0,0 +1,254 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Manager};
+
+use super::backup::format_timestamp;
+use super::policy::{require_root, FsCommandError, RootKind};
+use super::quarantine::QUARANTINE_DIR;
+use super::receipt::{folder_totals, read_receipt, RECEIPT_FILE};
+use super::session::count_session_images;
+
+const CACHE_FILE: &str = "destination-scans.json";
+
+/// How deep below the destination root to look for sessions, so archives
+/// organised into year or client folders are still found.
+const MAX_SCAN_DEPTH: usize = 4;
+
+/// Scanned sessions keyed by their folder path. An entry is reused while the
+/// folder and its receipt keep the modification times it was scanned with and
+/// the receipt still records the same finish time, since a receipt rewritten
+/// within the same second keeps its modification time.
+static SCAN_CACHE: Mutex<Option<HashMap<PathBuf, CachedSession>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScannedSession {
+    pub name: String,
+    pub path: String,
+    pub size_bytes: u64,
+    pub file_count: u64,
+    pub last_backup: Option<String>,
+    pub image_count: Option<u32>,
+    pub source_host: Option<String>,
+    pub source_path: Option<String>,
+    /// False for folders recognised only by their `.cosessiondb`, whose
+    /// details are read from the folder itself.
+    pub has_receipt: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DestinationScan {
+    pub destination_path: String,
+    pub sessions: Vec<ScannedSession>,
+    pub cached_count: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedSession {
+    folder_modified: u64,
+    receipt_modified: Option<u64>,
+    #[serde(default)]
+    receipt_finished_at: Option<String>,
+    session: ScannedSession,
+}
+
+/// Lists the session backups on a destination. Unchanged folders are served
+/// from the cache unless `refresh` is set.
+#[tauri::command]
+pub async fn scan_destination(
+    app: AppHandle,
+    destination_path: String,
+    refresh: Option<bool>,
+) -> Result<DestinationScan, FsCommandError> {
+    info!("Scanning destination: {}", destination_path);
+    let root = require_root(&destination_path, RootKind::Destination)?;
+    let cache_path = scan_cache_path(&app);
+    let refresh = refresh.unwrap_or(false);
+
+    let (sessions, cached_count) =
+        tokio::task::spawn_blocking(move || scan_sessions(&root, cache_path.as_deref(), refresh))
+            .await
+            .map_err(|e| format!("Destination scan failed: {}", e))?;
+
+    info!(
+        "Found {} sessions on {} ({} from cache)",
+        sessions.len(),
+        destination_path,
+        cached_count
+    );
+    Ok(DestinationScan {
+        destination_path,
+        sessions,
+        cached_count,
+    })
+}
+
+/// Finds the session backups below `root`, reusing cached results for
+/// folders that haven't changed. Returns the sessions and how many were cached.
+pub fn scan_sessions(
+    root: &Path,
+    cache_path: Option<&Path>,
+    refresh: bool,
+) -> (Vec<ScannedSession>, u32) {
+    let mut cache = SCAN_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let cache = cache.get_or_insert_with(|| load_cache(cache_path));
+
+    let mut folders = Vec::new();
+    find_session_folders(root, 0, &mut folders);
+
+    let mut sessions = Vec::with_capacity(folders.len());
+    let mut cached_count = 0;
+    for folder in folders {
+        let folder_modified = modified_secs(&folder).unwrap_or(0);
+        let receipt_modified = modified_secs(&folder.join(RECEIPT_FILE));
+        let receipt_finished_at = receipt_modified
+            .and_then(|_| read_receipt(&folder))
+            .map(|r| r.finished_at);
+
+        let cached = cache.get(&folder).filter(|c| {
+            !refresh
+                && c.folder_modified == folder_modified
+                && c.receipt_modified == receipt_modified
+                && c.receipt_finished_at == receipt_finished_at
+        });
+        if let Some(cached) = cached {
+            sessions.push(cached.session.clone());
+            cached_count += 1;
+            continue;
+        }
+
+        let session = scan_session(&folder);
+        cache.insert(
+            folder,
+            CachedSession {
+                folder_modified,
+                receipt_modified,
+                receipt_finished_at,
+                session: session.clone(),
+            },
+        );
+        sessions.push(session);
+    }
+
+    if let Some(cache_path) = cache_path {
+        save_cache(cache_path, cache);
+    }
+    sessions.sort_by_key(|s| s.name.to_lowercase());
+    (sessions, cached_count)
+}
+
+/// Path of the scan cache in the app's cache folder.
+pub fn scan_cache_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .app_cache_dir()
+        .map(|dir| dir.join(CACHE_FILE))
+        .ok()
+}
+
+/// Collects folders that hold a session backup. Hidden folders, including the
+/// quarantine, are skipped, and a session's own subfolders are not searched.
+fn find_session_folders(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+    if depth > 0 && is_session_folder(dir) {
+        found.push(dir.to_path_buf());
+        return;
+    }
+    if depth >= MAX_SCAN_DEPTH {
+        return;
+    }
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') || name == QUARANTINE_DIR {
+            continue;
+        }
+        // file_type() doesn't follow symlinks, so linked folders are never entered
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            find_session_folders(&entry.path(), depth + 1, found);
+        }
+    }
+}
+
+fn is_session_folder(dir: &Path) -> bool {
+    if dir.join(RECEIPT_FILE).is_file() {
+        return true;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        Path::new(&entry.file_name())
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("cosessiondb"))
+    })
+}
+
+fn scan_session(folder: &Path) -> ScannedSession {
+    let (file_count, size_bytes) = folder_totals(folder);
+    let folder_name = folder
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    match read_receipt(folder) {
+        Some(receipt) => ScannedSession {
+            name: receipt.session_name,
+            path: folder.to_string_lossy().to_string(),
+            size_bytes,
+            file_count,
+            last_backup: Some(receipt.finished_at),
+            image_count: receipt.image_count,
+            source_host: Some(receipt.source_host),
+            source_path: Some(receipt.source_path),
+            has_receipt: true,
+        },
+        // Without a receipt the folder's own modification time is the best guess
+        None => ScannedSession {
+            name: folder_name,
+            path: folder.to_string_lossy().to_string(),
+            size_bytes,
+            file_count,
+            last_backup: std::fs::metadata(folder)
+                .and_then(|m| m.modified())
+                .ok()
+                .map(format_timestamp),
+            image_count: Some(count_session_images(folder)),
+            source_host: None,
+            source_path: None,
+            has_receipt: false,
+        },
+    }
+}
+
+fn modified_secs(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    modified
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+fn load_cache(cache_path: Option<&Path>) -> HashMap<PathBuf, CachedSession> {
+    cache_path
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(cache_path: &Path, cache: &HashMap<PathBuf, CachedSession>) {
+    if let Some(parent) = cache_path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let result = serde_json::to_string(cache)
+        .map_err(|e| e.to_string())
+        .and_then(|content| std::fs::write(cache_path, content).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        warn!("Failed to save destination scan cache: {}", e);
+    }
+}
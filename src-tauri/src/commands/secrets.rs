@@ -0,0 +1,128 @@
+use aes::cipher::{KeyIvInit, StreamCipher};
+use base64::Engine;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+use super::sidecar::write_atomic_with_mode;
+
+/// Secrets the app needs to run backups, such as encryption passphrases. They
+/// live in the app's data folder, readable only by the user, and never in a
+/// session's `.jsync` file, which travels with the session.
+const SECRETS_FILE: &str = "secrets.json";
+
+const SECRETS_VERSION: u32 = 1;
+
+/// rclone's fixed key for obscuring config values. Obscuring keeps secrets
+/// from being read at a glance; it is not encryption.
+const OBSCURE_KEY: [u8; 32] = [
+    0x9c, 0x93, 0x5b, 0x48, 0x73, 0x0a, 0x55, 0x4d, 0x6b, 0xfd, 0x7c, 0x63, 0xc8, 0x86, 0xa9, 0x2b,
+    0xd3, 0x90, 0x19, 0x8e, 0xb8, 0x12, 0x8a, 0xfb, 0xf4, 0xde, 0x16, 0x2b, 0x8b, 0x95, 0xf6, 0x38,
+];
+
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+
+/// Serialises read-modify-write cycles on the secrets file.
+static SECRETS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SecretsFile {
+    version: u32,
+    /// Values are stored obscured, the way rclone stores them in its config.
+    secrets: BTreeMap<String, String>,
+}
+
+/// Obscures `value` in rclone's format, so it can be handed to rclone as is.
+pub fn obscure(value: &str) -> String {
+    let iv = *uuid::Uuid::new_v4().as_bytes();
+    let mut data = value.as_bytes().to_vec();
+    Aes256Ctr::new(&OBSCURE_KEY.into(), &iv.into()).apply_keystream(&mut data);
+    let mut out = iv.to_vec();
+    out.extend(data);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(out)
+}
+
+/// Reverses `obscure`, for the few rclone options that take a plain value.
+pub fn reveal(obscured: &str) -> Result<String, String> {
+    let data = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(obscured)
+        .map_err(|e| format!("Invalid obscured value: {}", e))?;
+    if data.len() < 16 {
+        return Err("Invalid obscured value: too short".to_string());
+    }
+    let (iv, data) = data.split_at(16);
+    let iv: [u8; 16] = iv.try_into().map_err(|_| "Invalid obscured value")?;
+    let mut data = data.to_vec();
+    Aes256Ctr::new(&OBSCURE_KEY.into(), &iv.into()).apply_keystream(&mut data);
+    String::from_utf8(data).map_err(|_| "Invalid obscured value: not UTF-8".to_string())
+}
+
+/// The obscured value stored under `key`, if there is one.
+pub fn read_secret(app: &AppHandle, key: &str) -> Result<Option<String>, String> {
+    let _guard = SECRETS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(load(&secrets_path(app)?)?.secrets.remove(key))
+}
+
+/// Stores `value` under `key`, replacing any earlier value.
+pub fn store_secret(app: &AppHandle, key: &str, value: &str) -> Result<(), String> {
+    let _guard = SECRETS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = secrets_path(app)?;
+    let mut file = load(&path)?;
+    file.secrets.insert(key.to_string(), obscure(value));
+    save(&path, &mut file)?;
+    info!("Stored secret {}", key);
+    Ok(())
+}
+
+pub fn delete_secret(app: &AppHandle, key: &str) -> Result<(), String> {
+    let _guard = SECRETS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = secrets_path(app)?;
+    let mut file = load(&path)?;
+    if file.secrets.remove(key).is_some() {
+        save(&path, &mut file)?;
+        info!("Deleted secret {}", key);
+    }
+    Ok(())
+}
+
+fn secrets_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(SECRETS_FILE))
+        .map_err(|e| format!("App data folder not available: {}", e))
+}
+
+fn load(path: &Path) -> Result<SecretsFile, String> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => {
+            serde_json::from_str(&content).map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SecretsFile::default()),
+        Err(e) => Err(format!("Failed to read {:?}: {}", path, e)),
+    }
+}
+
+fn save(path: &Path, file: &mut SecretsFile) -> Result<(), String> {
+    file.version = SECRETS_VERSION;
+    let content = serde_json::to_vec_pretty(file)
+        .map_err(|e| format!("Failed to serialize secrets: {}", e))?;
+    write_private_file(path, &content)
+}
+
+/// Writes a file through a temporary copy created with owner-only permissions,
+/// so its contents are never readable by other users, even briefly.
+pub fn write_private_file(path: &Path, content: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+
+    if let Err(e) = write_atomic_with_mode(path, content, 0o600) {
+        warn!("Failed to write {:?}: {}", path, e);
+        return Err(format!("Failed to write {:?}: {}", path, e));
+    }
+    Ok(())
+}
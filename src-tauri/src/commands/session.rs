@@ -1,9 +1,24 @@
-use log::{error, info};
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
 use tauri::AppHandle;
 use tauri_plugin_shell::ShellExt;
 
+use super::crypt::EncryptionSettings;
+use super::destinations::new_destination_id;
+use super::policy::{
+    register_destination_root, register_session_root, require_plain_name, require_root,
+    require_within, FsCommandError, RootKind,
+};
+use super::probe::{run_blocking_with_timeout, PROBE_TIMEOUT};
+use super::prune::RetentionPolicy;
+use super::remote::{validate_remote, RemoteDestination};
+use super::sidecar::{
+    read_session_config, update_session_config, write_session_config, SIDECAR_VERSION,
+};
+use super::snapshots::{BackupMode, SnapshotRetention};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SessionInfo {
     pub name: String,
@@ -29,6 +44,94 @@ pub struct BackupDestinationConfig {
     pub has_existing_backup: bool,
     #[serde(default)]
     pub image_count_at_last_backup: Option<u32>,
+    #[serde(default)]
+    pub volume_id: Option<String>,
+    #[serde(default)]
+    pub fs_type: Option<String>,
+    #[serde(default)]
+    pub removable: bool,
+    #[serde(default)]
+    pub volume_name: Option<String>,
+    #[serde(default)]
+    pub mode: BackupMode,
+    #[serde(default)]
+    pub snapshot_retention: SnapshotRetention,
+    /// Which sessions on this destination may be pruned; nothing is pruned
+    /// while it is unset.
+    #[serde(default)]
+    pub retention: Option<RetentionPolicy>,
+    /// Redundancy of the parity data kept next to a mirrored backup, as a
+    /// percentage; no parity is written when unset.
+    #[serde(default)]
+    pub parity_percent: Option<u8>,
+    /// Largest archive volume in bytes, in archive mode.
+    #[serde(default)]
+    pub archive_volume_bytes: Option<u64>,
+    /// Destination folders on other disks that an archive continues on once
+    /// this destination is full, in order.
+    #[serde(default)]
+    pub archive_spill_paths: Vec<String>,
+    /// Set when backups are encrypted; the passphrase is kept by the app.
+    #[serde(default)]
+    pub encryption: Option<EncryptionSettings>,
+    /// Set for SFTP, S3 and WebDAV destinations, which rclone reaches directly.
+    #[serde(default)]
+    pub remote: Option<RemoteDestination>,
+    /// When this destination last finished a successful backup.
+    #[serde(default)]
+    pub last_synced: Option<String>,
+    #[serde(default)]
+    pub last_attempt: Option<BackupAttempt>,
+    #[serde(default)]
+    pub last_success: Option<BackupAttempt>,
+    #[serde(default)]
+    pub last_drill: Option<RestoreDrill>,
+    /// When a restore drill on this destination last passed.
+    #[serde(default)]
+    pub last_passed_drill: Option<String>,
+    /// Fields written by newer app versions, kept so saving doesn't drop them.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Result of one backup run to a destination. Only the backend writes these,
+/// so they are recorded even if the window is closed before the run ends.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupAttempt {
+    pub started_at: String,
+    pub finished_at: String,
+    pub outcome: BackupOutcome,
+    #[serde(default)]
+    pub files: u32,
+    #[serde(default)]
+    pub bytes: u64,
+    #[serde(default)]
+    pub image_count: Option<u32>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupOutcome {
+    Success,
+    Failed,
+    Cancelled,
+}
+
+/// Result of restoring a few random files from a destination to a temporary
+/// folder and checking them against the manifest or the source.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RestoreDrill {
+    pub drilled_at: String,
+    pub passed: bool,
+    #[serde(default)]
+    pub files_checked: u32,
+    /// Files that failed to restore or came back different.
+    #[serde(default)]
+    pub failures: Vec<String>,
+    #[serde(default)]
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -39,6 +142,13 @@ pub struct SessionConfig {
     pub image_count_at_last_backup: Option<u32>,
     pub selected_paths: Vec<String>,
     pub destinations: Vec<BackupDestinationConfig>,
+    /// Incremented on every save, used to detect edits from another machine.
+    #[serde(default)]
+    pub revision: u64,
+    #[serde(default)]
+    pub written_by: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[tauri::command]
@@ -46,7 +156,7 @@ pub async fn load_session_config(
     session_path: String,
     session_name: String,
 ) -> Result<SessionConfig, String> {
-    let config_path = Path::new(&session_path).join(format!(".{}.jsync", session_name));
+    let config_path = session_config_path(&session_path, &session_name)?;
 
     if !config_path.exists() {
         info!(
@@ -54,46 +164,139 @@ pub async fn load_session_config(
             config_path
         );
         return Ok(SessionConfig {
-            version: 1,
+            version: SIDECAR_VERSION,
             last_synced: None,
             image_count_at_last_backup: None,
             selected_paths: vec![session_path.clone()], // Default to all selected
             destinations: Vec::new(),
+            revision: 0,
+            written_by: None,
+            extra: Map::new(),
         });
     }
 
     info!("Loading session config from {:?}", config_path);
-    let content = std::fs::read_to_string(&config_path)
-        .map_err(|e| format!("Failed to read session config: {}", e))?;
-
-    let mut config: SessionConfig = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse session config: {}", e))?;
+    let mut config = read_session_config(&config_path)?;
+
+    // The repair is written back under the sidecar lock, so every machine and
+    // every later load sees the same new IDs
+    if repair_duplicate_ids(&mut config) {
+        let saved = update_session_config(&config_path, |stored| {
+            repair_duplicate_ids(stored);
+            Ok(())
+        });
+        match saved {
+            Ok(()) => config = read_session_config(&config_path)?,
+            Err(e) => warn!("Failed to save repaired destination IDs: {}", e),
+        }
+    }
 
-    // Verify if backups exist at destinations
+    // Verify if backups exist at destinations. A dead network mount would block
+    // here forever, so each check is bounded and counts as "no backup" on timeout.
     for dest in &mut config.destinations {
-        let dest_session_path = Path::new(&dest.path).join(&session_name);
-        dest.has_existing_backup = dest_session_path.exists() && dest_session_path.is_dir();
+        // Remotes have no local path; they are probed when a backup starts
+        if dest.remote.is_some() {
+            continue;
+        }
+        let stored_path = dest.path.clone();
+        let volume_id = dest.volume_id.clone();
+        let name = session_name.clone();
+        let checked = run_blocking_with_timeout(PROBE_TIMEOUT, move || {
+            // Follow the volume if it has been remounted under a different name
+            let current_path = volume_id
+                .and_then(|id| super::volume::resolve_destination_path(&stored_path, &id).ok())
+                .unwrap_or(stored_path);
+            let dest_session_path = Path::new(&current_path).join(&name);
+            let has_backup = dest_session_path.is_dir();
+            (current_path, has_backup)
+        })
+        .await;
+
+        match checked {
+            Ok((current_path, has_backup)) => {
+                if current_path != dest.path {
+                    info!(
+                        "Destination {} is now mounted at {}",
+                        dest.path, current_path
+                    );
+                    dest.path = current_path;
+                }
+                dest.has_existing_backup = has_backup;
+            }
+            Err(e) => {
+                warn!("Destination {} did not respond: {}", dest.path, e);
+                dest.has_existing_backup = false;
+            }
+        }
+
+        // Destinations saved in a registered session's sidecar are allowed roots
+        register_destination_root(&dest.path);
+        for path in &dest.archive_spill_paths {
+            register_destination_root(path);
+        }
     }
 
     Ok(config)
 }
 
+/// Older versions derived destination IDs from the clock, so two destinations
+/// added in the same millisecond share an ID. The first keeps it; the rest get new ones.
+/// Returns whether any ID changed.
+fn repair_duplicate_ids(config: &mut SessionConfig) -> bool {
+    let mut seen: Vec<u64> = Vec::with_capacity(config.destinations.len());
+    let all_ids: Vec<u64> = config.destinations.iter().map(|d| d.id).collect();
+    let mut repaired = false;
+
+    for dest in &mut config.destinations {
+        if seen.contains(&dest.id) {
+            let mut taken = all_ids.clone();
+            taken.extend(&seen);
+            let new_id = new_destination_id(&taken);
+            warn!(
+                "Destination {} has a duplicate ID {}, reassigning to {}",
+                dest.path, dest.id, new_id
+            );
+            dest.id = new_id;
+            repaired = true;
+        }
+        seen.push(dest.id);
+    }
+    repaired
+}
+
+/// Path of the `.<session>.jsync` sidecar, for a registered session folder only.
+pub fn session_config_path(session_path: &str, session_name: &str) -> Result<PathBuf, String> {
+    let session_root = require_root(session_path, RootKind::Session)?;
+    require_plain_name(session_name)?;
+    Ok(session_root.join(format!(".{}.jsync", session_name)))
+}
+
+/// Returns the machine whose changes were merged in, if another one saved the
+/// sidecar since this instance loaded it.
 #[tauri::command]
 pub async fn save_session_config(
     session_path: String,
     session_name: String,
-    config: SessionConfig,
-) -> Result<(), String> {
-    let config_path = Path::new(&session_path).join(format!(".{}.jsync", session_name));
+    mut config: SessionConfig,
+) -> Result<Option<String>, String> {
+    let config_path = session_config_path(&session_path, &session_name)?;
+
+    // Only destinations the backend has already allowed may be persisted, so the
+    // webview can't smuggle in a new root by writing it into the sidecar
+    for dest in &config.destinations {
+        match &dest.remote {
+            Some(remote) => validate_remote(remote)?,
+            None => {
+                require_root(&dest.path, RootKind::Destination)?;
+            }
+        }
+        for path in &dest.archive_spill_paths {
+            require_root(path, RootKind::Destination)?;
+        }
+    }
 
     info!("Saving session config to {:?}", config_path);
-    let content = serde_json::to_string_pretty(&config)
-        .map_err(|e| format!("Failed to serialize session config: {}", e))?;
-
-    std::fs::write(&config_path, content)
-        .map_err(|e| format!("Failed to write session config: {}", e))?;
-
-    Ok(())
+    write_session_config(&config_path, &mut config)
 }
 
 #[tauri::command]
@@ -200,6 +403,7 @@ pub async fn get_capture_one_session(app: AppHandle) -> Result<SessionInfo, Stri
 
     info!("Detected Session Name: {}", session_name);
     info!("Detected Session Folder: {}", session_folder);
+    register_session_root(&session_folder);
 
     // Calculate session size
     let size = get_folder_size(&session_folder).await.unwrap_or_else(|e| {
@@ -219,8 +423,9 @@ pub async fn get_capture_one_session(app: AppHandle) -> Result<SessionInfo, Stri
 }
 
 #[tauri::command]
-pub async fn get_session_contents(path: String) -> Result<Vec<SessionItem>, String> {
+pub async fn get_session_contents(path: String) -> Result<Vec<SessionItem>, FsCommandError> {
     info!("Getting session contents for path: {}", path);
+    require_within(&path, &[RootKind::Session])?;
 
     let entries =
         std::fs::read_dir(&path).map_err(|e| format!("Failed to read directory: {}", e))?;
@@ -280,7 +485,17 @@ async fn get_folder_size(path: &str) -> Result<String, String> {
 }
 
 async fn get_image_count(session_path: &str) -> u32 {
-    let capture_path = std::path::Path::new(session_path).join("Capture");
+    let session_path = PathBuf::from(session_path);
+
+    // Offload the recursive counting to a blocking thread to avoid blocking the async runtime
+    tokio::task::spawn_blocking(move || count_session_images(&session_path))
+        .await
+        .unwrap_or(0)
+}
+
+/// Counts the images in a session's `Capture` folder.
+pub fn count_session_images(session_path: &Path) -> u32 {
+    let capture_path = session_path.join("Capture");
     if !capture_path.exists() {
         return 0;
     }
@@ -291,10 +506,7 @@ async fn get_image_count(session_path: &str) -> u32 {
         "mos", "3fr", "ari", "sr2", "srf", "rw2",
     ];
 
-    // Offload the recursive counting to a blocking thread to avoid blocking the async runtime
-    tokio::task::spawn_blocking(move || count_images_recursive(&capture_path, &extensions))
-        .await
-        .unwrap_or(0)
+    count_images_recursive(&capture_path, &extensions)
 }
 
 fn count_images_recursive(dir: &std::path::Path, extensions: &[&str]) -> u32 {
@@ -317,3 +529,29 @@ fn count_images_recursive(dir: &std::path::Path, extensions: &[&str]) -> u32 {
     }
     count
 }
+
+/// Adds every file under `dir` to `files` with its size and modification time.
+pub fn collect_session_files(dir: &Path, files: &mut Vec<(PathBuf, u64, u64)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            collect_session_files(&entry.path(), files);
+        } else if file_type.is_file() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            files.push((entry.path(), metadata.len(), modified));
+        }
+    }
+}
@@ -0,0 +1,574 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::session::{BackupDestinationConfig, SessionConfig};
+
+/// Current `.jsync` schema version. Bump it together with a new entry in
+/// `MIGRATIONS` whenever the stored shape changes.
+pub const SIDECAR_VERSION: u32 = 4;
+
+/// Upgrade steps, where `MIGRATIONS[n]` takes a sidecar from version `n + 1`
+/// to `n + 2`. They operate on raw JSON so fields this build doesn't know are
+/// carried through untouched.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] =
+    &[migrate_v1_to_v2, migrate_v2_to_v3, migrate_v3_to_v4];
+
+/// A lock older than this is assumed to belong to a crashed or disconnected
+/// editor and is taken over.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(30);
+
+/// Pause before the first retry while waiting for the lock; it doubles after
+/// each attempt up to `MAX_LOCK_RETRY_DELAY`.
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(100);
+const MAX_LOCK_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Each sidecar as this app instance last read or wrote it. If the revision on
+/// disk has moved on since, another machine saved in between, and this copy is
+/// the common base the two sets of edits are merged against.
+static KNOWN_COPIES: Mutex<Option<HashMap<PathBuf, Value>>> = Mutex::new(None);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LockInfo {
+    host: String,
+    pid: u32,
+    acquired_at: u64,
+}
+
+/// Advisory lock on a sidecar, held for the duration of a write. It is a plain
+/// file created exclusively so it also works on SMB and NFS shares.
+struct SidecarLock {
+    path: PathBuf,
+}
+
+impl Drop for SidecarLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Reads a sidecar, upgrading it to the current version. If the file can't be
+/// read or parsed, the last good copy is used instead, so a damaged sidecar
+/// doesn't leave the session without its settings.
+pub fn read_session_config(config_path: &Path) -> Result<SessionConfig, String> {
+    let backup_path = backup_path(config_path);
+
+    let primary_error = match read_and_migrate(config_path) {
+        Ok((config, content)) => {
+            remember_good_copy(&backup_path, &content);
+            remember_copy(config_path, &config);
+            return Ok(config);
+        }
+        Err(e) => e,
+    };
+
+    warn!(
+        "Session config {:?} is unreadable ({}), trying last good copy",
+        config_path, primary_error
+    );
+    match read_and_migrate(&backup_path) {
+        Ok((config, _)) => {
+            info!("Recovered session config from {:?}", backup_path);
+            Ok(config)
+        }
+        Err(backup_error) => {
+            warn!(
+                "No usable backup copy at {:?}: {}",
+                backup_path, backup_error
+            );
+            Err(primary_error)
+        }
+    }
+}
+
+/// Copies fields this build doesn't understand from the sidecar currently on
+/// disk into `config`, so saving from an older app version doesn't drop data
+/// written by a newer one. The stored version is never lowered either.
+///
+/// Backup results are owned by the backend, so the stored ones always win over
+/// whatever copy the webview sends back.
+fn merge_stored_fields(config: &mut SessionConfig, config_path: &Path) {
+    let Some(previous) = std::fs::read_to_string(config_path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+    else {
+        config.version = config.version.max(SIDECAR_VERSION);
+        return;
+    };
+
+    let stored_version = previous.get("version").and_then(Value::as_u64).unwrap_or(1) as u32;
+    config.version = config.version.max(SIDECAR_VERSION).max(stored_version);
+
+    let Ok(previous) = serde_json::from_value::<SessionConfig>(previous) else {
+        return;
+    };
+
+    for (key, value) in previous.extra {
+        config.extra.entry(key).or_insert(value);
+    }
+
+    // Timestamps are RFC 3339 in UTC, so they order correctly as strings
+    if previous.last_synced > config.last_synced {
+        config.last_synced = previous.last_synced;
+        config.image_count_at_last_backup = previous.image_count_at_last_backup;
+    }
+
+    for dest in &mut config.destinations {
+        if let Some(old) = previous.destinations.iter().find(|d| d.id == dest.id) {
+            for (key, value) in &old.extra {
+                dest.extra
+                    .entry(key.clone())
+                    .or_insert_with(|| value.clone());
+            }
+            dest.last_synced = old.last_synced.clone();
+            dest.last_attempt = old.last_attempt.clone();
+            dest.last_success = old.last_success.clone();
+            dest.last_drill = old.last_drill.clone();
+            dest.last_passed_drill = old.last_passed_drill.clone();
+        }
+    }
+}
+
+/// Writes a sidecar without ever leaving a truncated file behind: the new
+/// content goes to a temp file that is fsynced and renamed over the old one,
+/// after the previous version has been kept as the backup copy. Saves are
+/// refused while another editor holds the lock. If the sidecar was changed by
+/// someone else since this instance last read it, `config` is merged with
+/// their changes and the other editor's name is returned.
+pub fn write_session_config(
+    config_path: &Path,
+    config: &mut SessionConfig,
+) -> Result<Option<String>, String> {
+    let _lock = acquire_lock(config_path, Duration::ZERO)?;
+
+    let on_disk = std::fs::read_to_string(config_path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok());
+    let disk_revision = on_disk
+        .as_ref()
+        .and_then(|v| v.get("revision"))
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+
+    let mut merged_from = None;
+    if let Some(known) = known_copy(config_path) {
+        let known_revision = known.get("revision").and_then(Value::as_u64).unwrap_or(0);
+        if disk_revision != known_revision {
+            let editor = on_disk
+                .as_ref()
+                .and_then(|v| v.get("written_by"))
+                .and_then(Value::as_str)
+                .unwrap_or("another machine")
+                .to_string();
+            let stored = on_disk
+                .clone()
+                .and_then(|v| serde_json::from_value::<SessionConfig>(v).ok());
+            let base = serde_json::from_value::<SessionConfig>(known).ok();
+            let (Some(stored), Some(base)) = (stored, base) else {
+                return Err(format!(
+                    "Session settings were changed by {} since they were loaded; reload the session before saving",
+                    editor
+                ));
+            };
+            info!(
+                "Session config {:?} was changed by {}, merging",
+                config_path, editor
+            );
+            merge_concurrent_edits(config, &base, stored);
+            merged_from = Some(editor);
+        }
+    }
+
+    merge_stored_fields(config, config_path);
+    config.revision = disk_revision + 1;
+    config.written_by = Some(host_name());
+
+    replace_file(config_path, config, on_disk.is_some())?;
+    remember_copy(config_path, config);
+    Ok(merged_from)
+}
+
+/// Applies the edits made in `config` since `base` on top of `stored`, the
+/// version another editor saved meanwhile. Selected paths and each destination
+/// are taken from whichever side changed them, ours winning when both did.
+fn merge_concurrent_edits(config: &mut SessionConfig, base: &SessionConfig, stored: SessionConfig) {
+    if same(&config.selected_paths, &base.selected_paths) {
+        config.selected_paths = stored.selected_paths;
+    }
+
+    let find =
+        |list: &[BackupDestinationConfig], id: u64| list.iter().find(|d| d.id == id).cloned();
+    let mut merged = Vec::new();
+    for theirs in stored.destinations {
+        match (
+            find(&base.destinations, theirs.id),
+            find(&config.destinations, theirs.id),
+        ) {
+            // Removed here
+            (Some(_), None) => {}
+            (Some(old), Some(ours)) if edited(&ours, &old) => merged.push(ours),
+            // Unchanged here, or added there
+            _ => merged.push(theirs),
+        }
+    }
+    for ours in &config.destinations {
+        if merged.iter().any(|d| d.id == ours.id) {
+            continue;
+        }
+        // Added here, or edited here after being removed there
+        match find(&base.destinations, ours.id) {
+            Some(old) if !edited(ours, &old) => {}
+            _ => merged.push(ours.clone()),
+        }
+    }
+    config.destinations = merged;
+}
+
+/// Whether the user changed a destination. Backup results are left out, since
+/// the backend records those and the webview's copy of them may be stale.
+fn edited(ours: &BackupDestinationConfig, old: &BackupDestinationConfig) -> bool {
+    let mut ours = ours.clone();
+    ours.last_synced = old.last_synced.clone();
+    ours.last_attempt = old.last_attempt.clone();
+    ours.last_success = old.last_success.clone();
+    ours.last_drill = old.last_drill.clone();
+    ours.last_passed_drill = old.last_passed_drill.clone();
+    ours.extra = old.extra.clone();
+    !same(&ours, old)
+}
+
+fn same(a: &impl Serialize, b: &impl Serialize) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// Applies `update` to the sidecar as it is stored right now, under the lock.
+/// Used by the backend to record state without going through the webview's copy.
+pub fn update_session_config(
+    config_path: &Path,
+    update: impl FnOnce(&mut SessionConfig) -> Result<(), String>,
+) -> Result<(), String> {
+    update_locked(config_path, Duration::ZERO, update)
+}
+
+/// Like `update_session_config`, but while another editor holds the lock it
+/// retries with backoff until that lock would count as stale and be taken
+/// over, so state that exists nowhere else is not lost to a concurrent save.
+pub fn update_session_config_waiting(
+    config_path: &Path,
+    update: impl FnOnce(&mut SessionConfig) -> Result<(), String>,
+) -> Result<(), String> {
+    update_locked(config_path, STALE_LOCK_AGE, update)
+}
+
+fn update_locked(
+    config_path: &Path,
+    wait: Duration,
+    update: impl FnOnce(&mut SessionConfig) -> Result<(), String>,
+) -> Result<(), String> {
+    let _lock = acquire_lock(config_path, wait)?;
+
+    let (mut config, _) = read_and_migrate(config_path)?;
+    let stored_revision = config.revision;
+    update(&mut config)?;
+    config.version = config.version.max(SIDECAR_VERSION);
+    config.revision = stored_revision + 1;
+    config.written_by = Some(host_name());
+
+    replace_file(config_path, &config, true)?;
+
+    // Only move on if the webview's copy was current; otherwise keep the older
+    // base so its next save is merged against what it actually loaded
+    let known_revision =
+        known_copy(config_path).and_then(|known| known.get("revision").and_then(Value::as_u64));
+    if known_revision == Some(stored_revision) {
+        remember_copy(config_path, &config);
+    }
+    Ok(())
+}
+
+fn replace_file(
+    config_path: &Path,
+    config: &SessionConfig,
+    keep_previous: bool,
+) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize session config: {}", e))?;
+
+    // Keep the version being replaced, but only if it was itself readable
+    if keep_previous {
+        if let Err(e) = std::fs::copy(config_path, backup_path(config_path)) {
+            warn!("Failed to keep previous session config: {}", e);
+        }
+    }
+
+    write_atomic(config_path, content.as_bytes())
+        .map_err(|e| format!("Failed to write session config: {}", e))
+}
+
+/// Replaces `path` with `bytes` without ever leaving a truncated file behind:
+/// they go to a temp file next to it that is fsynced and renamed over it.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    write_atomic_with_mode(path, bytes, 0o666)
+}
+
+/// `write_atomic` for a file created with `mode`, so it is never readable by
+/// others even for a moment.
+pub fn write_atomic_with_mode(path: &Path, bytes: &[u8], mode: u32) -> std::io::Result<()> {
+    let temp_path = sibling_path(path, &format!(".tmp-{}", std::process::id()));
+    let written = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(&temp_path)
+        .and_then(|mut f| {
+            f.write_all(bytes)?;
+            f.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temp_path, path));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = std::fs::File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+pub fn backup_path(config_path: &Path) -> PathBuf {
+    sibling_path(config_path, ".bak")
+}
+
+fn sibling_path(config_path: &Path, suffix: &str) -> PathBuf {
+    let mut name = config_path
+        .file_name()
+        .map(|n| n.to_os_string())
+        .unwrap_or_default();
+    name.push(suffix);
+    config_path.with_file_name(name)
+}
+
+/// Takes the sidecar lock, waiting up to `wait` for a live holder to finish.
+fn acquire_lock(config_path: &Path, wait: Duration) -> Result<SidecarLock, String> {
+    let lock_path = sibling_path(config_path, ".lock");
+    let info = LockInfo {
+        host: host_name(),
+        pid: std::process::id(),
+        acquired_at: now_secs(),
+    };
+    let content = serde_json::to_string(&info)
+        .map_err(|e| format!("Failed to serialize session lock: {}", e))?;
+
+    let deadline = Instant::now() + wait;
+    let mut delay = LOCK_RETRY_DELAY;
+    // A stale lock is cleared at most once; if it is back, someone is racing us
+    let mut cleared = false;
+    loop {
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            Ok(mut file) => {
+                file.write_all(content.as_bytes())
+                    .map_err(|e| format!("Failed to write session lock: {}", e))?;
+                return Ok(SidecarLock { path: lock_path });
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let holder = std::fs::read_to_string(&lock_path)
+                    .ok()
+                    .and_then(|c| serde_json::from_str::<LockInfo>(&c).ok());
+                let age = lock_age(&lock_path, holder.as_ref());
+                if age < STALE_LOCK_AGE {
+                    if Instant::now() + delay <= deadline {
+                        std::thread::sleep(delay);
+                        delay = (delay * 2).min(MAX_LOCK_RETRY_DELAY);
+                        continue;
+                    }
+                    let who = holder
+                        .map(|h| format!("{} (pid {})", h.host, h.pid))
+                        .unwrap_or_else(|| "another editor".to_string());
+                    return Err(format!(
+                        "Session settings are being saved by {}; try again shortly",
+                        who
+                    ));
+                }
+                if cleared {
+                    return Err("Failed to lock session config".to_string());
+                }
+                warn!(
+                    "Taking over stale session lock {:?} ({}s old)",
+                    lock_path,
+                    age.as_secs()
+                );
+                let _ = std::fs::remove_file(&lock_path);
+                cleared = true;
+            }
+            Err(e) => return Err(format!("Failed to lock session config: {}", e)),
+        }
+    }
+}
+
+fn lock_age(lock_path: &Path, holder: Option<&LockInfo>) -> Duration {
+    let acquired = match holder {
+        Some(info) => UNIX_EPOCH + Duration::from_secs(info.acquired_at),
+        // An unreadable lock file is judged by its modification time instead
+        None => std::fs::metadata(lock_path)
+            .and_then(|m| m.modified())
+            .unwrap_or(UNIX_EPOCH),
+    };
+    SystemTime::now()
+        .duration_since(acquired)
+        .unwrap_or(Duration::ZERO)
+}
+
+fn known_copy(config_path: &Path) -> Option<Value> {
+    let known = KNOWN_COPIES.lock().unwrap_or_else(|e| e.into_inner());
+    known.as_ref()?.get(config_path).cloned()
+}
+
+fn remember_copy(config_path: &Path, config: &SessionConfig) {
+    let Ok(copy) = serde_json::to_value(config) else {
+        return;
+    };
+    let mut known = KNOWN_COPIES.lock().unwrap_or_else(|e| e.into_inner());
+    known
+        .get_or_insert_with(HashMap::new)
+        .insert(config_path.to_path_buf(), copy);
+}
+
+pub fn host_name() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for its full length and gethostname NUL-terminates on success
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if rc != 0 {
+        return "unknown host".to_string();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).to_string()
+}
+
+/// Current time in whole seconds since the Unix epoch.
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn read_and_migrate(path: &Path) -> Result<(SessionConfig, String), String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read session config: {}", e))?;
+    let raw: Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse session config: {}", e))?;
+    let migrated = migrate(raw)?;
+    let config = serde_json::from_value(migrated)
+        .map_err(|e| format!("Failed to parse session config: {}", e))?;
+    Ok((config, content))
+}
+
+fn migrate(mut raw: Value) -> Result<Value, String> {
+    let obj = raw
+        .as_object_mut()
+        .ok_or("Session config is not a JSON object")?;
+
+    // Sidecars written before versioning was checked may carry 0 or nothing at all
+    let mut version = obj
+        .get("version")
+        .and_then(Value::as_u64)
+        .unwrap_or(1)
+        .max(1) as u32;
+
+    while version < SIDECAR_VERSION {
+        info!(
+            "Migrating session config from v{} to v{}",
+            version,
+            version + 1
+        );
+        MIGRATIONS[(version - 1) as usize](obj);
+        version += 1;
+        obj.insert("version".to_string(), Value::from(version));
+    }
+
+    if version > SIDECAR_VERSION {
+        warn!(
+            "Session config is v{}, newer than this app (v{}); unknown fields will be preserved",
+            version, SIDECAR_VERSION
+        );
+    }
+
+    Ok(raw)
+}
+
+fn remember_good_copy(backup_path: &Path, content: &str) {
+    if std::fs::read_to_string(backup_path).ok().as_deref() == Some(content) {
+        return;
+    }
+    if let Err(e) = std::fs::write(backup_path, content) {
+        warn!("Failed to keep backup copy at {:?}: {}", backup_path, e);
+    }
+}
+
+/// v2 ties destinations to their volume and mount details. Older destinations
+/// get explicit empty values; `has_existing_backup` is recomputed on load anyway.
+fn migrate_v1_to_v2(config: &mut Map<String, Value>) {
+    config
+        .entry("selected_paths")
+        .or_insert_with(|| Value::Array(Vec::new()));
+
+    let destinations = config
+        .entry("destinations")
+        .or_insert_with(|| Value::Array(Vec::new()));
+
+    if let Some(destinations) = destinations.as_array_mut() {
+        for dest in destinations.iter_mut().filter_map(Value::as_object_mut) {
+            dest.entry("has_existing_backup")
+                .or_insert(Value::Bool(false));
+            dest.entry("volume_id").or_insert(Value::Null);
+            dest.entry("fs_type").or_insert(Value::Null);
+            dest.entry("removable").or_insert(Value::Bool(false));
+            dest.entry("volume_name").or_insert(Value::Null);
+        }
+    }
+}
+
+/// v3 records backup results per destination. Destinations that already have
+/// an image count from a successful backup inherit the session-wide time.
+fn migrate_v2_to_v3(config: &mut Map<String, Value>) {
+    let last_synced = config.get("last_synced").cloned().unwrap_or(Value::Null);
+
+    if let Some(destinations) = config.get_mut("destinations").and_then(Value::as_array_mut) {
+        for dest in destinations.iter_mut().filter_map(Value::as_object_mut) {
+            let backed_up = dest
+                .get("image_count_at_last_backup")
+                .is_some_and(|count| !count.is_null());
+            let inherited = if backed_up {
+                last_synced.clone()
+            } else {
+                Value::Null
+            };
+            dest.entry("last_synced").or_insert(inherited);
+            dest.entry("last_attempt").or_insert(Value::Null);
+            dest.entry("last_success").or_insert(Value::Null);
+        }
+    }
+}
+
+/// v4 keeps each destination's retention policy, which used to be passed in
+/// with every prune. Destinations start without one.
+fn migrate_v3_to_v4(config: &mut Map<String, Value>) {
+    if let Some(destinations) = config.get_mut("destinations").and_then(Value::as_array_mut) {
+        for dest in destinations.iter_mut().filter_map(Value::as_object_mut) {
+            dest.entry("retention").or_insert(Value::Null);
+        }
+    }
+}
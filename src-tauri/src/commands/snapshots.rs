@@ -0,0 +1,266 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::backup::format_timestamp;
+use super::compat::{decode_name, FsFamily};
+use super::manifest::is_backup_metadata;
+use super::policy::{require_plain_name, require_root, FsCommandError, RootKind};
+use super::quarantine::session_backup_path;
+use super::receipt::read_receipt;
+
+/// Folder inside a session backup that holds its snapshots in snapshot mode.
+pub const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// Suffix of a snapshot that is still being written. It is only renamed to its
+/// final name once complete, so an interrupted run never looks like a snapshot.
+pub const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Snapshot folder names are UTC timestamps without characters FAT or SMB reject.
+const SNAPSHOT_NAME_FORMAT: &str = "%Y-%m-%dT%H%M%SZ";
+
+/// How a destination stores backups: a single mirror kept in sync with the
+/// session, a new hard-linked snapshot per run, a deduplicated chunk
+/// repository shared by every session on the destination, or a set of
+/// fixed-size archive volumes per run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupMode {
+    #[default]
+    Mirror,
+    Snapshots,
+    Repository,
+    Archive,
+}
+
+/// Which snapshots (or, in archive mode, archives) survive a run. One is kept
+/// if it is among the newest `keep_last` or younger than `keep_within_days`;
+/// the newest is always kept. With neither set, every snapshot is kept, and
+/// the last `DEFAULT_ARCHIVES_KEPT` archives.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotRetention {
+    #[serde(default)]
+    pub keep_last: Option<u32>,
+    #[serde(default)]
+    pub keep_within_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotInfo {
+    pub name: String,
+    pub path: String,
+    pub created_at: Option<String>,
+    pub image_count: Option<u32>,
+    pub files_transferred: Option<u32>,
+    pub bytes_transferred: Option<u64>,
+}
+
+#[tauri::command]
+pub async fn list_snapshots(
+    destination_path: String,
+    session_name: String,
+) -> Result<Vec<SnapshotInfo>, FsCommandError> {
+    require_root(&destination_path, RootKind::Destination)?;
+    let backup_path = session_backup_path(&destination_path, &session_name)?;
+    let snapshots_dir = backup_path.join(SNAPSHOTS_DIR);
+
+    let snapshots = tokio::task::spawn_blocking(move || {
+        complete_snapshots(&snapshots_dir)
+            .into_iter()
+            .rev()
+            .map(|(name, path)| {
+                let receipt = read_receipt(&path);
+                SnapshotInfo {
+                    created_at: snapshot_time(&name).map(|t| format_timestamp(t.and_utc())),
+                    image_count: receipt.as_ref().and_then(|r| r.image_count),
+                    files_transferred: receipt.as_ref().map(|r| r.files_transferred),
+                    bytes_transferred: receipt.as_ref().map(|r| r.bytes_transferred),
+                    name,
+                    path: path.to_string_lossy().to_string(),
+                }
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Failed to list snapshots: {}", e))?;
+
+    Ok(snapshots)
+}
+
+/// Name for a snapshot started now.
+pub fn new_snapshot_name() -> String {
+    chrono::Utc::now().format(SNAPSHOT_NAME_FORMAT).to_string()
+}
+
+/// The newest finished snapshot, if any.
+pub fn latest_snapshot(snapshots_dir: &Path) -> Option<PathBuf> {
+    complete_snapshots(snapshots_dir)
+        .pop()
+        .map(|(_, path)| path)
+}
+
+/// Hard-links every file of `previous` into `snapshot` that rclone left out
+/// because it was unchanged, i.e. files still in the source and covered by
+/// `roots`. Files rclone copied are never touched, so nothing is ever written
+/// through a link into an older snapshot. Falls back to copying where the
+/// filesystem refuses hard links. Names on a `family` destination are stored
+/// encoded, so they are decoded before looking for the source file. Returns
+/// how many files were linked.
+pub fn link_unchanged(
+    previous: &Path,
+    snapshot: &Path,
+    source: &Path,
+    roots: &[PathBuf],
+    family: FsFamily,
+) -> Result<u64, String> {
+    let mut linked = 0;
+    let dirs = LinkDirs {
+        previous,
+        snapshot,
+        source,
+        roots,
+        family,
+    };
+    link_recursive(previous, &dirs, &mut linked)?;
+    Ok(linked)
+}
+
+struct LinkDirs<'a> {
+    previous: &'a Path,
+    snapshot: &'a Path,
+    source: &'a Path,
+    roots: &'a [PathBuf],
+    family: FsFamily,
+}
+
+impl LinkDirs<'_> {
+    /// The session file a file stored at `relative` was copied from, unless
+    /// its decoded name could not be a single file name.
+    fn source_file(&self, relative: &Path) -> Option<PathBuf> {
+        let mut source_file = self.source.to_path_buf();
+        for component in relative.components() {
+            let name = decode_name(self.family, &component.as_os_str().to_string_lossy());
+            require_plain_name(&name).ok()?;
+            source_file.push(name);
+        }
+        Some(source_file)
+    }
+}
+
+fn link_recursive(dir: &Path, dirs: &LinkDirs, linked: &mut u64) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("Failed to read {:?}: {}", dir, e))?;
+
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            link_recursive(&path, dirs, linked)?;
+            continue;
+        }
+        if !file_type.is_file() || is_backup_metadata(&entry.file_name()) {
+            continue;
+        }
+
+        let Ok(relative) = path.strip_prefix(dirs.previous) else {
+            continue;
+        };
+        let Some(source_file) = dirs.source_file(relative) else {
+            continue;
+        };
+        let target = dirs.snapshot.join(relative);
+        let selected = dirs.roots.iter().any(|root| source_file.starts_with(root));
+        if !selected || target.exists() || !source_file.is_file() {
+            continue;
+        }
+
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        if let Err(e) = std::fs::hard_link(&path, &target) {
+            warn!("Hard link failed for {:?}, copying instead: {}", target, e);
+            std::fs::copy(&path, &target)
+                .map_err(|e| format!("Failed to copy {:?}: {}", target, e))?;
+        }
+        *linked += 1;
+    }
+
+    Ok(())
+}
+
+/// Deletes snapshots the retention settings no longer cover, along with
+/// partial snapshots left by interrupted runs. Returns how many were removed.
+pub fn apply_snapshot_retention(snapshots_dir: &Path, retention: &SnapshotRetention) -> u32 {
+    let mut removed = 0;
+
+    if let Ok(entries) = std::fs::read_dir(snapshots_dir) {
+        for entry in entries.flatten() {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(PARTIAL_SUFFIX)
+            {
+                match std::fs::remove_dir_all(entry.path()) {
+                    Ok(()) => removed += 1,
+                    Err(e) => warn!(
+                        "Failed to remove partial snapshot {:?}: {}",
+                        entry.path(),
+                        e
+                    ),
+                }
+            }
+        }
+    }
+
+    if retention.keep_last.is_none() && retention.keep_within_days.is_none() {
+        return removed;
+    }
+
+    let cutoff = retention
+        .keep_within_days
+        .map(|days| chrono::Utc::now().naive_utc() - chrono::Duration::days(i64::from(days)));
+    let snapshots = complete_snapshots(snapshots_dir);
+    let keep_last = retention.keep_last.unwrap_or(0).max(1) as usize;
+    let expired = snapshots.len().saturating_sub(keep_last);
+
+    for (name, path) in snapshots.into_iter().take(expired) {
+        let recent = match (cutoff, snapshot_time(&name)) {
+            (Some(cutoff), Some(time)) => time >= cutoff,
+            _ => false,
+        };
+        if recent {
+            continue;
+        }
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => {
+                info!("Removed expired snapshot {:?}", path);
+                removed += 1;
+            }
+            Err(e) => warn!("Failed to remove snapshot {:?}: {}", path, e),
+        }
+    }
+
+    removed
+}
+
+/// Finished snapshots, oldest first.
+fn complete_snapshots(snapshots_dir: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = std::fs::read_dir(snapshots_dir) else {
+        return Vec::new();
+    };
+
+    let mut snapshots: Vec<(String, PathBuf)> = entries
+        .flatten()
+        .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .map(|e| (e.file_name().to_string_lossy().to_string(), e.path()))
+        .filter(|(name, _)| snapshot_time(name).is_some())
+        .collect();
+    snapshots.sort();
+    snapshots
+}
+
+fn snapshot_time(name: &str) -> Option<chrono::NaiveDateTime> {
+    chrono::NaiveDateTime::parse_from_str(name, SNAPSHOT_NAME_FORMAT).ok()
+}
@@ -0,0 +1,105 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::sidecar::now_secs;
+
+/// Name of the marker file written into every destination root. It ties the
+/// destination to the physical volume it was added on, independent of where
+/// macOS happens to mount that volume.
+pub const VOLUME_MARKER_FILE: &str = ".jsync-volume";
+
+const VOLUMES_ROOT: &str = "/Volumes";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VolumeMarker {
+    pub volume_id: String,
+    pub created_at: u64,
+}
+
+/// Returns the volume ID stored at `dest_path`, writing a new marker first if
+/// the destination doesn't have one yet. Reusing an existing marker means the
+/// same drive keeps one identity across sessions.
+pub fn ensure_volume_marker(dest_path: &str) -> Result<String, String> {
+    if let Some(marker) = read_volume_marker(Path::new(dest_path)) {
+        return Ok(marker.volume_id);
+    }
+
+    let marker = VolumeMarker {
+        volume_id: uuid::Uuid::new_v4().to_string(),
+        created_at: now_secs(),
+    };
+
+    let content = serde_json::to_string_pretty(&marker)
+        .map_err(|e| format!("Failed to serialize volume marker: {}", e))?;
+    std::fs::write(Path::new(dest_path).join(VOLUME_MARKER_FILE), content)
+        .map_err(|e| format!("Failed to write volume marker: {}", e))?;
+
+    info!("Wrote volume marker {} to {}", marker.volume_id, dest_path);
+    Ok(marker.volume_id)
+}
+
+pub fn read_volume_marker(dest_path: &Path) -> Option<VolumeMarker> {
+    let content = std::fs::read_to_string(dest_path.join(VOLUME_MARKER_FILE)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Finds the current mount path of the destination identified by `volume_id`.
+///
+/// The stored path is tried first. If the marker there is missing or belongs to
+/// another volume, every mounted volume is checked at the same relative path,
+/// which covers macOS remounting `Backup` as `Backup 1`. An error is returned
+/// rather than falling back to the stored path, so a different drive that
+/// happens to share the name is never written to.
+pub fn resolve_destination_path(stored_path: &str, volume_id: &str) -> Result<String, String> {
+    let stored = Path::new(stored_path);
+
+    match read_volume_marker(stored) {
+        Some(marker) if marker.volume_id == volume_id => return Ok(stored_path.to_string()),
+        Some(_) => warn!(
+            "Volume at {} has a different identity, searching mounted volumes",
+            stored_path
+        ),
+        None => info!(
+            "No matching volume marker at {}, searching mounted volumes",
+            stored_path
+        ),
+    }
+
+    if let Some(found) = find_relocated_destination(stored, volume_id) {
+        info!("Destination {} relocated to {:?}", stored_path, found);
+        return Ok(found.to_string_lossy().to_string());
+    }
+
+    if stored.exists() {
+        Err(format!(
+            "The drive mounted at {} is not the one this destination was added on",
+            stored_path
+        ))
+    } else {
+        Err(format!("Destination not accessible: {}", stored_path))
+    }
+}
+
+fn find_relocated_destination(stored: &Path, volume_id: &str) -> Option<PathBuf> {
+    // Only paths under /Volumes can move between mount points
+    let relative = stored.strip_prefix(VOLUMES_ROOT).ok()?;
+    let mut components = relative.components();
+    components.next()?;
+    let within_volume = components.as_path();
+
+    let entries = std::fs::read_dir(VOLUMES_ROOT).ok()?;
+    for entry in entries.flatten() {
+        let candidate = entry.path().join(within_volume);
+        if candidate == stored {
+            continue;
+        }
+        if let Some(marker) = read_volume_marker(&candidate) {
+            if marker.volume_id == volume_id {
+                return Some(candidate);
+            }
+        }
+    }
+
+    None
+}
@@ -1,4 +1,4 @@
-use log::info;
+use log::{error, info};
 use tauri::{
     menu::{Menu, MenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
@@ -45,6 +45,12 @@ pub fn run() {
                 macos_dialog::warm_up();
             }
 
+            // rclone only ever reads JSync's own config, never the user's. If it
+            // can't be created now, every rclone run tries again and reports why.
+            if let Err(e) = commands::rclone::init_rclone_config(app.handle()) {
+                error!("Failed to set up rclone config: {}", e);
+            }
+
             // Setup system tray
             setup_tray(app)?;
 
@@ -84,8 +90,43 @@ pub fn run() {
             commands::destinations::check_path_exists,
             commands::destinations::create_directory,
             commands::destinations::get_disk_info,
+            commands::probe::probe_destination,
+            commands::remote::add_remote_destination,
+            commands::remote::probe_remote_destination,
+            commands::rclone_config::list_rclone_remotes,
+            commands::rclone_config::create_rclone_remote,
+            commands::rclone_config::test_rclone_remote,
+            commands::rclone_config::delete_rclone_remote,
+            commands::quarantine::list_quarantine,
+            commands::quarantine::restore_quarantined,
+            commands::quarantine::purge_quarantine,
+            commands::scan::scan_destination,
+            commands::prune::plan_prune,
+            commands::prune::apply_prune,
+            commands::snapshots::list_snapshots,
+            commands::repository::list_repository_snapshots,
+            commands::repository::restore_repository_snapshot,
+            commands::repository::check_repository,
+            commands::archive::list_archives,
+            commands::archive::plan_archive_restore,
+            commands::archive::restore_archive,
+            commands::audit::audit_destination,
+            commands::audit::cancel_audit,
+            commands::drill::run_restore_drill,
+            commands::crypt::set_encryption_passphrase,
+            commands::crypt::has_encryption_passphrase,
+            commands::crypt::forget_encryption_passphrase,
+            commands::crypt::restore_encrypted_backup,
+            commands::crypt::verify_encrypted_backup,
+            commands::export::export_session_archive,
+            commands::export::verify_session_archive,
+            commands::export::extract_session_archive,
+            commands::parity::create_parity,
+            commands::parity::verify_parity,
+            commands::parity::repair_from_parity,
             commands::backup::start_backup,
             commands::backup::cancel_backup,
+            commands::backup::preview_backup,
             commands::permissions::check_full_disk_access,
             commands::quit_app,
             commands::relaunch_app,
@@ -39,9 +39,11 @@ import {
   createDirectory,
   startBackup,
   cancelBackup,
+  runRestoreDrill,
   onBackupProgress,
   onBackupComplete,
   onBackupError,
+  onDestinationRelocated,
   sendBackupNotification,
   onRefreshSession,
   requestNotificationPermission,
@@ -81,6 +83,8 @@ const completionAnimations = `
   }
 `;
 
+const DRILL_INTERVAL_MINUTES = 24 * 60;
+
 function App() {
   // Application State
   const [view, setView] = useState<"main" | "location-detail">("main");
@@ -224,11 +228,22 @@ function App() {
         selected_paths: selectedPaths,
         destinations: destinations,
       };
-      saveSessionConfig(session.path, session.name, config).catch(
-        console.error,
-      );
+      saveSessionConfig(session.path, session.name, config)
+        .then((mergedFrom) => {
+          if (!mergedFrom) return;
+          // Another machine saved meanwhile; show the merged settings
+          loadSessionData(session);
+          sendBackupNotification(
+            "Session Settings Merged",
+            `${mergedFrom} also changed this session's settings; your changes were applied on top of theirs.`,
+          );
+        })
+        .catch((error) => {
+          console.error("Failed to save session config:", error);
+          sendBackupNotification("Session Settings Not Saved", String(error));
+        });
     }
-  }, [session, destinations, selectedPaths, lastSynced, imageCountAtLastBackup, isLoadingConfig]);
+  }, [session, destinations, selectedPaths, lastSynced, imageCountAtLastBackup, isLoadingConfig, loadSessionData]);
 
   // Reset backup status when session changes
   useEffect(() => {
@@ -243,7 +258,7 @@ function App() {
     const checkAccessibility = async () => {
       const inaccessible = new Set<number>();
       for (const dest of destinations) {
-        const exists = await checkPathExists(dest.path);
+        const exists = await checkPathExists(dest.path).catch(() => false);
         if (!exists) inaccessible.add(dest.id);
       }
       setInaccessibleDests(inaccessible);
@@ -436,6 +451,22 @@ function App() {
     };
   }, []); // Empty dependency array, using refs for dynamic state
 
+  // Follow destinations whose volume was remounted under a different path
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    onDestinationRelocated((relocated) => {
+      setDestinations((prev) =>
+        prev.map((d) =>
+          d.id === relocated.destination_id ? { ...d, path: relocated.path } : d,
+        ),
+      );
+    }).then((u) => (unlisten = u));
+
+    return () => {
+      if (unlisten) unlisten();
+    };
+  }, []);
+
   const handleStartBackup = useCallback(async () => {
     if (enabledCount === 0 || !session) return;
 
@@ -456,7 +487,7 @@ function App() {
     const enabledDests = destinations.filter(d => d.enabled);
     for (const dest of enabledDests) {
       if (dest.destination_type === "local") {
-        const exists = await checkPathExists(dest.path);
+        const exists = await checkPathExists(dest.path).catch(() => false);
         if (!exists) {
           try {
             await createDirectory(dest.path);
@@ -516,6 +547,49 @@ function App() {
     handleStartBackup,
   );
 
+  // Restore drills: once a day, restore a few files from each destination and check them
+  const handleRestoreDrill = useCallback(async () => {
+    if (!session || backupState === "running") return;
+
+    const due = destinations.filter(
+      (d) =>
+        d.enabled &&
+        (!d.last_drill ||
+          Date.now() - new Date(d.last_drill.drilled_at).getTime() >= DRILL_INTERVAL_MINUTES * 60000),
+    );
+    if (due.length === 0) return;
+
+    try {
+      const results = await runRestoreDrill(session.path, session.name, due);
+      // Destinations that weren't connected are left due for the next run
+      const drills = results.flatMap((r) => (r.drill ? [{ destination_id: r.destination_id, drill: r.drill }] : []));
+      setDestinations((prev) =>
+        prev.map((d) => {
+          const result = drills.find((r) => r.destination_id === d.id);
+          if (!result) return d;
+          return {
+            ...d,
+            last_drill: result.drill,
+            last_passed_drill: result.drill.passed ? result.drill.drilled_at : d.last_passed_drill,
+          };
+        }),
+      );
+
+      const failed = drills.filter((r) => !r.drill.passed);
+      if (failed.length > 0 && notificationsEnabledRef.current) {
+        const labels = failed.map((r) => destinations.find((d) => d.id === r.destination_id)?.label ?? "Unknown");
+        sendBackupNotification(
+          "Restore Drill Failed",
+          `Files restored from ${labels.join(", ")} did not check out. Open the location details for more.`,
+        );
+      }
+    } catch (error) {
+      console.error("Restore drill failed:", error);
+    }
+  }, [session, destinations, backupState]);
+
+  useScheduler(true, DRILL_INTERVAL_MINUTES, handleRestoreDrill);
+
   const formatLastSync = (iso: string | null) => {
     if (!iso) return "Never synced";
     const date = new Date(iso);
@@ -652,7 +726,10 @@ function App() {
         return;
       }
 
-      const destination = await parseDestination(path);
+      const destination = await parseDestination(
+        path,
+        destinations.map((d) => d.id),
+      );
       // Backend parse_destination doesn't know about our sidecar extension yet
       const newDest: Destination = {
         ...destination,
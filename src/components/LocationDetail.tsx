@@ -129,6 +129,23 @@ export function LocationDetail({
           </span>
         </div>
 
+        {/* Restore Drill */}
+        <div className="flex items-center justify-between px-1">
+          <span className="text-[9px] font-bold text-white/20 uppercase tracking-[0.12em]">
+            Restore Drill
+          </span>
+          <span
+            className={`text-[11px] font-medium ${dest.last_drill && !dest.last_drill.passed ? "text-red-400/80" : "text-white/40"}`}
+            title={dest.last_drill?.error ?? dest.last_drill?.failures.join("\n") ?? undefined}
+          >
+            {dest.last_drill && !dest.last_drill.passed
+              ? "Last drill failed"
+              : dest.last_passed_drill
+                ? `Passed ${new Date(dest.last_passed_drill).toLocaleDateString()}`
+                : "Not yet tested"}
+          </span>
+        </div>
+
         {/* Capacity Card */}
         {diskInfo && (() => {
           const usedBytes = diskInfo.total_bytes - diskInfo.available_bytes;
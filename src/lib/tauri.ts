@@ -3,7 +3,7 @@ import { load, Store } from '@tauri-apps/plugin-store';
 import { isPermissionGranted, requestPermission } from '@tauri-apps/plugin-notification';
 import { check, Update } from '@tauri-apps/plugin-updater';
 import { listen, UnlistenFn } from '@tauri-apps/api/event';
-import type { Destination, SessionInfo, SessionItem, BackupProgress, BackupComplete, SessionConfig } from './types';
+import type { Destination, SessionInfo, SessionItem, BackupProgress, BackupComplete, SessionConfig, DestinationRelocated, ProbeResult, BackupPreview, QuarantineEntry, DestinationScan, PrunePlan, PruneResult, SnapshotInfo, RepoSnapshotSummary, RestoreStats, RepositoryCheck, ArchiveSummary, ArchiveRestorePlan, SessionExport, SessionArchiveCheck, EncryptionSettings, EncryptedBackupCheck, RemoteDestination, RemoteBackend, ConfigRemote, AuditReport, AuditProgress, DrillResult, ParityInfo, ParityReport, ParityProgress } from './types';
 
 // Session commands
 export async function getCaptureOneSession(): Promise<SessionInfo> {
@@ -18,7 +18,8 @@ export async function loadSessionConfig(sessionPath: string, sessionName: string
   return invoke('load_session_config', { sessionPath, sessionName });
 }
 
-export async function saveSessionConfig(sessionPath: string, sessionName: string, config: SessionConfig): Promise<void> {
+/** Resolves to the machine whose changes were merged in, if another one saved since the config was loaded. */
+export async function saveSessionConfig(sessionPath: string, sessionName: string, config: SessionConfig): Promise<string | null> {
   return invoke('save_session_config', { sessionPath, sessionName, config });
 }
 
@@ -27,14 +28,155 @@ export async function openFolderPicker(): Promise<string | null> {
   return invoke('open_folder_picker');
 }
 
-export async function parseDestination(path: string): Promise<Destination> {
-  return invoke('parse_destination', { path });
+export async function parseDestination(path: string, existingIds: number[] = []): Promise<Destination> {
+  return invoke('parse_destination', { path, existingIds });
 }
 
-export async function deleteBackupFolder(destinationPath: string, sessionName: string): Promise<void> {
+export async function deleteBackupFolder(destinationPath: string, sessionName: string): Promise<QuarantineEntry> {
   return invoke('delete_backup_folder', { destinationPath, sessionName });
 }
 
+export async function listQuarantine(destinationPath: string): Promise<QuarantineEntry[]> {
+  return invoke('list_quarantine', { destinationPath });
+}
+
+export async function restoreQuarantined(destinationPath: string, entryId: string): Promise<string> {
+  return invoke('restore_quarantined', { destinationPath, entryId });
+}
+
+export async function purgeQuarantine(destinationPath: string, entryId?: string): Promise<number> {
+  return invoke('purge_quarantine', { destinationPath, entryId: entryId ?? null });
+}
+
+export async function scanDestination(destinationPath: string, refresh = false): Promise<DestinationScan> {
+  return invoke('scan_destination', { destinationPath, refresh });
+}
+
+/** Uses the retention policy saved with the destination in the session config. */
+export async function planPrune(sessionPath: string, sessionName: string, destinationId: number): Promise<PrunePlan> {
+  return invoke('plan_prune', { sessionPath, sessionName, destinationId });
+}
+
+export async function applyPrune(
+  sessionPath: string,
+  sessionName: string,
+  destinationId: number,
+  sessionPaths: string[]
+): Promise<PruneResult> {
+  return invoke('apply_prune', { sessionPath, sessionName, destinationId, sessionPaths });
+}
+
+export async function listSnapshots(destinationPath: string, sessionName: string): Promise<SnapshotInfo[]> {
+  return invoke('list_snapshots', { destinationPath, sessionName });
+}
+
+export async function listRepositorySnapshots(destinationPath: string, sessionName?: string): Promise<RepoSnapshotSummary[]> {
+  return invoke('list_repository_snapshots', { destinationPath, sessionName: sessionName ?? null });
+}
+
+export async function restoreRepositorySnapshot(
+  destinationPath: string,
+  snapshotId: string,
+  targetPath: string,
+  progressId: number
+): Promise<RestoreStats> {
+  return invoke('restore_repository_snapshot', { destinationPath, snapshotId, targetPath, progressId });
+}
+
+export async function checkRepository(destinationPath: string, readData = false): Promise<RepositoryCheck> {
+  return invoke('check_repository', { destinationPath, readData });
+}
+
+export async function listArchives(destinationPath: string, sessionName: string): Promise<ArchiveSummary[]> {
+  return invoke('list_archives', { destinationPath, sessionName });
+}
+
+export async function planArchiveRestore(catalogPath: string, searchPaths: string[], files?: string[]): Promise<ArchiveRestorePlan> {
+  return invoke('plan_archive_restore', { catalogPath, searchPaths, files: files ?? null });
+}
+
+export async function restoreArchive(
+  catalogPath: string,
+  searchPaths: string[],
+  targetPath: string,
+  progressId: number,
+  files?: string[]
+): Promise<RestoreStats> {
+  return invoke('restore_archive', { catalogPath, searchPaths, targetPath, files: files ?? null, progressId });
+}
+
+export async function exportSessionArchive(
+  sessionPath: string,
+  sessionName: string,
+  selectedPaths: string[],
+  outputPath: string,
+  progressId: number
+): Promise<SessionExport> {
+  return invoke('export_session_archive', {
+    request: {
+      session_path: sessionPath,
+      session_name: sessionName,
+      selected_paths: selectedPaths,
+      output_path: outputPath,
+      progress_id: progressId,
+    }
+  });
+}
+
+export async function verifySessionArchive(archivePath: string): Promise<SessionArchiveCheck> {
+  return invoke('verify_session_archive', { archivePath });
+}
+
+export async function extractSessionArchive(archivePath: string, targetPath: string): Promise<RestoreStats> {
+  return invoke('extract_session_archive', { archivePath, targetPath });
+}
+
+export async function setEncryptionPassphrase(passphrase: string, keyId?: string): Promise<string> {
+  return invoke('set_encryption_passphrase', { keyId: keyId ?? null, passphrase });
+}
+
+export async function hasEncryptionPassphrase(keyId: string): Promise<boolean> {
+  return invoke('has_encryption_passphrase', { keyId });
+}
+
+export async function forgetEncryptionPassphrase(keyId: string): Promise<void> {
+  return invoke('forget_encryption_passphrase', { keyId });
+}
+
+export async function restoreEncryptedBackup(destinationPath: string, sessionName: string, encryption: EncryptionSettings, targetPath: string): Promise<RestoreStats> {
+  return invoke('restore_encrypted_backup', { destinationPath, sessionName, encryption, targetPath });
+}
+
+export async function verifyEncryptedBackup(
+  sessionPath: string,
+  selectedPaths: string[],
+  destinationPath: string,
+  sessionName: string,
+  encryption: EncryptionSettings
+): Promise<EncryptedBackupCheck> {
+  return invoke('verify_encrypted_backup', { sessionPath, selectedPaths, destinationPath, sessionName, encryption });
+}
+
+export async function auditDestination(destinationPath: string, sessionName?: string, samplePercent?: number): Promise<AuditReport> {
+  return invoke('audit_destination', { destinationPath, sessionName, samplePercent });
+}
+
+export async function cancelAudit(): Promise<void> {
+  return invoke('cancel_audit');
+}
+
+export async function createParity(destinationPath: string, sessionName: string, redundancyPercent: number): Promise<ParityInfo> {
+  return invoke('create_parity', { destinationPath, sessionName, redundancyPercent });
+}
+
+export async function verifyParity(destinationPath: string, sessionName: string): Promise<ParityReport> {
+  return invoke('verify_parity', { destinationPath, sessionName });
+}
+
+export async function repairFromParity(destinationPath: string, sessionName: string): Promise<ParityReport> {
+  return invoke('repair_from_parity', { destinationPath, sessionName });
+}
+
 export async function checkPathExists(path: string): Promise<boolean> {
   return invoke('check_path_exists', { path });
 }
@@ -43,6 +185,44 @@ export async function createDirectory(path: string): Promise<void> {
   return invoke('create_directory', { path });
 }
 
+export async function probeDestination(path: string): Promise<ProbeResult> {
+  return invoke('probe_destination', { path });
+}
+
+export async function addRemoteDestination(
+  remote: RemoteDestination,
+  credential?: string | null,
+  existingIds: number[] = []
+): Promise<Destination> {
+  return invoke('add_remote_destination', { remote, credential: credential ?? null, existingIds });
+}
+
+export async function probeRemoteDestination(remote: RemoteDestination): Promise<ProbeResult> {
+  return invoke('probe_remote_destination', { remote });
+}
+
+// rclone remotes
+export async function listRcloneRemotes(): Promise<ConfigRemote[]> {
+  return invoke('list_rclone_remotes');
+}
+
+export async function createRcloneRemote(
+  name: string,
+  backend: RemoteBackend,
+  options: Record<string, string>,
+  credential?: string | null
+): Promise<ConfigRemote> {
+  return invoke('create_rclone_remote', { name, backend, options, credential: credential ?? null });
+}
+
+export async function testRcloneRemote(name: string): Promise<ProbeResult> {
+  return invoke('test_rclone_remote', { name });
+}
+
+export async function deleteRcloneRemote(name: string): Promise<void> {
+  return invoke('delete_rclone_remote', { name });
+}
+
 // Backup commands
 export async function startBackup(
   sessionPath: string,
@@ -62,6 +242,36 @@ export async function startBackup(
   });
 }
 
+export async function runRestoreDrill(
+  sessionPath: string,
+  sessionName: string,
+  destinations: Destination[]
+): Promise<DrillResult[]> {
+  return invoke('run_restore_drill', {
+    request: {
+      session_path: sessionPath,
+      session_name: sessionName,
+      destinations,
+    }
+  });
+}
+
+export async function previewBackup(
+  sessionPath: string,
+  sessionName: string,
+  destinations: Destination[],
+  selectedPaths: string[],
+): Promise<BackupPreview> {
+  return invoke('preview_backup', {
+    request: {
+      session_path: sessionPath,
+      session_name: sessionName,
+      destinations,
+      selected_paths: selectedPaths,
+    }
+  });
+}
+
 export async function cancelBackup(): Promise<void> {
   return invoke('cancel_backup');
 }
@@ -79,6 +289,18 @@ export function onBackupError(callback: (error: BackupComplete) => void): Promis
   return listen<BackupComplete>('backup-error', (event) => callback(event.payload));
 }
 
+export function onAuditProgress(callback: (progress: AuditProgress) => void): Promise<UnlistenFn> {
+  return listen<AuditProgress>('audit-progress', (event) => callback(event.payload));
+}
+
+export function onParityProgress(callback: (progress: ParityProgress) => void): Promise<UnlistenFn> {
+  return listen<ParityProgress>('parity-progress', (event) => callback(event.payload));
+}
+
+export function onDestinationRelocated(callback: (relocated: DestinationRelocated) => void): Promise<UnlistenFn> {
+  return listen<DestinationRelocated>('destination-relocated', (event) => callback(event.payload));
+}
+
 export function onRefreshSession(callback: () => void): Promise<UnlistenFn> {
   return listen('refresh-session', callback);
 }
@@ -117,7 +339,11 @@ export async function sendBackupNotification(title: string, body: string): Promi
 // Disk info
 export interface DiskInfo {
   total_bytes: number;
+  free_bytes: number;
   available_bytes: number;
+  block_size: number;
+  fs_type: string | null;
+  read_only: boolean;
 }
 
 export async function getDiskInfo(path: string): Promise<DiskInfo> {
@@ -6,6 +6,75 @@ export interface Destination {
   enabled: boolean;
   has_existing_backup: boolean;
   image_count_at_last_backup?: number | null;
+  volume_id?: string | null;
+  fs_type?: string | null;
+  removable?: boolean;
+  volume_name?: string | null;
+  mode?: BackupMode;
+  snapshot_retention?: SnapshotRetention;
+  retention?: RetentionPolicy | null;
+  parity_percent?: number | null;
+  archive_volume_bytes?: number | null;
+  archive_spill_paths?: string[];
+  encryption?: EncryptionSettings | null;
+  remote?: RemoteDestination | null;
+  last_synced?: string | null;
+  last_attempt?: BackupAttempt | null;
+  last_success?: BackupAttempt | null;
+  last_drill?: RestoreDrill | null;
+  last_passed_drill?: string | null;
+}
+
+export type BackupMode = 'mirror' | 'snapshots' | 'repository' | 'archive';
+
+export interface EncryptionSettings {
+  key_id: string;
+  encrypt_names: boolean;
+}
+
+export type RemoteBackend = 'sftp' | 's3' | 'webdav';
+
+export interface RemoteDestination {
+  backend: RemoteBackend;
+  options: Record<string, string>;
+  root: string;
+  credential_id?: string | null;
+}
+
+export interface ConfigRemote {
+  name: string;
+  backend: RemoteBackend;
+  options: Record<string, string>;
+  has_credential: boolean;
+}
+
+export interface SnapshotRetention {
+  keep_last?: number | null;
+  keep_within_days?: number | null;
+}
+
+export interface BackupAttempt {
+  started_at: string;
+  finished_at: string;
+  outcome: 'success' | 'failed' | 'cancelled';
+  files: number;
+  bytes: number;
+  image_count: number | null;
+  error: string | null;
+}
+
+export interface RestoreDrill {
+  drilled_at: string;
+  passed: boolean;
+  files_checked: number;
+  failures: string[];
+  error: string | null;
+}
+
+export interface DrillResult {
+  destination_id: number;
+  drill: RestoreDrill | null;
+  not_run: string | null;
 }
 
 export interface SessionConfig {
@@ -14,6 +83,8 @@ export interface SessionConfig {
   image_count_at_last_backup?: number | null;
   selected_paths: string[];
   destinations: Destination[];
+  revision?: number;
+  written_by?: string | null;
 }
 
 export interface SessionInfo {
@@ -47,6 +118,56 @@ export interface BackupComplete {
   image_count?: number | null;
 }
 
+export interface DestinationRelocated {
+  destination_id: number;
+  path: string;
+}
+
+export interface ProbeResult {
+  status: 'reachable' | 'read_only' | 'slow' | 'unreachable';
+  latency_ms: number;
+  error: string | null;
+}
+
+export interface CompatFinding {
+  kind: 'file_too_large' | 'invalid_name' | 'reserved_name';
+  severity: 'warning' | 'error';
+  path: string;
+  detail: string;
+}
+
+export interface CompatReport {
+  fs_type: string | null;
+  findings: CompatFinding[];
+  total_findings: number;
+  rclone_options: string[];
+  name_encoding: string | null;
+}
+
+export interface DestinationPreview {
+  destination_id: number;
+  path: string;
+  compatibility: CompatReport | null;
+  error: string | null;
+}
+
+export interface BackupPreview {
+  destinations: DestinationPreview[];
+}
+
+export interface QuarantineEntry {
+  id: string;
+  session_name: string;
+  original_path: string;
+  deleted_at: number;
+  expires_at: number;
+}
+
+export type FsCommandError =
+  | { kind: 'path_not_allowed'; path: string }
+  | { kind: 'invalid_path'; path: string; reason: string }
+  | { kind: 'failed'; message: string };
+
 export interface AppState {
   view: 'main' | 'prefs';
   backupState: 'idle' | 'running' | 'success' | 'error';
@@ -58,3 +179,194 @@ export interface AppState {
   notificationsEnabled: boolean;
   session: SessionInfo | null;
 }
+
+export interface ScannedSession {
+  name: string;
+  path: string;
+  size_bytes: number;
+  file_count: number;
+  last_backup: string | null;
+  image_count: number | null;
+  source_host: string | null;
+  source_path: string | null;
+  has_receipt: boolean;
+}
+
+export interface DestinationScan {
+  destination_path: string;
+  sessions: ScannedSession[];
+  cached_count: number;
+}
+
+export interface RetentionPolicy {
+  keep_within_days?: number | null;
+  min_free_bytes?: number | null;
+}
+
+export interface PruneCandidate {
+  session: ScannedSession;
+  reason: string;
+}
+
+export interface PrunePlan {
+  destination_path: string;
+  available_bytes: number;
+  projected_available_bytes: number;
+  prune: PruneCandidate[];
+  keep: PruneCandidate[];
+}
+
+export interface PruneResult {
+  pruned: string[];
+  freed_bytes: number;
+  errors: string[];
+}
+
+export interface SnapshotInfo {
+  name: string;
+  path: string;
+  created_at: string | null;
+  image_count: number | null;
+  files_transferred: number | null;
+  bytes_transferred: number | null;
+}
+
+export interface RepoSnapshotSummary {
+  id: string;
+  session_name: string;
+  source_path: string;
+  source_host: string;
+  created_at: string;
+  image_count: number | null;
+  file_count: number;
+  total_bytes: number;
+}
+
+export interface RestoreStats {
+  path: string;
+  files: number;
+  bytes: number;
+}
+
+export interface ArchiveSummary {
+  id: string;
+  catalog_path: string;
+  created_at: string;
+  image_count: number | null;
+  volumes: number;
+  files: number;
+  total_bytes: number;
+}
+
+export interface VolumeStatus {
+  number: number;
+  name: string;
+  size: number;
+  archives_dir: string | null;
+  location: string | null;
+}
+
+export interface ArchiveRestorePlan {
+  archive_id: string;
+  volumes: VolumeStatus[];
+  missing: number[];
+  files: number;
+  bytes: number;
+}
+
+export interface SessionExport {
+  archive_path: string;
+  checksum_path: string;
+  hash: string;
+  files: number;
+  bytes: number;
+  archive_bytes: number;
+}
+
+export interface SessionArchiveCheck {
+  archive_path: string;
+  session_name: string | null;
+  checksum_matches: boolean | null;
+  files_checked: number;
+  corrupted: string[];
+  missing: string[];
+  unexpected: string[];
+  error: string | null;
+  passed: boolean;
+}
+
+export interface EncryptedBackupCheck {
+  matched: number;
+  differ: string[];
+  missing: string[];
+  unexpected: string[];
+  errors: string[];
+  passed: boolean;
+}
+
+export interface RepositoryCheck {
+  snapshots: number;
+  referenced_chunks: number;
+  unreferenced_chunks: number;
+  missing_count: number;
+  corrupt_count: number;
+  read_data: boolean;
+  missing: string[];
+  corrupt: string[];
+  errors: string[];
+}
+
+export interface SessionAudit {
+  session_name: string;
+  path: string;
+  files_checked: number;
+  total_files: number;
+  corrupted: string[];
+  missing: string[];
+  unexpected: string[];
+  error: string | null;
+  passed: boolean;
+}
+
+export interface AuditReport {
+  destination_path: string;
+  sample_percent: number;
+  sessions: SessionAudit[];
+  passed: boolean;
+}
+
+export interface AuditProgress {
+  session_name: string;
+  percent: number;
+  files_checked: number;
+  total_files: number;
+}
+
+export interface ParityInfo {
+  path: string;
+  files: number;
+  data_blocks: number;
+  parity_blocks: number;
+  parity_bytes: number;
+  redundancy_percent: number;
+}
+
+export interface ParityReport {
+  path: string;
+  created_at: string;
+  redundancy_percent: number;
+  data_blocks: number;
+  parity_blocks: number;
+  damaged_blocks: number;
+  damaged_parity_blocks: number;
+  damaged_files: string[];
+  changed_files: string[];
+  repairable: boolean;
+  repaired_files: string[];
+}
+
+export interface ParityProgress {
+  session_name: string;
+  operation: 'create' | 'verify' | 'repair';
+  percent: number;
+}